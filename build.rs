@@ -0,0 +1,13 @@
+fn main() {
+    // detectors of your own are included from outside of the source tree
+    #[cfg(feature = "detectors")]
+    {
+        println!("cargo:rerun-if-env-changed=WAILA_DETECTORS");
+        if let Some(path) = std::env::var_os("WAILA_DETECTORS") {
+            println!("cargo:rerun-if-changed={}", path.to_string_lossy());
+        }
+    }
+    // the gRPC service is generated from its proto definitions, which needs protoc
+    #[cfg(feature = "grpc")]
+    tonic_build::compile_protos("proto/waila.proto").expect("compile proto/waila.proto");
+}
@@ -0,0 +1,39 @@
+// An example of detectors built into waila-cli with the `detectors` feature:
+//
+//     WAILA_DETECTORS=$PWD/detectors/voucher.rs cargo build --features detectors
+//
+// The file is included in the binary and defines `register`, which is called once at
+// startup and registers its detectors with `waila_cli::detector::register`.
+
+use serde_json::{json, Map, Value};
+use waila_cli::detector::Detector;
+use waila_cli::{Error, Result};
+
+/// Voucher codes of the form `VOUCHER-<digits>`
+struct Voucher;
+
+impl Detector for Voucher {
+    fn kind(&self) -> &'static str {
+        "Voucher"
+    }
+
+    fn matches(&self, s: &str) -> bool {
+        s.starts_with("VOUCHER-")
+    }
+
+    fn parse(&self, s: &str) -> Result<Map<String, Value>> {
+        let code = &s["VOUCHER-".len()..];
+        if code.is_empty() || !code.bytes().all(|c| c.is_ascii_digit()) {
+            return Err(Error::InvalidPart(format!("not a voucher code: {s}")));
+        }
+        let mut map = Map::new();
+        map.insert("code".to_string(), json!(code));
+
+        Ok(map)
+    }
+}
+
+/// Register the detectors of this file
+pub fn register() {
+    waila_cli::detector::register(&Voucher);
+}
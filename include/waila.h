@@ -0,0 +1,28 @@
+/* C bindings of waila-cli, built with `cargo build --release --features ffi`
+ * into libwaila_cli. Strings are NUL terminated UTF-8. Returned strings are
+ * owned by the caller and released with waila_free. */
+
+#ifndef WAILA_H
+#define WAILA_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* The json the command line prints for query, or {"error": "..."} */
+char *waila_parse(const char *query);
+
+/* Like waila_parse, with every field, null if absent */
+char *waila_parse_all(const char *query);
+
+/* The kind of query, e.g. OnChain, or NULL if it is not recognized */
+char *waila_kind(const char *query);
+
+/* Release a string returned by the functions above */
+void waila_free(char *s);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
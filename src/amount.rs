@@ -0,0 +1,181 @@
+//! Amount formatting and parsing. Amounts are carried as millisatoshis so
+//! lightning amounts keep their full precision.
+
+use crate::{Error, Result};
+use bitcoin::{Amount, Denomination};
+use serde_json::{json, Map, Value};
+
+/// Units of exact conversions and the millisatoshis in one of each
+const UNITS: [(&str, u64); 6] = [
+    ("msat", 1),
+    ("sat", 1_000),
+    ("ubtc", 100_000),
+    ("bits", 100_000),
+    ("mbtc", 100_000_000),
+    ("btc", 100_000_000_000),
+];
+
+/// Whole satoshis of a millisatoshi amount, rounded down
+pub fn msat_to_amount(msat: u64) -> Amount {
+    Amount::from_sat(msat / 1_000)
+}
+
+/// Express an amount as a string with denomination. Millisatoshis are exact,
+/// other units are rounded down to the satoshi.
+pub fn format_amount(msat: u64, unit: Denomination) -> String {
+    match unit {
+        Denomination::MilliSatoshi => format!("{msat} {unit}"),
+        _ => msat_to_amount(msat).to_string_with_denomination(unit),
+    }
+}
+
+/// Express an amount as a json number in the given unit. Whole units
+/// are integers, fractional ones are floats.
+pub fn numeric_amount(msat: u64, unit: Denomination) -> Value {
+    match unit {
+        Denomination::Satoshi => json!(msat / 1_000),
+        Denomination::MilliSatoshi => json!(msat),
+        _ => json!(msat_to_amount(msat).to_float_in(unit)),
+    }
+}
+
+/// Express an amount in several denominations at once
+pub fn amounts(msat: u64) -> Value {
+    json!({
+        "btc": msat_to_amount(msat).to_string_in(Denomination::Bitcoin),
+        "sat": msat / 1_000,
+        "msat": msat,
+    })
+}
+
+/// Parse an amount with optional denomination suffix, e.g. "1000", "1000 sat",
+/// "0.5mbtc" or "1500msat". A bare number is taken as satoshis.
+pub fn parse_msat(s: &str) -> Result<u64> {
+    let s = s.trim();
+    let split = s.find(|c: char| c.is_ascii_alphabetic()).unwrap_or(s.len());
+    let (num, unit) = s.split_at(split);
+    let num = num.trim();
+    let invalid = || Error::InvalidAmount(s.to_string());
+
+    match unit.trim().to_ascii_lowercase().as_str() {
+        "msat" | "msats" => num.parse::<u64>().map_err(|_| invalid()),
+        unit => {
+            let denom = match unit {
+                "" | "sat" | "sats" | "satoshi" => Denomination::Satoshi,
+                "ubtc" | "bit" | "bits" => Denomination::MicroBitcoin,
+                "mbtc" => Denomination::MilliBitcoin,
+                "btc" => Denomination::Bitcoin,
+                _ => return Err(invalid()),
+            };
+            let amt = Amount::from_str_in(num, denom).map_err(|_| invalid())?;
+            amt.to_sat().checked_mul(1_000).ok_or_else(invalid)
+        }
+    }
+}
+
+/// An amount in `unit`, msat, sat, ubtc, bits, mbtc or btc, as an exact decimal without trailing
+/// zeros, e.g. "0.015"
+pub fn exact_in(msat: u64, unit: &str) -> Result<String> {
+    let Some((_, per_unit)) = UNITS
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(unit))
+    else {
+        return Err(Error::InvalidAmount(format!(
+            "unknown unit {unit}, expected msat, sat, ubtc, bits, mbtc or btc"
+        )));
+    };
+    let (whole, fraction) = (msat / per_unit, msat % per_unit);
+    if fraction == 0 {
+        return Ok(whole.to_string());
+    }
+    let digits = per_unit.ilog10() as usize;
+    let fraction = format!("{fraction:0digits$}");
+
+    Ok(format!("{whole}.{}", fraction.trim_end_matches('0')))
+}
+
+/// An amount in each of `units`, keyed by unit, as exact decimals
+pub fn convert(msat: u64, units: &[String]) -> Result<Value> {
+    let mut map = Map::new();
+    for unit in units {
+        map.insert(unit.to_ascii_lowercase(), json!(exact_in(msat, unit)?));
+    }
+
+    Ok(Value::Object(map))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 21 million bitcoin in millisatoshis
+    const MAX_MONEY_MSAT: u64 = 21_000_000 * 100_000_000_000;
+
+    #[test]
+    fn exact_in_keeps_every_digit() {
+        assert_eq!(exact_in(1_500_000, "mbtc").unwrap(), "0.015");
+        assert_eq!(exact_in(1_999, "sat").unwrap(), "1.999");
+        assert_eq!(exact_in(1_000, "SAT").unwrap(), "1");
+        assert_eq!(exact_in(150_000, "bits").unwrap(), "1.5");
+        // a millisatoshi short of and at a whole unit
+        assert_eq!(exact_in(99_999_999_999, "btc").unwrap(), "0.99999999999");
+        assert_eq!(exact_in(100_000_000_000, "btc").unwrap(), "1");
+        assert_eq!(exact_in(1, "btc").unwrap(), "0.00000000001");
+        assert_eq!(exact_in(0, "btc").unwrap(), "0");
+    }
+
+    #[test]
+    fn exact_in_large_amounts() {
+        assert_eq!(exact_in(MAX_MONEY_MSAT, "btc").unwrap(), "21000000");
+        assert_eq!(
+            exact_in(MAX_MONEY_MSAT + 1, "btc").unwrap(),
+            "21000000.00000000001"
+        );
+        assert_eq!(exact_in(u64::MAX, "btc").unwrap(), "184467440.73709551615");
+        assert_eq!(exact_in(u64::MAX, "msat").unwrap(), u64::MAX.to_string());
+    }
+
+    #[test]
+    fn exact_in_rejects_unknown_units() {
+        assert!(exact_in(1_000, "eur").is_err());
+        assert!(exact_in(1_000, "").is_err());
+    }
+
+    #[test]
+    fn parse_msat_units() {
+        assert_eq!(parse_msat("1000").unwrap(), 1_000_000);
+        assert_eq!(parse_msat(" 1000 SATS ").unwrap(), 1_000_000);
+        assert_eq!(parse_msat("1500msat").unwrap(), 1_500);
+        assert_eq!(parse_msat("0.5mbtc").unwrap(), 50_000_000);
+        assert_eq!(parse_msat("5 bits").unwrap(), 500_000);
+        assert_eq!(parse_msat("1 ubtc").unwrap(), 100_000);
+        assert_eq!(parse_msat("0.00000001 btc").unwrap(), 1_000);
+    }
+
+    #[test]
+    fn parse_msat_rejects_sub_unit_precision() {
+        // below a satoshi only msat can go, and not below a millisatoshi
+        assert!(parse_msat("0.5 sat").is_err());
+        assert!(parse_msat("0.000000015 btc").is_err());
+        assert!(parse_msat("1.5 msat").is_err());
+    }
+
+    #[test]
+    fn parse_msat_near_the_limits() {
+        assert_eq!(parse_msat("21000000 btc").unwrap(), MAX_MONEY_MSAT);
+        assert_eq!(
+            parse_msat("184467440 btc").unwrap(),
+            18_446_744_000_000_000_000
+        );
+        // more millisatoshis than a u64 holds
+        assert!(parse_msat("184467441 btc").is_err());
+        assert!(parse_msat("18446744073709551616 msat").is_err());
+    }
+
+    #[test]
+    fn parse_msat_rejects_malformed_input() {
+        for s in ["", "abc", "1 eur", "-1", "1e3", "1.2.3 sat", "sat 1000"] {
+            assert!(parse_msat(s).is_err(), "{s}");
+        }
+    }
+}
@@ -0,0 +1,287 @@
+//! BIP-353 payment instructions published in DNS, validated with DNSSEC
+
+use crate::{Error, Result};
+use serde_json::{json, Value};
+use std::fmt;
+use std::net::SocketAddr;
+#[cfg(feature = "network")]
+use {
+    crate::{cache, net},
+    dnssec_prover::query::{build_txt_proof, ProofBuilder, QueryBuf},
+    dnssec_prover::rr::{Name, StaticRecord, Txt, RR},
+    dnssec_prover::ser::parse_rr_stream,
+    dnssec_prover::validation::verify_rr_stream,
+    std::io::Read,
+    std::time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+    tracing::info,
+};
+
+/// Default DNS server, queried over TCP
+pub const DEFAULT_DNS_SERVER: &str = "8.8.8.8:53";
+
+/// Largest DNS message read from a DNS-over-HTTPS resolver
+#[cfg(feature = "network")]
+const MAX_MESSAGE: u64 = 64 * 1024;
+
+/// Where DNS queries are sent
+#[derive(Debug, Clone)]
+pub enum Resolver {
+    /// A DNS server, queried over TCP
+    Tcp(SocketAddr),
+    /// A DNS-over-HTTPS resolver (RFC 8484), e.g. `https://dns.google/dns-query`
+    Https(String),
+}
+
+impl fmt::Display for Resolver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Resolver::Tcp(server) => write!(f, "{server}"),
+            Resolver::Https(url) => write!(f, "{url}"),
+        }
+    }
+}
+
+/// Payment instructions resolved for a human readable name
+#[derive(Debug, Clone)]
+pub struct Resolution {
+    /// The name as given, `user@domain`
+    pub name: String,
+    /// The DNS name holding the TXT record
+    pub record: String,
+    /// The BIP-21 URI found in the record
+    pub uri: String,
+    /// Unix time from which the DNSSEC signatures are valid
+    pub valid_from: u64,
+    /// Unix time at which the DNSSEC signatures expire
+    pub expires: u64,
+    /// Unix time at which the proof was validated
+    pub validated_at: u64,
+}
+
+impl Resolution {
+    /// Summary of the resolution and its DNSSEC validation
+    pub fn to_json(&self) -> Value {
+        json!({
+            "name": self.name,
+            "record": self.record,
+            "uri": self.uri,
+            "dnssec": {
+                "validated": in_window(self.valid_from, self.expires, self.validated_at),
+                "validated_at": self.validated_at,
+                "valid_from": self.valid_from,
+                "expires": self.expires,
+            },
+        })
+    }
+}
+
+/// The DNS name of the payment instructions for `user@domain`
+pub fn record_name(name: &str) -> Result<String> {
+    let Some((user, domain)) = name.split_once('@') else {
+        return Err(Error::Resolve(format!("not a user@domain name: {name}")));
+    };
+    let domain = domain.trim_end_matches('.');
+
+    Ok(format!("{user}.user._bitcoin-payment.{domain}."))
+}
+
+/// Whether signatures valid from `valid_from` until `expires` are valid at `now`
+fn in_window(valid_from: u64, expires: u64, now: u64) -> bool {
+    valid_from <= now && now <= expires
+}
+
+/// Look up the payment instructions for `user@domain`, proving the answer
+/// with a DNSSEC chain of trust to the root
+#[cfg(feature = "network")]
+pub fn resolve(name: &str, resolver: &Resolver) -> Result<Resolution> {
+    let record = record_name(name)?;
+    let dns_name = Name::try_from(record.as_str())
+        .map_err(|_| Error::Resolve(format!("invalid DNS name: {record}")))?;
+
+    let proof = query_proof(&dns_name, &record, resolver)?;
+
+    validate(name, &proof, now())
+}
+
+/// Validate the DNSSEC `proof` of the payment instructions for `name` at the
+/// unix time `now`, rejecting signatures that are not yet or no longer valid
+#[cfg(feature = "network")]
+fn validate(name: &str, proof: &[u8], now: u64) -> Result<Resolution> {
+    let record = record_name(name)?;
+    let dns_name = Name::try_from(record.as_str())
+        .map_err(|_| Error::Resolve(format!("invalid DNS name: {record}")))?;
+    let rrs =
+        parse_rr_stream(proof).map_err(|_| Error::Resolve("malformed DNSSEC proof".to_string()))?;
+    let verified = verify_rr_stream(&rrs)
+        .map_err(|e| Error::Resolve(format!("DNSSEC validation failed: {e:?}")))?;
+    if !in_window(verified.valid_from, verified.expires, now) {
+        return Err(Error::Resolve(format!(
+            "DNSSEC signatures are only valid from {} until {}",
+            verified.valid_from, verified.expires
+        )));
+    }
+
+    let mut uris = verified
+        .resolve_name(&dns_name)
+        .into_iter()
+        .filter_map(|rr| match rr {
+            RR::Txt(txt) => Some(String::from_utf8_lossy(&txt.data).into_owned()),
+            _ => None,
+        })
+        .filter(|data| data.to_ascii_lowercase().starts_with("bitcoin:"));
+    let Some(uri) = uris.next() else {
+        return Err(Error::Resolve(format!(
+            "no payment instructions at {record}"
+        )));
+    };
+    if uris.next().is_some() {
+        return Err(Error::Resolve(format!(
+            "more than one set of payment instructions at {record}"
+        )));
+    }
+
+    Ok(Resolution {
+        name: name.to_string(),
+        record,
+        uri,
+        valid_from: verified.valid_from,
+        expires: verified.expires,
+        validated_at: now,
+    })
+}
+
+/// Seconds since the unix epoch
+#[cfg(feature = "network")]
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// The DNSSEC proof of the TXT records at `dns_name`, from the cache if it was
+/// fetched within the DNS TTL. A cached proof is validated again like a fresh one,
+/// including the validity window of its signatures.
+#[cfg(feature = "network")]
+fn query_proof(dns_name: &Name, record: &str, resolver: &Resolver) -> Result<Vec<u8>> {
+    net::ensure_online()?;
+    let key = format!("DNS {resolver} {record}");
+    // the TTL of the answer, written in front of the proof
+    let cached = cache::get_with_age(&key).and_then(|(entry, age)| {
+        let ttl = u32::from_be_bytes(entry.get(..4)?.try_into().ok()?);
+        (age <= Duration::from_secs(ttl.into())).then(|| entry[4..].to_vec())
+    });
+    if let Some(proof) = cached {
+        return Ok(proof);
+    }
+
+    let (proof, ttl) = match resolver {
+        Resolver::Tcp(server) => {
+            let start = Instant::now();
+            let proof = build_txt_proof(net::tcp_endpoint(*server)?, dns_name);
+            info!(%server, record, elapsed = ?start.elapsed(), ok = proof.is_ok(), "DNS query");
+            proof.map_err(|e| Error::Resolve(format!("DNS query failed: {e}")))?
+        }
+        Resolver::Https(url) => build_txt_proof_https(url, dns_name)?,
+    };
+    cache::put(&key, &[&ttl.to_be_bytes()[..], &proof].concat());
+
+    Ok(proof)
+}
+
+/// Build the DNSSEC proof of the TXT records at `dns_name`, sending each query
+/// to the DNS-over-HTTPS resolver at `url`
+#[cfg(feature = "network")]
+fn build_txt_proof_https(url: &str, dns_name: &Name) -> Result<(Vec<u8>, u32)> {
+    let (mut builder, query) = ProofBuilder::new(dns_name, Txt::TYPE);
+    let mut pending = vec![query];
+    while let Some(query) = pending.pop() {
+        let resp = query_https(url, &query)?;
+        let queries = builder
+            .process_response(&resp)
+            .map_err(|()| Error::Resolve("unexpected DNS answer".to_string()))?;
+        pending.extend(queries);
+    }
+
+    builder
+        .finish_proof()
+        .map_err(|()| Error::Resolve("incomplete DNSSEC proof".to_string()))
+}
+
+/// Send one query to a DNS-over-HTTPS resolver with POST. Queries are built for
+/// TCP and start with a two byte length, which is left out over HTTPS.
+#[cfg(feature = "network")]
+fn query_https(url: &str, query: &QueryBuf) -> Result<QueryBuf> {
+    let req = net::request("POST", url)?;
+    let start = Instant::now();
+    let resp = req
+        .set("Content-Type", "application/dns-message")
+        .set("Accept", "application/dns-message")
+        .send_bytes(&query[2..]);
+    info!(url, elapsed = ?start.elapsed(), ok = resp.is_ok(), "DNS-over-HTTPS query");
+    let resp = resp?;
+    let mut message = vec![];
+    resp.into_reader()
+        .take(MAX_MESSAGE)
+        .read_to_end(&mut message)?;
+
+    let mut buf = QueryBuf::new_zeroed(0);
+    buf.extend_from_slice(&message);
+
+    Ok(buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resolution(validated_at: u64) -> Resolution {
+        Resolution {
+            name: "matt@mattcorallo.com".to_string(),
+            record: record_name("matt@mattcorallo.com").unwrap(),
+            uri: "bitcoin:?lno=lno1qsgq".to_string(),
+            valid_from: 1_700_000_000,
+            expires: 1_700_086_400,
+            validated_at,
+        }
+    }
+
+    #[test]
+    fn record_names() {
+        assert_eq!(
+            record_name("matt@mattcorallo.com.").unwrap(),
+            "matt.user._bitcoin-payment.mattcorallo.com."
+        );
+        assert!(record_name("mattcorallo.com").is_err());
+    }
+
+    #[test]
+    fn signatures_are_valid_within_their_window() {
+        assert!(!in_window(100, 200, 99));
+        assert!(in_window(100, 200, 100));
+        assert!(in_window(100, 200, 200));
+        assert!(!in_window(100, 200, 201));
+    }
+
+    #[test]
+    fn validation_reports_the_window() {
+        let dnssec = |at| resolution(at).to_json()["dnssec"].clone();
+        assert_eq!(dnssec(1_700_000_000)["validated"], true);
+        assert_eq!(dnssec(1_700_086_401)["validated"], false);
+        assert_eq!(dnssec(1_699_999_999)["validated"], false);
+        assert_eq!(dnssec(1_700_000_000)["validated_at"], 1_700_000_000);
+    }
+
+    #[cfg(feature = "network")]
+    #[test]
+    fn unproven_answers_are_rejected() {
+        let name = "matt@mattcorallo.com";
+        // an empty proof and one cut off inside its first record
+        let proofs: [&[u8]; 2] = [&[], &[0x04, b'm', b'a', b't', b't']];
+        for proof in proofs {
+            for now in [0, 1_700_000_000, u64::MAX] {
+                assert!(matches!(validate(name, proof, now), Err(Error::Resolve(_))));
+            }
+        }
+    }
+}
@@ -0,0 +1,204 @@
+//! Chain lookups against a bitcoind JSON-RPC server
+
+use crate::chain::{script_pubkey, Chain};
+use crate::{net, Error, Result};
+use serde_json::{json, Value};
+use std::collections::BTreeSet;
+use std::fmt;
+use std::path::PathBuf;
+use url::Url;
+
+/// How to authenticate to bitcoind
+#[derive(Debug, Clone)]
+pub enum Auth {
+    /// The cookie file written by bitcoind, e.g. `~/.bitcoin/.cookie`
+    Cookie(PathBuf),
+    /// `rpcuser` and `rpcpassword`
+    UserPass(String, String),
+}
+
+/// Client of a bitcoind node
+#[derive(Clone)]
+pub struct Bitcoind {
+    /// The url without credentials, reported in the output
+    url: String,
+    /// The `Authorization` header. Credentials are kept out of the url, which
+    /// transport errors print.
+    authorization: String,
+}
+
+impl fmt::Debug for Bitcoind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Bitcoind")
+            .field("url", &self.url)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Bitcoind {
+    /// A client for the node at `url`, e.g. `http://127.0.0.1:8332`
+    pub fn new(url: &str, auth: &Auth) -> Result<Self> {
+        let (user, password) = match auth {
+            Auth::Cookie(path) => {
+                let cookie = std::fs::read_to_string(path)?;
+                let Some((user, password)) = cookie.trim().split_once(':') else {
+                    return Err(Error::Backend(format!(
+                        "malformed cookie file {}",
+                        path.display()
+                    )));
+                };
+                (user.to_string(), password.to_string())
+            }
+            Auth::UserPass(user, password) => (user.clone(), password.clone()),
+        };
+        let mut endpoint =
+            Url::parse(url).map_err(|e| Error::Backend(format!("invalid bitcoind url: {e}")))?;
+        // credentials given in the url are dropped, those of `auth` are used
+        if endpoint.set_username("").is_err() || endpoint.set_password(None).is_err() {
+            return Err(Error::Backend("invalid bitcoind url".to_string()));
+        }
+
+        Ok(Self {
+            url: endpoint.to_string(),
+            authorization: format!("Basic {}", base64(format!("{user}:{password}").as_bytes())),
+        })
+    }
+
+    fn call(&self, method: &str, params: Value) -> Result<Value> {
+        let req = json!({ "jsonrpc": "1.0", "id": "waila", "method": method, "params": params });
+        let resp = match net::request("POST", &self.url)?
+            .set("Authorization", &self.authorization)
+            .send_json(req)
+        {
+            Ok(resp) => resp.into_json::<Value>()?,
+            // rpc errors come with a json body as well
+            Err(ureq::Error::Status(_, resp)) => match resp.into_json::<Value>() {
+                Ok(body) => body,
+                Err(_) => {
+                    return Err(Error::Backend(format!(
+                        "{method}: bitcoind rejected the request, check the credentials"
+                    )))
+                }
+            },
+            Err(e) => return Err(e.into()),
+        };
+
+        if let Some(err) = resp.get("error").filter(|err| !err.is_null()) {
+            let msg = err["message"]
+                .as_str()
+                .map(str::to_string)
+                .unwrap_or_else(|| err.to_string());
+            return Err(Error::Backend(format!("{method}: {msg}")));
+        }
+
+        Ok(resp["result"].clone())
+    }
+}
+
+impl Chain for Bitcoind {
+    /// bitcoind keeps no address index, so the stats are those of the unspent
+    /// outputs of the address found by scanning the UTXO set. Spent outputs and the
+    /// mempool aren't seen: the unconfirmed balance is 0 and an address whose outputs
+    /// are all spent reads as unused. The script of the address is decoded by the
+    /// node with `decodescript`.
+    fn address_stats(&self, address: &str) -> Result<Value> {
+        // checks the address before the slow scan
+        let script = script_pubkey(address)?;
+        let decoded = self.call("decodescript", json!([format!("{script:x}")]))?;
+        let scan = self.call(
+            "scantxoutset",
+            json!(["start", [format!("addr({address})")]]),
+        )?;
+        let confirmed = (scan["total_amount"].as_f64().unwrap_or_default() * 100_000_000.0).round();
+        let txids: BTreeSet<&str> = scan["unspents"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|utxo| utxo["txid"].as_str())
+            .collect();
+
+        Ok(json!({
+            "bitcoind": self.url,
+            "confirmed_balance": confirmed as i64,
+            "unconfirmed_balance": 0,
+            "tx_count": txids.len(),
+            "used": !txids.is_empty(),
+            "script": {
+                "type": decoded["type"],
+                "asm": decoded["asm"],
+                "descriptor": decoded["desc"],
+            },
+        }))
+    }
+
+    fn address_history(&self, _address: &str, _n: usize) -> Result<Value> {
+        Err(Error::Backend(
+            "bitcoind keeps no address index, --history needs Esplora or Electrum".to_string(),
+        ))
+    }
+
+    fn tx_status(&self, txid: &str) -> Result<Value> {
+        // verbosity 2 includes the fee, needs -txindex for confirmed transactions
+        let tx = self.call("getrawtransaction", json!([txid, 2]))?;
+        let fee = (tx["fee"].as_f64().unwrap_or_default() * 100_000_000.0).round() as u64;
+        let vsize = tx["vsize"].as_u64().unwrap_or_default() as f64;
+        let feerate = if vsize > 0.0 {
+            (fee as f64 / vsize * 100.0).round() / 100.0
+        } else {
+            0.0
+        };
+        let confirmations = tx["confirmations"].as_u64().unwrap_or_default();
+
+        let mut out = json!({
+            "bitcoind": self.url,
+            "confirmed": confirmations > 0,
+            "block_height": null,
+            "block_hash": tx["blockhash"],
+            "confirmations": confirmations,
+            "fee": fee,
+            "feerate": feerate,
+        });
+        if let Some(hash) = tx["blockhash"].as_str() {
+            let header = self.call("getblockheader", json!([hash]))?;
+            out["block_height"] = header["height"].clone();
+        }
+
+        Ok(out)
+    }
+
+    fn suggested_feerates(&self) -> Result<Value> {
+        // estimates are in BTC/kvB
+        let rate = |target: u32| -> Result<f64> {
+            let estimate = self.call("estimatesmartfee", json!([target]))?;
+            let sat_per_vb = estimate["feerate"].as_f64().unwrap_or_default() * 100_000.0;
+            Ok((sat_per_vb * 100.0).round() / 100.0)
+        };
+
+        Ok(json!({
+            "fastest": rate(1)?,
+            "half_hour": rate(3)?,
+            "hour": rate(6)?,
+        }))
+    }
+}
+
+/// Standard base64 with padding, for the basic auth header
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &b)| n | (u32::from(b) << (16 - 8 * i)));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[((n >> (18 - 6 * i)) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+
+    out
+}
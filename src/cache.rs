@@ -0,0 +1,67 @@
+//! On-disk cache of network responses, so repeated invocations don't query
+//! the same endpoints again. Entries are kept in files named by the hash of
+//! their key and expire after a configurable time.
+
+use bitcoin::hashes::{sha256, Hash};
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use std::time::{Duration, SystemTime};
+use tracing::{debug, trace};
+
+/// Default time for which entries are used
+pub const DEFAULT_TTL: u64 = 300;
+
+/// Directory of the cache and how long entries stay fresh, unset if disabled
+static CACHE: OnceLock<(PathBuf, Duration)> = OnceLock::new();
+
+/// Enable the cache, keeping entries for `ttl`. Without `dir`, the cache lives
+/// under `$XDG_CACHE_HOME` or `~/.cache`.
+pub fn enable(dir: Option<PathBuf>, ttl: Duration) {
+    let dir = dir.or_else(|| {
+        std::env::var_os("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+            .map(|cache| cache.join("waila-cli"))
+    });
+    if let Some(dir) = dir {
+        let _ = CACHE.set((dir, ttl));
+    }
+}
+
+fn path(key: &str) -> Option<PathBuf> {
+    let (dir, _) = CACHE.get()?;
+    let hash = sha256::Hash::hash(key.as_bytes());
+
+    Some(dir.join(hash.to_string()))
+}
+
+/// The cached value of `key`, if fresher than the configured time
+pub fn get(key: &str) -> Option<Vec<u8>> {
+    get_with_age(key).map(|(value, _)| value)
+}
+
+/// The cached value of `key` and its age, if fresher than the configured time
+pub fn get_with_age(key: &str) -> Option<(Vec<u8>, Duration)> {
+    let (_, ttl) = CACHE.get()?;
+    let path = path(key)?;
+    let modified = std::fs::metadata(&path).ok()?.modified().ok()?;
+    let age = SystemTime::now().duration_since(modified).ok()?;
+    if age > *ttl {
+        trace!(key, ?age, "cache entry expired");
+        return None;
+    }
+    debug!(key, ?age, "cache hit");
+
+    Some((std::fs::read(path).ok()?, age))
+}
+
+/// Store `value` for `key`. Failures are ignored, the cache is only an optimization.
+pub fn put(key: &str, value: &[u8]) {
+    let Some(path) = path(key) else {
+        return;
+    };
+    if let Some(dir) = path.parent() {
+        let _ = std::fs::create_dir_all(dir);
+    }
+    let _ = std::fs::write(path, value);
+}
@@ -0,0 +1,90 @@
+//! The interpretations of strings that are valid as more than one kind, ranked
+//! by how likely each is meant
+
+use bitcoin::secp256k1::XOnlyPublicKey;
+use serde::Serialize;
+use std::str::FromStr;
+
+/// A way to read the input
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Candidate {
+    /// A kind of the output, or `Bip353Name` and `Nip05` for names that are not parsed
+    pub kind: &'static str,
+    /// Between 0 and 1, the confidences of all candidates sum to 1
+    pub confidence: f64,
+    pub reason: &'static str,
+}
+
+impl Candidate {
+    fn new(kind: &'static str, weight: f64, reason: &'static str) -> Self {
+        Self {
+            kind,
+            confidence: weight,
+            reason,
+        }
+    }
+}
+
+/// All readings of `s`, most likely first. `kind` is the one picked by the parser,
+/// the only candidate if `s` is not ambiguous.
+pub fn interpret(s: &str, kind: &'static str) -> Vec<Candidate> {
+    let mut candidates = if s.strip_prefix('₿').is_some_and(is_name) {
+        vec![
+            Candidate::new("Bip353Name", 0.9, "the ₿ prefix marks a BIP-353 name"),
+            Candidate::new("LnAddress", 0.1, "the name may also serve LNURL-pay"),
+        ]
+    } else if is_name(s) {
+        let nip05 = if s.starts_with("_@") { 0.6 } else { 0.2 };
+        vec![
+            Candidate::new(
+                "LnAddress",
+                0.5,
+                "user@domain is most often a lightning address",
+            ),
+            Candidate::new("Bip353Name", 0.3, "a BIP-353 name may be given without ₿"),
+            Candidate::new("Nip05", nip05, "a NIP-05 identifier of a nostr user"),
+        ]
+    } else if s.len() == 64 && s.bytes().all(|c| c.is_ascii_hexdigit()) {
+        let mut candidates = vec![Candidate::new(
+            "Txid",
+            0.6,
+            "32 bytes of hex are most often a transaction id",
+        )];
+        if XOnlyPublicKey::from_str(s).is_ok() {
+            candidates.push(Candidate::new(
+                "NostrValue",
+                0.4,
+                "a valid x-only public key, read as nostr with --nostr",
+            ));
+        }
+        candidates
+    } else {
+        vec![]
+    };
+    if candidates.len() < 2 {
+        return vec![Candidate::new(kind, 1.0, "the only valid reading")];
+    }
+
+    // weights are relative, scale them to sum to 1
+    let total: f64 = candidates.iter().map(|c| c.confidence).sum();
+    for c in &mut candidates {
+        c.confidence = (c.confidence / total * 100.0).round() / 100.0;
+    }
+    candidates.sort_by(|a, b| b.confidence.total_cmp(&a.confidence));
+
+    candidates
+}
+
+/// Whether `s` looks like user@domain
+fn is_name(s: &str) -> bool {
+    let Some((user, domain)) = s.split_once('@') else {
+        return false;
+    };
+    let valid = |c: char| c.is_ascii_alphanumeric() || "-_.+".contains(c);
+
+    !user.is_empty()
+        && domain.contains('.')
+        && !domain.contains(':')
+        && user.chars().all(valid)
+        && domain.chars().all(valid)
+}
@@ -0,0 +1,166 @@
+//! The canonical encoding of a string, one per payment, for storing strings and making
+//! QR codes of them. Bech32 is lowercase, schemes are lowercase or dropped where the
+//! string is a bare invoice, offer or LNURL, and BIP-21 parameters are percent-encoded in
+//! a fixed order.
+
+use crate::compose::{btc_decimal, encode};
+use crate::{descriptor, suggest, tx, Error, Result};
+use bitcoin::consensus::encode::serialize_hex;
+use bitcoin::{Amount, Denomination};
+use bitcoin_waila::PaymentParams;
+use std::str::FromStr;
+use url::form_urlencoded;
+
+/// BIP-21 parameters in the order they are put, any others follow sorted by name
+const PARAM_ORDER: [&str; 6] = ["amount", "label", "message", "lightning", "lno", "pj"];
+
+/// The canonical encoding of `s`
+pub fn canonicalize(s: &str) -> Result<String> {
+    let s = s.trim();
+    let Ok(payment_params) = PaymentParams::from_str(s) else {
+        if let Some(desc) = descriptor::detect(s) {
+            return Ok(format!("{}#{}", desc.body, desc.checksum));
+        }
+        return match tx::detect(s) {
+            Some(tx::TxInput::Txid(txid)) => Ok(txid.to_string()),
+            Some(tx::TxInput::Raw(tx)) => Ok(serialize_hex(&tx)),
+            None => Err(Error::Unrecognized(suggest::suggest(s))),
+        };
+    };
+
+    Ok(match &payment_params {
+        PaymentParams::OnChain(_) => payment_params
+            .address()
+            .map_or_else(|| s.to_string(), |addr| addr.to_string()),
+        PaymentParams::Bip21(_) => bip21(s, &payment_params),
+        // the rest is bech32, hex or a lightning address, all lowercase when canonical
+        _ => without_scheme(s).to_lowercase(),
+    })
+}
+
+/// The canonical encoding of `s` for a QR code. Bech32, of a bare string or the address of
+/// a URI, is uppercased, which QR codes store in the smaller alphanumeric mode.
+pub fn for_qr(s: &str) -> Result<String> {
+    let canonical = canonicalize(s)?;
+    let (head, params) = match canonical.split_once('?') {
+        Some((head, params)) => (head, Some(params)),
+        None => (canonical.as_str(), None),
+    };
+    let body = head.strip_prefix("bitcoin:").unwrap_or(head);
+    if !is_bech32(body) {
+        return Ok(canonical);
+    }
+
+    let head = head.to_uppercase();
+    Ok(match params {
+        Some(params) => format!("{head}?{params}"),
+        None => head,
+    })
+}
+
+/// Whether `s` is a lowercase bech32 address, invoice, offer or LNURL
+fn is_bech32(s: &str) -> bool {
+    const CHARSET: &str = "qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+    let Some((hrp, data)) = s.rsplit_once('1') else {
+        return false;
+    };
+    let known = matches!(hrp, "bc" | "tb" | "bcrt") || hrp.starts_with("ln");
+
+    known
+        && hrp
+            .bytes()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit())
+        && !data.is_empty()
+        && data.chars().all(|c| CHARSET.contains(c))
+}
+
+/// The BIP-21 URI `s`, with the scheme and address lowercased where that doesn't change
+/// them, the parameters decoded, normalized and encoded again in a fixed order
+fn bip21(s: &str, payment_params: &PaymentParams) -> String {
+    let query = s.split_once('?').map_or("", |(_, query)| query);
+    let mut params: Vec<(String, String)> = form_urlencoded::parse(query.as_bytes())
+        .map(|(key, value)| {
+            let value = match key.as_ref() {
+                // 0.010 is written as 0.01
+                "amount" => Amount::from_str_in(&value, Denomination::Bitcoin)
+                    .map_or_else(|_| value.to_string(), |amt| btc_decimal(amt.to_sat())),
+                "lightning" | "lno" => value.to_lowercase(),
+                _ => value.to_string(),
+            };
+            (key.to_string(), value)
+        })
+        .collect();
+    let rank = |key: &str| {
+        PARAM_ORDER
+            .iter()
+            .position(|known| *known == key)
+            .unwrap_or(PARAM_ORDER.len())
+    };
+    params.sort_by(|(a, _), (b, _)| rank(a).cmp(&rank(b)).then_with(|| a.cmp(b)));
+
+    let address = payment_params
+        .address()
+        .map(|addr| addr.to_string())
+        .unwrap_or_default();
+    let mut uri = format!("bitcoin:{address}");
+    for (i, (key, value)) in params.iter().enumerate() {
+        uri.push(if i == 0 { '?' } else { '&' });
+        uri.push_str(&format!("{key}={}", encode(value)));
+    }
+
+    uri
+}
+
+/// `s` without a `lightning:` scheme, in any case
+fn without_scheme(s: &str) -> &str {
+    match s.get(..10) {
+        Some(scheme) if scheme.eq_ignore_ascii_case("lightning:") => &s[10..],
+        _ => s,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ADDRESS: &str = "bc1quq9zdqylz2gchy6ljq90uhfm3qtl8csel9k7ps";
+    const TXID: &str = "f4184fc596403b9d638783cf57adfe4c75c605f6356fbc91338530e9831e9e16";
+
+    #[test]
+    fn strings_are_canonicalized() {
+        let upper = ADDRESS.to_uppercase();
+        assert_eq!(canonicalize(&format!(" {upper} ")).unwrap(), ADDRESS);
+        assert_eq!(
+            canonicalize(&format!("BITCOIN:{upper}?label=Hi&amount=0.010")).unwrap(),
+            format!("bitcoin:{ADDRESS}?amount=0.01&label=Hi")
+        );
+        assert_eq!(
+            canonicalize("raw(deadbeef)").unwrap(),
+            "raw(deadbeef)#89f8spxm"
+        );
+        assert_eq!(canonicalize(TXID).unwrap(), TXID);
+        assert!(canonicalize("nonsense").is_err());
+    }
+
+    #[test]
+    fn bech32_is_uppercased_for_qr_codes() {
+        let upper = ADDRESS.to_uppercase();
+        assert_eq!(for_qr(ADDRESS).unwrap(), upper);
+        assert_eq!(
+            for_qr(&format!("bitcoin:{ADDRESS}?amount=0.010&label=Hi")).unwrap(),
+            format!("BITCOIN:{upper}?amount=0.01&label=Hi")
+        );
+        // hex and descriptors are left as they are canonical
+        assert_eq!(for_qr(TXID).unwrap(), TXID);
+        assert_eq!(for_qr("raw(deadbeef)").unwrap(), "raw(deadbeef)#89f8spxm");
+    }
+
+    #[test]
+    fn bech32_is_told_apart() {
+        assert!(is_bech32(ADDRESS));
+        assert!(is_bech32("lnbc2500u1pvjluez"));
+        assert!(!is_bech32(TXID));
+        assert!(!is_bech32("satoshi@example.com"));
+        assert!(!is_bech32("bc1"));
+    }
+}
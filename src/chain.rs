@@ -0,0 +1,34 @@
+//! Backends answering on-chain lookups. Every backend reports in the same
+//! shape, amounts in satoshis and feerates in sat/vB.
+
+use crate::{Error, Result};
+use bitcoin::{Address, ScriptBuf};
+use serde_json::Value;
+use std::str::FromStr;
+
+/// A source of on-chain state
+pub trait Chain {
+    /// Balance and usage of an address. The unconfirmed balance is the net
+    /// change of mempool transactions and may be negative.
+    fn address_stats(&self, address: &str) -> Result<Value>;
+
+    /// The most recent `n` transactions touching an address, newest first, with
+    /// the net amount the address received (negative if it spent)
+    fn address_history(&self, address: &str, n: usize) -> Result<Value>;
+
+    /// Confirmation status and fee of a transaction
+    fn tx_status(&self, txid: &str) -> Result<Value>;
+
+    /// Suggested feerates for confirmation within one block (fastest),
+    /// three blocks (half hour) and six blocks (hour)
+    fn suggested_feerates(&self) -> Result<Value>;
+}
+
+/// The output script of `address`
+pub fn script_pubkey(address: &str) -> Result<ScriptBuf> {
+    let Ok(address) = Address::from_str(address) else {
+        return Err(Error::Resolve(format!("not an address: {address}")));
+    };
+
+    Ok(address.assume_checked().script_pubkey())
+}
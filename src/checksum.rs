@@ -0,0 +1,457 @@
+//! Diagnosis of bech32, bech32m and Base58Check strings whose checksum fails,
+//! locating the characters most likely mistyped
+
+use bitcoin::hashes::{sha256d, Hash};
+use std::fmt;
+
+const BECH32_CHARSET: &[u8; 32] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+const BASE58_ALPHABET: &[u8; 58] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+/// Checksum constants of bech32 (BIP-173) and bech32m (BIP-350)
+const BECH32_CONST: u32 = 1;
+const BECH32M_CONST: u32 = 0x2bc8_30a3;
+
+/// Longest string whose mistyped characters are searched for, that of the longest
+/// lightning invoices. The search tries every change at every position, each checked
+/// over the whole string, so its cost grows with the square of the length.
+const MAX_LOCATE_LEN: usize = 1023;
+
+/// Where a string with a failing checksum is likely wrong
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnosis {
+    /// `bech32` or `base58check`
+    pub encoding: &'static str,
+    /// Positions of characters outside of the alphabet, counting from 1
+    pub invalid: Vec<usize>,
+    /// Positions whose change makes the checksum valid, counting from 1. Empty if
+    /// more than a single character or a swap of neighbours is wrong.
+    pub suspects: Vec<usize>,
+    /// Whether the suspects were searched for, which strings longer than
+    /// [`MAX_LOCATE_LEN`] skip
+    pub located: bool,
+    /// Positions of the letters in the minority case of a bech32 string that mixes
+    /// upper and lower case, which bech32 forbids, counting from 1
+    pub mixed_case: Vec<usize>,
+    /// The all lowercase string, if mixing case is all that is wrong with it
+    pub candidate: Option<String>,
+}
+
+impl fmt::Display for Diagnosis {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let list = |positions: &[usize]| {
+            positions
+                .iter()
+                .map(|p| p.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        if let Some(candidate) = &self.candidate {
+            return write!(
+                f,
+                "bech32 string mixes upper and lower case at position {}, the lowercase {candidate} is valid",
+                list(&self.mixed_case)
+            );
+        }
+        if !self.mixed_case.is_empty() {
+            write!(
+                f,
+                "bech32 string mixes upper and lower case at position {}; ",
+                list(&self.mixed_case)
+            )?;
+        }
+        if !self.invalid.is_empty() {
+            return write!(
+                f,
+                "invalid {} character at position {}",
+                self.encoding,
+                list(&self.invalid)
+            );
+        }
+        if !self.located {
+            write!(
+                f,
+                "invalid {} checksum, the string is too long to locate the error",
+                self.encoding
+            )
+        } else if self.suspects.is_empty() {
+            write!(
+                f,
+                "invalid {} checksum, more than one character is wrong",
+                self.encoding
+            )
+        } else {
+            write!(
+                f,
+                "invalid {} checksum, likely wrong at position {}",
+                self.encoding,
+                list(&self.suspects)
+            )
+        }
+    }
+}
+
+/// Diagnose `s` if it looks like a bech32 or Base58Check string whose checksum fails
+pub fn diagnose(s: &str) -> Option<Diagnosis> {
+    // a valid bech32 string would otherwise read as base58 with its 0s and ls mistyped
+    if bitcoin::bech32::decode(s).is_ok() {
+        return None;
+    }
+
+    diagnose_bech32(s).or_else(|| diagnose_base58(s))
+}
+
+fn diagnose_bech32(s: &str) -> Option<Diagnosis> {
+    let lower = s.to_ascii_lowercase();
+    let sep = lower.rfind('1')?;
+    let (hrp, data) = (&lower[..sep], &lower.as_bytes()[sep + 1..]);
+    if !is_bitcoin_hrp(hrp) || data.len() < 6 {
+        return None;
+    }
+
+    let invalid: Vec<usize> = data
+        .iter()
+        .enumerate()
+        .filter(|(_, c)| !BECH32_CHARSET.contains(c))
+        .map(|(i, _)| sep + 2 + i)
+        .collect();
+    // too many strange characters, this isn't meant as bech32
+    if invalid.len() > data.len() / 4 {
+        return None;
+    }
+    let mixed_case = mixed_case(s);
+    if !invalid.is_empty() {
+        return Some(Diagnosis {
+            encoding: "bech32",
+            invalid,
+            suspects: vec![],
+            located: false,
+            mixed_case,
+            candidate: None,
+        });
+    }
+
+    let values: Vec<u8> = data
+        .iter()
+        .map(|c| {
+            BECH32_CHARSET
+                .iter()
+                .position(|x| x == c)
+                .unwrap_or_default() as u8
+        })
+        .collect();
+    let valid = |values: &[u8]| {
+        let chk = bech32_polymod(hrp, values);
+        chk == BECH32_CONST || chk == BECH32M_CONST
+    };
+    if valid(&values) {
+        if mixed_case.is_empty() {
+            return None;
+        }
+        return Some(Diagnosis {
+            encoding: "bech32",
+            invalid: vec![],
+            suspects: vec![],
+            located: false,
+            mixed_case,
+            candidate: Some(lower.clone()),
+        });
+    }
+    let located = s.len() <= MAX_LOCATE_LEN;
+    let suspects = if located {
+        locate(&values, 32, valid)
+    } else {
+        vec![]
+    };
+
+    Some(Diagnosis {
+        encoding: "bech32",
+        invalid: vec![],
+        suspects: suspects.into_iter().map(|i| sep + 2 + i).collect(),
+        located,
+        mixed_case,
+        candidate: None,
+    })
+}
+
+/// Positions of the letters in the less used case, counting from 1, if `s` has both
+fn mixed_case(s: &str) -> Vec<usize> {
+    let upper = s.bytes().filter(u8::is_ascii_uppercase).count();
+    let lower = s.bytes().filter(u8::is_ascii_lowercase).count();
+    if upper == 0 || lower == 0 {
+        return vec![];
+    }
+    let minority = |c: &u8| {
+        if upper <= lower {
+            c.is_ascii_uppercase()
+        } else {
+            c.is_ascii_lowercase()
+        }
+    };
+
+    s.bytes()
+        .enumerate()
+        .filter(|(_, c)| minority(c))
+        .map(|(i, _)| i + 1)
+        .collect()
+}
+
+/// Whether `hrp` is used by addresses, invoices, LNURLs or nostr entities
+fn is_bitcoin_hrp(hrp: &str) -> bool {
+    matches!(
+        hrp,
+        "bc" | "tb" | "bcrt" | "lnurl" | "npub" | "nsec" | "note" | "nprofile" | "nevent"
+    ) || (hrp.starts_with("ln") && hrp.bytes().all(|c| c.is_ascii_alphanumeric()))
+}
+
+fn diagnose_base58(s: &str) -> Option<Diagnosis> {
+    // addresses, WIF keys and extended keys
+    if !(25..=112).contains(&s.len()) {
+        return None;
+    }
+    let invalid: Vec<usize> = s
+        .bytes()
+        .enumerate()
+        .filter(|(_, c)| !BASE58_ALPHABET.contains(c))
+        .map(|(i, _)| i + 1)
+        .collect();
+    if invalid.len() > 2 || !s.bytes().any(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    if !invalid.is_empty() {
+        return Some(Diagnosis {
+            encoding: "base58check",
+            invalid,
+            suspects: vec![],
+            located: false,
+            mixed_case: vec![],
+            candidate: None,
+        });
+    }
+
+    let values: Vec<u8> = s
+        .bytes()
+        .map(|c| {
+            BASE58_ALPHABET
+                .iter()
+                .position(|x| *x == c)
+                .unwrap_or_default() as u8
+        })
+        .collect();
+    if base58check_valid(&values) {
+        return None;
+    }
+    // base58 strings are never longer than MAX_LOCATE_LEN
+    let suspects = locate(&values, 58, base58check_valid)
+        .into_iter()
+        .map(|i| i + 1)
+        .collect();
+
+    Some(Diagnosis {
+        encoding: "base58check",
+        invalid: vec![],
+        suspects,
+        located: true,
+        mixed_case: vec![],
+        candidate: None,
+    })
+}
+
+/// Indices at which changing a single symbol, or swapping it with the next one,
+/// makes `valid` pass. The checksums detect any few errors, so a single fix that
+/// passes is very likely the right one.
+fn locate(values: &[u8], base: u8, valid: impl Fn(&[u8]) -> bool) -> Vec<usize> {
+    let mut suspects = vec![];
+    let mut candidate = values.to_vec();
+    for i in 0..values.len() {
+        for v in (0..base).filter(|v| *v != values[i]) {
+            candidate[i] = v;
+            if valid(&candidate) && !suspects.contains(&i) {
+                suspects.push(i);
+            }
+        }
+        candidate[i] = values[i];
+    }
+    if !suspects.is_empty() {
+        return suspects;
+    }
+
+    for i in 0..values.len().saturating_sub(1) {
+        candidate.swap(i, i + 1);
+        if values[i] != values[i + 1] && valid(&candidate) {
+            suspects.extend([i, i + 1]);
+        }
+        candidate.swap(i, i + 1);
+    }
+
+    suspects
+}
+
+/// The BIP-173 checksum polynomial over the expanded `hrp` and the 5-bit `values`
+fn bech32_polymod(hrp: &str, values: &[u8]) -> u32 {
+    const GEN: [u32; 5] = [
+        0x3b6a_57b2,
+        0x2650_8e6d,
+        0x1ea1_19fa,
+        0x3d42_33dd,
+        0x2a14_62b3,
+    ];
+    let expanded = hrp
+        .bytes()
+        .map(|c| c >> 5)
+        .chain([0])
+        .chain(hrp.bytes().map(|c| c & 31));
+
+    let mut chk: u32 = 1;
+    for v in expanded.chain(values.iter().copied()) {
+        let top = chk >> 25;
+        chk = ((chk & 0x01ff_ffff) << 5) ^ u32::from(v);
+        for (i, gen) in GEN.iter().enumerate() {
+            if (top >> i) & 1 == 1 {
+                chk ^= gen;
+            }
+        }
+    }
+
+    chk
+}
+
+/// Whether the base58 digits `values` carry a valid double-sha256 checksum
+fn base58check_valid(values: &[u8]) -> bool {
+    // leading zero digits stand for zero bytes
+    let zeros = values.iter().take_while(|v| **v == 0).count();
+    let mut bytes: Vec<u8> = vec![];
+    for v in &values[zeros..] {
+        let mut carry = u32::from(*v);
+        for b in bytes.iter_mut().rev() {
+            carry += u32::from(*b) * 58;
+            *b = (carry & 0xff) as u8;
+            carry >>= 8;
+        }
+        while carry > 0 {
+            bytes.insert(0, (carry & 0xff) as u8);
+            carry >>= 8;
+        }
+    }
+    let mut decoded = vec![0; zeros];
+    decoded.extend(bytes);
+    if decoded.len() < 5 {
+        return false;
+    }
+
+    let (payload, checksum) = decoded.split_at(decoded.len() - 4);
+    sha256d::Hash::hash(payload).to_byte_array()[..4] == *checksum
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BECH32: &str = "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4";
+    const BASE58: &str = "1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa";
+
+    /// `s` with the character at `position`, counting from 1, replaced by `c`
+    fn substitute(s: &str, position: usize, c: char) -> String {
+        let mut chars: Vec<char> = s.chars().collect();
+        chars[position - 1] = c;
+        chars.into_iter().collect()
+    }
+
+    /// `s` with the characters at `position` and the next one swapped
+    fn swap(s: &str, position: usize) -> String {
+        let mut chars: Vec<char> = s.chars().collect();
+        chars.swap(position - 1, position);
+        chars.into_iter().collect()
+    }
+
+    #[test]
+    fn valid_strings_are_not_diagnosed() {
+        assert_eq!(diagnose(BECH32), None);
+        assert_eq!(diagnose(&BECH32.to_uppercase()), None);
+        assert_eq!(diagnose(BASE58), None);
+    }
+
+    #[test]
+    fn single_substitution_is_located() {
+        let diagnosis = diagnose(&substitute(BECH32, 10, 'q')).unwrap();
+        assert_eq!(diagnosis.encoding, "bech32");
+        assert_eq!(diagnosis.suspects, [10]);
+        assert_eq!(
+            diagnosis.to_string(),
+            "invalid bech32 checksum, likely wrong at position 10"
+        );
+        // the checksum itself
+        let diagnosis = diagnose(&substitute(BECH32, 42, 'q')).unwrap();
+        assert_eq!(diagnosis.suspects, [42]);
+
+        let diagnosis = diagnose(&substitute(BASE58, 5, 'Q')).unwrap();
+        assert_eq!(diagnosis.encoding, "base58check");
+        assert_eq!(diagnosis.suspects, [5]);
+    }
+
+    #[test]
+    fn swap_of_neighbours_is_located() {
+        assert_eq!(diagnose(&swap(BECH32, 12)).unwrap().suspects, [12, 13]);
+        assert_eq!(diagnose(&swap(BASE58, 9)).unwrap().suspects, [9, 10]);
+    }
+
+    #[test]
+    fn double_substitution_is_not_located() {
+        let diagnosis = diagnose(&substitute(&substitute(BECH32, 8, 'z'), 20, 'z')).unwrap();
+        assert_eq!(diagnosis.encoding, "bech32");
+        assert!(diagnosis.suspects.is_empty());
+        assert_eq!(
+            diagnosis.to_string(),
+            "invalid bech32 checksum, more than one character is wrong"
+        );
+
+        let diagnosis = diagnose(&substitute(&substitute(BASE58, 5, 'Q'), 20, 'Z')).unwrap();
+        assert_eq!(diagnosis.encoding, "base58check");
+        assert!(diagnosis.suspects.is_empty());
+    }
+
+    #[test]
+    fn long_strings_are_not_located() {
+        let long = format!("lnbc1{}", "q".repeat(100_000));
+        let diagnosis = diagnose(&long).unwrap();
+        assert_eq!(diagnosis.encoding, "bech32");
+        assert!(!diagnosis.located);
+        assert!(diagnosis.suspects.is_empty());
+        assert_eq!(
+            diagnosis.to_string(),
+            "invalid bech32 checksum, the string is too long to locate the error"
+        );
+    }
+
+    #[test]
+    fn invalid_characters_are_reported() {
+        let diagnosis = diagnose(&substitute(BECH32, 42, 'b')).unwrap();
+        assert_eq!(diagnosis.invalid, [42]);
+        assert!(diagnosis.suspects.is_empty());
+
+        let diagnosis = diagnose(&substitute(BASE58, 5, '0')).unwrap();
+        assert_eq!(diagnosis.encoding, "base58check");
+        assert_eq!(diagnosis.invalid, [5]);
+    }
+
+    #[test]
+    fn mixed_case_gives_the_lowercase_string() {
+        let diagnosis = diagnose("bc1qw508d6qejxtdg4Y5R3ZARVARY0C5XW7KV8F3T4").unwrap();
+        assert_eq!(
+            diagnosis.mixed_case,
+            [1, 2, 4, 5, 9, 11, 12, 13, 14, 15, 16, 17]
+        );
+        assert_eq!(diagnosis.candidate.as_deref(), Some(BECH32));
+    }
+
+    #[test]
+    fn other_strings_are_not_diagnosed() {
+        assert_eq!(diagnose("hello world"), None);
+        // too short for either encoding
+        assert_eq!(diagnose("bc1qw5bio"), None);
+        // an hrp of no bitcoin format isn't read as bech32
+        let diagnosis = diagnose("xy1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4");
+        assert_ne!(diagnosis.map(|d| d.encoding), Some("bech32"));
+        // base58 strings of waila have digits
+        assert_eq!(diagnose("ThereIsNoDigitInThisLongSentence"), None);
+    }
+}
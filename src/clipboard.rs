@@ -0,0 +1,55 @@
+//! The system clipboard, written through the copy command of the platform so no
+//! display server library is linked in.
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+use waila_cli::{Error, Result};
+
+/// Copy commands in the order they are tried, with their arguments
+fn commands() -> Vec<(&'static str, &'static [&'static str])> {
+    if cfg!(target_os = "macos") {
+        return vec![("pbcopy", &[])];
+    }
+    if cfg!(windows) {
+        return vec![("clip", &[])];
+    }
+    let mut commands: Vec<(&'static str, &'static [&'static str])> = vec![];
+    if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+        commands.push(("wl-copy", &[]));
+    }
+    commands.push(("xclip", &["-selection", "clipboard"]));
+    commands.push(("xsel", &["--clipboard", "--input"]));
+
+    commands
+}
+
+/// Put `data` on the clipboard with the first copy command that is installed
+pub fn copy(data: &str) -> Result<()> {
+    let commands = commands();
+    for (program, args) in &commands {
+        let Ok(mut child) = Command::new(program)
+            .args(*args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+        else {
+            continue;
+        };
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(data.as_bytes())?;
+        }
+        let status = child.wait()?;
+        if !status.success() {
+            return Err(Error::Clipboard(format!("{program} exited with {status}")));
+        }
+        tracing::debug!("copied {} bytes with {program}", data.len());
+        return Ok(());
+    }
+
+    let names: Vec<&str> = commands.iter().map(|(program, _)| *program).collect();
+    Err(Error::Clipboard(format!(
+        "none of {} is installed",
+        names.join(", ")
+    )))
+}
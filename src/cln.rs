@@ -0,0 +1,97 @@
+//! Core Lightning as a backend for requesting BOLT12 invoices from offers
+
+use crate::{net, Error, Result};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::path::PathBuf;
+
+/// How to reach a Core Lightning node
+#[derive(Debug, Clone)]
+pub enum Backend {
+    /// The JSON-RPC unix socket, usually `~/.lightning/bitcoin/lightning-rpc`
+    Rpc(PathBuf),
+    /// The clnrest plugin, authenticated with a rune
+    Rest { url: String, rune: String },
+}
+
+impl Backend {
+    fn call(&self, method: &str, params: Value) -> Result<Value> {
+        // a local socket is still a node talking to the network
+        net::ensure_online()?;
+        let resp = match self {
+            #[cfg(unix)]
+            Backend::Rpc(path) => {
+                let mut stream = std::os::unix::net::UnixStream::connect(path)?;
+                let req = json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params });
+                serde_json::to_writer(&mut stream, &req)?;
+                // the node answers with a single json object
+                let mut de = serde_json::Deserializer::from_reader(stream);
+                Value::deserialize(&mut de)?
+            }
+            #[cfg(not(unix))]
+            Backend::Rpc(_) => {
+                return Err(Error::Backend(
+                    "the JSON-RPC socket is only available on unix".to_string(),
+                ))
+            }
+            Backend::Rest { url, rune } => {
+                let url = format!("{}/v1/{method}", url.trim_end_matches('/'));
+                match net::request("POST", &url)?
+                    .set("Rune", rune)
+                    .send_json(params)
+                {
+                    Ok(resp) => json!({ "result": resp.into_json::<Value>()? }),
+                    // errors come with a json body as well
+                    Err(ureq::Error::Status(_, resp)) => {
+                        json!({ "error": resp.into_json::<Value>()? })
+                    }
+                    Err(e) => return Err(e.into()),
+                }
+            }
+        };
+
+        if let Some(err) = resp.get("error") {
+            let msg = err["message"]
+                .as_str()
+                .map(str::to_string)
+                .unwrap_or_else(|| err.to_string());
+            return Err(Error::Backend(format!("{method}: {msg}")));
+        }
+
+        Ok(resp["result"].clone())
+    }
+
+    /// Send an invoice request for `offer` over onion messages and return the
+    /// BOLT12 invoice. `amount_msat` is needed only if the offer has no amount.
+    pub fn fetch_invoice(&self, offer: &str, amount_msat: Option<u64>) -> Result<String> {
+        let mut params = json!({ "offer": offer });
+        if let Some(msat) = amount_msat {
+            params["amount_msat"] = json!(msat);
+        }
+        let result = self.call("fetchinvoice", params)?;
+
+        match result["invoice"].as_str() {
+            Some(invoice) => Ok(invoice.to_string()),
+            None => Err(Error::Backend(
+                "fetchinvoice returned no invoice".to_string(),
+            )),
+        }
+    }
+
+    /// Decode a BOLT12 invoice with the node, amounts in millisatoshis
+    pub fn describe_invoice(&self, invoice: &str) -> Result<Value> {
+        let decoded = self.call("decode", json!({ "string": invoice }))?;
+
+        Ok(json!({
+            "kind": "Bolt12Invoice",
+            "invoice": invoice,
+            "valid": decoded["valid"],
+            "amount_msat": decoded["invoice_amount_msat"],
+            "description": decoded["offer_description"],
+            "payment_hash": decoded["invoice_payment_hash"],
+            "node_id": decoded["invoice_node_id"],
+            "created_at": decoded["invoice_created_at"],
+            "relative_expiry": decoded["invoice_relative_expiry"],
+        }))
+    }
+}
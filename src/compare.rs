@@ -0,0 +1,138 @@
+//! Similarity of two addresses, to catch lookalikes planted by address poisoning.
+//! An attacker sends dust from an address matching the first and last characters
+//! of one the victim uses, hoping it gets copied from the history instead.
+
+use crate::{Error, Result};
+use bitcoin::Address;
+use serde_json::{json, Value};
+use std::str::FromStr;
+
+/// Matching characters at each end beyond which a different address is suspicious
+const POISONING_MATCH: usize = 3;
+
+/// Compare the addresses `a` and `b` by their common prefix and suffix and
+/// their edit distance
+///
+/// ## Errors
+/// If either is not an address
+pub fn compare(a: &str, b: &str) -> Result<Value> {
+    let (a, b) = (normalize(a)?, normalize(b)?);
+    let (a, b) = (a.as_str(), b.as_str());
+    let prefix = common_prefix(a, b);
+    let suffix = common_suffix(a, b);
+    // the type prefix, e.g. bc1q, is the same for most addresses and tells nothing
+    let type_prefix = type_prefix_len(a).min(type_prefix_len(b));
+    let verdict = if a == b {
+        "identical"
+    } else if prefix.saturating_sub(type_prefix) >= POISONING_MATCH && suffix >= POISONING_MATCH {
+        "likely_poisoning"
+    } else {
+        "different"
+    };
+
+    Ok(json!({
+        "first": a,
+        "second": b,
+        "prefix_match": prefix,
+        "suffix_match": suffix,
+        "edit_distance": edit_distance(a, b),
+        "verdict": verdict,
+    }))
+}
+
+/// `address` as compared: bech32 in lowercase, as an uppercase one for QR codes is
+/// the same address
+fn normalize(address: &str) -> Result<String> {
+    let address = address.trim();
+    if Address::from_str(address).is_err() {
+        return Err(Error::InvalidKey(format!("not an address: {address}")));
+    }
+    if type_prefix_len(address) > 1 {
+        return Ok(address.to_ascii_lowercase());
+    }
+
+    Ok(address.to_string())
+}
+
+fn common_prefix(a: &str, b: &str) -> usize {
+    a.chars().zip(b.chars()).take_while(|(x, y)| x == y).count()
+}
+
+fn common_suffix(a: &str, b: &str) -> usize {
+    a.chars()
+        .rev()
+        .zip(b.chars().rev())
+        .take_while(|(x, y)| x == y)
+        .count()
+}
+
+/// Length of the part of `address` fixed by its type: the human readable part,
+/// separator and witness version of bech32, the version character of base58
+fn type_prefix_len(address: &str) -> usize {
+    let lower = address.to_ascii_lowercase();
+    match lower.split_once('1') {
+        Some((hrp, _)) if matches!(hrp, "bc" | "tb" | "bcrt") => hrp.len() + 2,
+        _ => 1,
+    }
+}
+
+/// The Levenshtein distance between `a` and `b`
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, x) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, y) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(x != *y);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+
+    row[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // lookalikes: three characters in common after the type prefix and at the end
+    const PAID: &str = "bc1quq9zdqylz2gchy6ljq90uhfm3qtl8csel9k7ps";
+    const POISONED: &str = "bc1quq9chs7xp2kdeyxz9m4g6jtgvv8nrdsuts27ps";
+    // the same ending but only two characters in common after the type prefix
+    const FIRST: &str = "bc1qst86yzf6xhr75qhszqnk7pklzskx9ugy6479gz";
+    const SECOND: &str = "bc1qstc0j3deywg8repx2rg5rd2an8lwcxfm9cn9gz";
+
+    #[test]
+    fn lookalikes_are_likely_poisoning() {
+        let report = compare(PAID, POISONED).unwrap();
+        assert_eq!(report["prefix_match"], 7);
+        assert_eq!(report["suffix_match"], 3);
+        assert_eq!(report["verdict"], "likely_poisoning");
+    }
+
+    #[test]
+    fn addresses_matching_less_are_different() {
+        let report = compare(FIRST, SECOND).unwrap();
+        assert_eq!(report["prefix_match"], 6);
+        assert_eq!(report["suffix_match"], 3);
+        assert_eq!(report["verdict"], "different");
+        assert_eq!(compare(PAID, FIRST).unwrap()["verdict"], "different");
+    }
+
+    #[test]
+    fn bech32_case_is_ignored() {
+        let report = compare(PAID, &PAID.to_ascii_uppercase()).unwrap();
+        assert_eq!(report["verdict"], "identical");
+        assert_eq!(report["edit_distance"], 0);
+        assert_eq!(report["second"], PAID);
+    }
+
+    #[test]
+    fn non_addresses_are_rejected() {
+        assert!(matches!(compare(PAID, "hello"), Err(Error::InvalidKey(_))));
+        let garbled = format!("{}q", &PAID[..PAID.len() - 1]);
+        assert!(compare(&garbled, PAID).is_err());
+    }
+}
@@ -0,0 +1,239 @@
+//! BIP-21 URIs composed from their parts, the inverse of parsing one. Each part is
+//! checked on its own and against the others before it is encoded.
+
+use crate::{amount_msat, Error, Result};
+use bitcoin_waila::PaymentParams;
+use std::str::FromStr;
+use url::{form_urlencoded, Url};
+
+/// The parts of a URI, at least one of the address, invoice and offer
+#[derive(Debug, Clone, Default)]
+pub struct Parts {
+    pub address: Option<String>,
+    /// Amount in millisatoshis, whole satoshis. Taken from the invoice if left out.
+    pub amount: Option<u64>,
+    pub label: Option<String>,
+    pub message: Option<String>,
+    /// A BOLT-11 invoice, for the `lightning` parameter
+    pub lightning: Option<String>,
+    /// A BOLT-12 offer, for the `lno` parameter
+    pub offer: Option<String>,
+    /// A payjoin endpoint, for the `pj` parameter
+    pub payjoin: Option<String>,
+}
+
+/// The `bitcoin:` URI of `parts`
+pub fn compose(parts: &Parts) -> Result<String> {
+    if parts.address.is_none() && parts.lightning.is_none() && parts.offer.is_none() {
+        return Err(Error::InvalidPart(
+            "a URI needs an address, an invoice or an offer".to_string(),
+        ));
+    }
+
+    let address = parts
+        .address
+        .as_deref()
+        .map(|s| parse(s, "address", |pp| matches!(pp, PaymentParams::OnChain(_))))
+        .transpose()?;
+    let invoice = parts
+        .lightning
+        .as_deref()
+        .map(|s| parse(s, "invoice", |pp| matches!(pp, PaymentParams::Bolt11(_))))
+        .transpose()?;
+    if let Some(offer) = &parts.offer {
+        parse(offer, "offer", |pp| matches!(pp, PaymentParams::Bolt12(_)))?;
+    }
+
+    // an invoice for another network would not be paid by the wallet reading the URI
+    let network = |pp: &PaymentParams| pp.network().map(|net| net.to_string());
+    if let (Some(address), Some(invoice)) = (&address, &invoice) {
+        if let Some(expected) = network(address) {
+            if network(invoice).as_deref() != Some(expected.as_str()) {
+                return Err(Error::UnexpectedNetwork(expected, network(invoice)));
+            }
+        }
+    }
+
+    // the on-chain amount must be what the invoice asks for
+    let invoice_amount = invoice.as_ref().map(amount_msat).transpose()?.flatten();
+    let amount = match (parts.amount, invoice_amount) {
+        (Some(msat), Some(invoice_msat)) if msat != invoice_msat => {
+            return Err(Error::InvalidAmount(format!(
+                "{msat} msat, the invoice is for {invoice_msat} msat"
+            )))
+        }
+        (amount, invoice_amount) => amount.or(invoice_amount),
+    };
+    if let Some(msat) = amount.filter(|msat| msat % 1_000 != 0) {
+        return Err(Error::InvalidAmount(format!(
+            "{msat} msat, a URI amount is whole satoshis"
+        )));
+    }
+
+    if let Some(pj) = &parts.payjoin {
+        if address.is_none() {
+            return Err(Error::InvalidPart(
+                "a payjoin endpoint needs an address".to_string(),
+            ));
+        }
+        let url = Url::parse(pj)
+            .map_err(|e| Error::InvalidPart(format!("invalid payjoin endpoint {pj}: {e}")))?;
+        // BIP-78 allows plain http only to onion services
+        let onion = url.host_str().is_some_and(|host| host.ends_with(".onion"));
+        if url.scheme() != "https" && !(url.scheme() == "http" && onion) {
+            return Err(Error::InvalidPart(format!(
+                "a payjoin endpoint must be https or an http onion service: {pj}"
+            )));
+        }
+    }
+
+    let mut params = vec![];
+    if let Some(msat) = amount {
+        params.push(("amount", btc_decimal(msat / 1_000)));
+    }
+    for (key, value) in [
+        ("label", &parts.label),
+        ("message", &parts.message),
+        ("lightning", &parts.lightning),
+        ("lno", &parts.offer),
+        ("pj", &parts.payjoin),
+    ] {
+        if let Some(value) = value {
+            params.push((key, encode(value)));
+        }
+    }
+    let mut uri = format!("bitcoin:{}", parts.address.as_deref().unwrap_or_default());
+    for (i, (key, value)) in params.iter().enumerate() {
+        uri.push(if i == 0 { '?' } else { '&' });
+        uri.push_str(&format!("{key}={value}"));
+    }
+
+    // what is composed must read back, a URI without an address only to wallets that
+    // pay its invoice or offer
+    if address.is_some() && PaymentParams::from_str(&uri).is_err() {
+        return Err(Error::InvalidPart(format!(
+            "the composed URI does not parse: {uri}"
+        )));
+    }
+
+    Ok(uri)
+}
+
+/// `s` parsed, failing unless `is_kind` holds for it
+fn parse(s: &str, what: &str, is_kind: fn(&PaymentParams) -> bool) -> Result<PaymentParams> {
+    match PaymentParams::from_str(s) {
+        Ok(pp) if is_kind(&pp) => Ok(pp),
+        _ => Err(Error::InvalidPart(format!("not a valid {what}: {s}"))),
+    }
+}
+
+/// `sat` in bitcoin, without trailing zeros
+pub(crate) fn btc_decimal(sat: u64) -> String {
+    let btc = format!("{}.{:08}", sat / 100_000_000, sat % 100_000_000);
+
+    btc.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+/// `value` percent-encoded for a query parameter, spaces as %20 as BIP-21 has them
+pub(crate) fn encode(value: &str) -> String {
+    // a literal '+' is encoded as %2B, so any '+' left stands for a space
+    form_urlencoded::byte_serialize(value.as_bytes())
+        .collect::<String>()
+        .replace('+', "%20")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ADDRESS: &str = "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4";
+    const TESTNET_ADDRESS: &str = "tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx";
+    // a testnet invoice for 2 mBTC
+    const INVOICE: &str = "lntb20m1pvjluezsp5zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zygshp58yjmdan79s6qqdhdzgynm4zwqd5d7xmw5fk98klysy043l2ahrqspp5qqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqypqfpp3x9et2e20v6pu37c5d9vax37wxq72un989qrsgqdj545axuxtnfemtpwkc45hx9d2ft7x04mt8q7y6t0k2dge9e7h8kpy9p34ytyslj3yu569aalz2xdk8xkd7ltxqld94u8h2esmsmacgpghe9k8";
+
+    fn address(address: &str) -> Parts {
+        Parts {
+            address: Some(address.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn parts_are_encoded() {
+        let parts = Parts {
+            amount: Some(150_000_000),
+            label: Some("Luke Jr+1".to_string()),
+            payjoin: Some("https://example.com/pj".to_string()),
+            ..address(ADDRESS)
+        };
+        assert_eq!(
+            compose(&parts).unwrap(),
+            format!("bitcoin:{ADDRESS}?amount=0.0015&label=Luke%20Jr%2B1&pj=https%3A%2F%2Fexample.com%2Fpj")
+        );
+        assert_eq!(
+            compose(&address(ADDRESS)).unwrap(),
+            format!("bitcoin:{ADDRESS}")
+        );
+    }
+
+    #[test]
+    fn amounts_are_taken_from_the_invoice() {
+        let parts = Parts {
+            lightning: Some(INVOICE.to_string()),
+            ..address(TESTNET_ADDRESS)
+        };
+        let uri = compose(&parts).unwrap();
+        assert!(uri.starts_with(&format!(
+            "bitcoin:{TESTNET_ADDRESS}?amount=0.002&lightning="
+        )));
+
+        let parts = Parts {
+            amount: Some(1_000),
+            ..parts
+        };
+        assert!(matches!(compose(&parts), Err(Error::InvalidAmount(_))));
+    }
+
+    #[test]
+    fn inconsistent_parts_are_refused() {
+        assert!(matches!(
+            compose(&Parts::default()),
+            Err(Error::InvalidPart(_))
+        ));
+        assert!(matches!(
+            compose(&address("bc1qnotanaddress")),
+            Err(Error::InvalidPart(_))
+        ));
+        let parts = Parts {
+            lightning: Some(INVOICE.to_string()),
+            ..address(ADDRESS)
+        };
+        assert!(matches!(
+            compose(&parts),
+            Err(Error::UnexpectedNetwork(expected, _)) if expected == "bitcoin"
+        ));
+        let parts = Parts {
+            amount: Some(1_500),
+            ..address(ADDRESS)
+        };
+        assert!(matches!(compose(&parts), Err(Error::InvalidAmount(_))));
+        let parts = Parts {
+            payjoin: Some("http://example.com/pj".to_string()),
+            ..address(ADDRESS)
+        };
+        assert!(matches!(compose(&parts), Err(Error::InvalidPart(_))));
+        let parts = Parts {
+            payjoin: Some("http://example.onion/pj".to_string()),
+            ..address(ADDRESS)
+        };
+        assert!(compose(&parts).is_ok());
+    }
+
+    #[test]
+    fn amounts_are_in_bitcoin() {
+        assert_eq!(btc_decimal(100_000_000), "1");
+        assert_eq!(btc_decimal(150_000_000), "1.5");
+        assert_eq!(btc_decimal(1), "0.00000001");
+        assert_eq!(btc_decimal(0), "0");
+    }
+}
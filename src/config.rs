@@ -0,0 +1,372 @@
+//! Defaults read from `~/.config/waila/config.toml`, or from `waila/config.toml` under
+//! `$XDG_CONFIG_HOME`. Keys are named like the flags they set, which take precedence.
+//! `WAILA_*` environment variables set the same keys, over those of the file, and a
+//! `[profile.NAME]` section chosen with `--profile` sets them over both.
+
+use crate::{render, Args, Format, NETWORKS, UNITS};
+use clap::parser::ValueSource;
+use clap::{ArgMatches, ValueEnum};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::io;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use waila_cli::{esplora, fiat, graph, Error, Result};
+
+/// The settings of a config file
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Config {
+    units: Option<String>,
+    format: Option<String>,
+    color: Option<String>,
+    key_case: Option<String>,
+    fiat: Option<Vec<String>>,
+    rate_source: Option<String>,
+    proxy: Option<String>,
+    dns_server: Option<SocketAddr>,
+    doh: Option<String>,
+    esplora: Option<String>,
+    /// The Esplora API of each network, as an `[esplora-url]` table
+    esplora_url: Option<BTreeMap<String, String>>,
+    electrum: Option<String>,
+    bitcoind: Option<String>,
+    bitcoind_cookie: Option<PathBuf>,
+    cln_rpc: Option<PathBuf>,
+    cln_rest: Option<String>,
+    graph: Option<String>,
+    graph_url: Option<String>,
+    cache_ttl: Option<u64>,
+    online: Option<bool>,
+    resolve: Option<bool>,
+    expect_network: Option<String>,
+    record_history: Option<bool>,
+    hash_inputs: Option<bool>,
+    /// Relays to hint at in the nprofile of `convert nostr`, for its `--relay`
+    relays: Option<Vec<String>>,
+}
+
+/// Environment variables and the keys they set
+const ENV: [(&str, &str); 24] = [
+    ("WAILA_UNITS", "units"),
+    ("WAILA_FORMAT", "format"),
+    ("WAILA_COLOR", "color"),
+    ("WAILA_KEY_CASE", "key-case"),
+    ("WAILA_FIAT", "fiat"),
+    ("WAILA_RATE_SOURCE", "rate-source"),
+    ("WAILA_PROXY", "proxy"),
+    ("WAILA_DNS_SERVER", "dns-server"),
+    ("WAILA_DOH", "doh"),
+    ("WAILA_ESPLORA", "esplora"),
+    ("WAILA_ELECTRUM", "electrum"),
+    ("WAILA_BITCOIND", "bitcoind"),
+    ("WAILA_BITCOIND_COOKIE", "bitcoind-cookie"),
+    ("WAILA_CLN_RPC", "cln-rpc"),
+    ("WAILA_CLN_REST", "cln-rest"),
+    ("WAILA_GRAPH", "graph"),
+    ("WAILA_GRAPH_URL", "graph-url"),
+    ("WAILA_CACHE_TTL", "cache-ttl"),
+    ("WAILA_ONLINE", "online"),
+    ("WAILA_RESOLVE", "resolve"),
+    ("WAILA_NETWORK", "expect-network"),
+    ("WAILA_RECORD_HISTORY", "record-history"),
+    ("WAILA_HASH_INPUTS", "hash-inputs"),
+    ("WAILA_RELAYS", "relays"),
+];
+
+/// The variables of [`ENV`], for the help
+pub const ENV_HELP: &str = "\
+Environment:
+  WAILA_UNITS, WAILA_FORMAT, WAILA_COLOR, WAILA_KEY_CASE, WAILA_FIAT (comma separated),
+  WAILA_RATE_SOURCE, WAILA_PROXY, WAILA_DNS_SERVER, WAILA_DOH, WAILA_ESPLORA, WAILA_ELECTRUM,
+  WAILA_BITCOIND, WAILA_BITCOIND_COOKIE, WAILA_CLN_RPC, WAILA_CLN_REST, WAILA_GRAPH,
+  WAILA_GRAPH_URL, WAILA_CACHE_TTL, WAILA_ONLINE, WAILA_RESOLVE, WAILA_RECORD_HISTORY and
+  WAILA_HASH_INPUTS (true or false), WAILA_NETWORK (for --expect-network) and WAILA_RELAYS
+  (comma separated, for the --relay of convert nostr) set the defaults of their flags, over
+  those of the config file";
+
+/// Read the config at `path`, or at the default location if there is one there, the
+/// environment variables overriding its keys and the `profile` overriding both
+pub fn load(path: Option<&Path>, profile: Option<&str>) -> Result<Config> {
+    let (path, required) = match (path, default_path()) {
+        (Some(path), _) => (path.to_path_buf(), true),
+        (None, Some(path)) => (path, false),
+        (None, None) if profile.is_none() => return from_env(),
+        (None, None) => return Err(Error::Config("--profile needs a config file".to_string())),
+    };
+    let invalid = |e: &dyn std::fmt::Display| Error::Config(format!("{}: {e}", path.display()));
+    let text = match std::fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if !required && profile.is_none() && e.kind() == io::ErrorKind::NotFound => {
+            return from_env()
+        }
+        Err(e) => return Err(invalid(&e)),
+    };
+    let mut table: toml::Table = toml::from_str(&text).map_err(|e| invalid(&e))?;
+    let mut profiles = match table.remove("profile") {
+        Some(toml::Value::Table(profiles)) => profiles,
+        Some(_) => {
+            return Err(invalid(
+                &"profile must be a table of [profile.NAME] sections",
+            ))
+        }
+        None => toml::Table::new(),
+    };
+    table.extend(env_table());
+    if let Some(name) = profile {
+        match profiles.remove(name) {
+            Some(toml::Value::Table(section)) => table.extend(section),
+            Some(_) => return Err(invalid(&format!("profile.{name} must be a table"))),
+            None => return Err(invalid(&format!("no [profile.{name}] section"))),
+        }
+    }
+
+    toml::Value::Table(table)
+        .try_into()
+        .map_err(|e| invalid(&format!("{e}, or in a WAILA_ environment variable")))
+}
+
+/// The settings of the environment variables alone, for `--no-config`
+pub fn from_env() -> Result<Config> {
+    toml::Value::Table(env_table())
+        .try_into()
+        .map_err(|e| Error::Config(format!("in a WAILA_ environment variable: {e}")))
+}
+
+/// The keys set by environment variables, with the types of the config file
+fn env_table() -> toml::Table {
+    let mut table = toml::Table::new();
+    for (var, key) in ENV {
+        let Ok(value) = std::env::var(var) else {
+            continue;
+        };
+        // a value of the wrong type is left a string, for the error to name the key
+        let value = match key {
+            "fiat" | "relays" => toml::Value::Array(
+                value
+                    .split(',')
+                    .map(|item| toml::Value::String(item.trim().to_string()))
+                    .collect(),
+            ),
+            "cache-ttl" => value
+                .parse()
+                .map_or(toml::Value::String(value), toml::Value::Integer),
+            "online" | "resolve" | "record-history" | "hash-inputs" => value
+                .parse()
+                .map_or(toml::Value::String(value), toml::Value::Boolean),
+            _ => toml::Value::String(value),
+        };
+        table.insert(key.to_string(), value);
+    }
+
+    table
+}
+
+fn default_path() -> Option<PathBuf> {
+    std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .map(|config| config.join("waila").join("config.toml"))
+}
+
+impl Config {
+    /// The relays of `convert nostr` when none are given with `--relay`
+    pub fn relays(&self) -> Vec<String> {
+        self.relays.clone().unwrap_or_default()
+    }
+
+    /// Set the options of `args` that `matches` tells were not given on the command line
+    pub fn apply(self, args: &mut Args, matches: &ArgMatches) -> Result<()> {
+        let unset = |id: &str| matches.value_source(id) != Some(ValueSource::CommandLine);
+
+        let unit = self
+            .units
+            .map(|unit| match unit.to_lowercase() {
+                unit if UNITS.contains(&unit.as_str()) => Ok(unit),
+                _ => Err(Error::Config(format!(
+                    "invalid units: {unit}, expected one of {}",
+                    UNITS.join(", ")
+                ))),
+            })
+            .transpose()?;
+        set(&mut args.unit, unit, unset("unit"));
+        set(
+            &mut args.format,
+            value_enum::<Format>("format", self.format)?,
+            unset("format"),
+        );
+        set(
+            &mut args.style.color,
+            value_enum::<render::Color>("color", self.color)?,
+            unset("color"),
+        );
+        set(
+            &mut args.style.key_case,
+            value_enum::<render::KeyCase>("key-case", self.key_case)?,
+            unset("key_case"),
+        );
+        set(&mut args.fiat, self.fiat, unset("fiat"));
+        set(
+            &mut args.rate_source,
+            value_enum::<fiat::Source>("rate-source", self.rate_source)?,
+            unset("rate_source"),
+        );
+        set(&mut args.proxy, self.proxy.map(Some), unset("proxy"));
+        set(&mut args.dns_server, self.dns_server, unset("dns_server"));
+        set(&mut args.doh, self.doh.map(Some), unset("doh"));
+        set(&mut args.esplora, self.esplora.map(Some), unset("esplora"));
+        let esplora_urls = self
+            .esplora_url
+            .map(|urls| {
+                urls.into_iter()
+                    .map(|(network, url)| {
+                        esplora::network_url(&format!("{network}={url}")).map_err(|e| {
+                            Error::Config(format!("invalid esplora-url.{network}: {e}"))
+                        })
+                    })
+                    .collect::<Result<Vec<_>>>()
+            })
+            .transpose()?;
+        set(&mut args.esplora_urls, esplora_urls, unset("esplora_urls"));
+        set(
+            &mut args.electrum,
+            self.electrum.map(Some),
+            unset("electrum"),
+        );
+        set(
+            &mut args.bitcoind,
+            self.bitcoind.map(Some),
+            unset("bitcoind"),
+        );
+        set(
+            &mut args.bitcoind_cookie,
+            self.bitcoind_cookie.map(Some),
+            unset("bitcoind_cookie"),
+        );
+        set(&mut args.cln_rpc, self.cln_rpc.map(Some), unset("cln_rpc"));
+        set(
+            &mut args.cln_rest,
+            self.cln_rest.map(Some),
+            unset("cln_rest"),
+        );
+        set(
+            &mut args.graph,
+            value_enum::<graph::Graph>("graph", self.graph)?,
+            unset("graph"),
+        );
+        set(
+            &mut args.graph_url,
+            self.graph_url.map(Some),
+            unset("graph_url"),
+        );
+        set(&mut args.cache_ttl, self.cache_ttl, unset("cache_ttl"));
+        set(&mut args.online, self.online, unset("online"));
+        set(&mut args.resolve, self.resolve, unset("resolve"));
+        set(
+            &mut args.record_history,
+            self.record_history,
+            unset("record_history"),
+        );
+        set(
+            &mut args.hash_inputs,
+            self.hash_inputs,
+            unset("hash_inputs"),
+        );
+        let network = self
+            .expect_network
+            .map(|network| match network.to_lowercase() {
+                network if NETWORKS.contains(&network.as_str()) => Ok(network),
+                _ => Err(Error::Config(format!("invalid expect-network: {network}"))),
+            })
+            .transpose()?;
+        set(
+            &mut args.expect_network,
+            network.map(Some),
+            unset("expect_network"),
+        );
+
+        Ok(())
+    }
+}
+
+fn set<T>(field: &mut T, value: Option<T>, unset: bool) {
+    if let (Some(value), true) = (value, unset) {
+        *field = value;
+    }
+}
+
+/// The value named `value` of the enum set by `key`
+fn value_enum<T: ValueEnum>(key: &str, value: Option<String>) -> Result<Option<T>> {
+    value
+        .map(|value| {
+            T::from_str(&value, true).map_err(|_| Error::Config(format!("invalid {key}: {value}")))
+        })
+        .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Cli;
+    use clap::{CommandFactory, FromArgMatches};
+
+    const CONFIG: &str = r#"
+units = "btc"
+format = "markdown"
+graph = "mempool"
+cache-ttl = 600
+
+[profile.work]
+units = "sat"
+cache-ttl = 60
+"#;
+
+    #[test]
+    fn settings_override_those_before_them() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("waila-config-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join("config.toml");
+        std::fs::write(&path, CONFIG)?;
+        // no other test reads these variables
+        std::env::set_var("WAILA_UNITS", "mbtc");
+        std::env::set_var("WAILA_GRAPH", "amboss");
+        let profile = load(Some(&path), Some("work"));
+        let file = load(Some(&path), None);
+        std::env::remove_var("WAILA_UNITS");
+        std::env::remove_var("WAILA_GRAPH");
+        std::fs::remove_dir_all(&dir)?;
+
+        let matches = Cli::command().get_matches_from(["waila-cli", "--cache-ttl", "5", "-"]);
+        let mut args = Cli::from_arg_matches(&matches).unwrap().args;
+        profile?.apply(&mut args, &matches)?;
+        // the file alone
+        assert_eq!(args.format, Format::Markdown);
+        // the environment over the file
+        assert_eq!(args.graph, graph::Graph::Amboss);
+        // the profile over the environment
+        assert_eq!(args.unit, "sat");
+        // the command line over the profile
+        assert_eq!(args.cache_ttl, 5);
+
+        let mut args = Cli::from_arg_matches(&matches).unwrap().args;
+        file?.apply(&mut args, &matches)?;
+        assert_eq!(args.unit, "mbtc");
+        assert_eq!(args.cache_ttl, 5);
+
+        Ok(())
+    }
+
+    #[test]
+    fn missing_profiles_are_errors() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("waila-profile-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join("config.toml");
+        std::fs::write(&path, CONFIG)?;
+        let loaded = load(Some(&path), Some("home"));
+        std::fs::remove_dir_all(&dir)?;
+
+        assert!(matches!(loaded, Err(Error::Config(e)) if e.contains("no [profile.home] section")));
+
+        Ok(())
+    }
+}
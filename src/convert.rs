@@ -0,0 +1,129 @@
+//! Conversions of keys and addresses between their encodings
+
+use crate::output::{NostrKeys, Script};
+use crate::{Error, Result};
+use bitcoin::hashes::{sha256, Hash};
+use bitcoin::{Address, ScriptBuf};
+use std::str::FromStr;
+#[cfg(feature = "nostr")]
+use {
+    crate::output::{NostrKey, NostrSecret},
+    crate::secret::mask,
+    nostr::key::{Keys, SecretKey, XOnlyPublicKey},
+    nostr::nips::nip19::{FromBech32, Profile, ToBech32},
+};
+
+/// A nostr key, given in hex or as an npub, nsec or nprofile, in every NIP-19 encoding.
+/// A hex key is read as a secret key if `secret` is set. The nprofile hints at the
+/// relays of a given nprofile and `relays`. A secret key is masked unless `show_secrets`.
+#[cfg(feature = "nostr")]
+pub fn nostr(key: &str, secret: bool, relays: &[String], show_secrets: bool) -> Result<NostrKeys> {
+    let mut hints = vec![];
+    let mut secret_key = None;
+    let pubkey = if secret {
+        let sk = SecretKey::from_str(key)
+            .map_err(|_| Error::InvalidKey(format!("not a nostr secret key in hex: {key}")))?;
+        secret_key = Some(sk);
+        Keys::new(sk).public_key()
+    } else if let Ok(pubkey) = XOnlyPublicKey::from_str(key) {
+        pubkey
+    } else if let Ok(pubkey) = XOnlyPublicKey::from_bech32(key) {
+        pubkey
+    } else if let Ok(sk) = SecretKey::from_bech32(key) {
+        secret_key = Some(sk);
+        Keys::new(sk).public_key()
+    } else if let Ok(profile) = Profile::from_bech32(key) {
+        hints = profile.relays;
+        profile.public_key
+    } else {
+        return Err(Error::InvalidKey(format!("not a nostr key: {key}")));
+    };
+    for relay in relays {
+        if !hints.contains(relay) {
+            hints.push(relay.clone());
+        }
+    }
+
+    let secret = match secret_key {
+        Some(sk) => {
+            let (hex, nsec) = (sk.display_secret().to_string(), sk.to_bech32()?);
+            Some(if show_secrets {
+                NostrSecret {
+                    hex,
+                    nsec,
+                    redacted: false,
+                }
+            } else {
+                NostrSecret {
+                    hex: mask(&hex),
+                    nsec: mask(&nsec),
+                    redacted: true,
+                }
+            })
+        }
+        None => None,
+    };
+
+    Ok(NostrKeys {
+        key: NostrKey {
+            hex: pubkey.to_string(),
+            bech32: pubkey.to_bech32()?,
+        },
+        nprofile: Profile::new(pubkey, hints.clone()).to_bech32()?,
+        relays: hints,
+        secret,
+    })
+}
+
+#[cfg(not(feature = "nostr"))]
+pub fn nostr(
+    _key: &str,
+    _secret: bool,
+    _relays: &[String],
+    _show_secrets: bool,
+) -> Result<NostrKeys> {
+    Err(Error::Unsupported("nostr"))
+}
+
+/// The output script of `address` and the hashes identifying it
+pub fn script(address: &str) -> Result<Script> {
+    let Ok(parsed) = Address::from_str(address) else {
+        return Err(Error::InvalidKey(format!("not an address: {address}")));
+    };
+    let parsed = parsed.assume_checked();
+    let script = parsed.script_pubkey();
+    let bytes = script.as_bytes();
+    let hash160 = if script.is_p2pkh() {
+        Some(hex(&bytes[3..23]))
+    } else if script.is_p2sh() {
+        Some(hex(&bytes[2..22]))
+    } else {
+        None
+    };
+    // the program follows the version and push opcodes
+    let witness_version = script.witness_version().map(|version| version.to_num());
+    let witness_program = witness_version.map(|_| hex(&bytes[2..]));
+
+    Ok(Script {
+        address: address.to_string(),
+        address_type: parsed.address_type().map(|kind| kind.to_string()),
+        script_pubkey: hex(bytes),
+        script_asm: script.to_asm_string(),
+        hash160,
+        witness_version,
+        witness_program,
+        scripthash: scripthash(&script),
+    })
+}
+
+/// The Electrum script hash, the sha256 of the script in reverse byte order, as hex
+pub fn scripthash(script: &ScriptBuf) -> String {
+    let mut hash = sha256::Hash::hash(script.as_bytes()).to_byte_array();
+    hash.reverse();
+
+    hex(&hash)
+}
+
+pub(crate) fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
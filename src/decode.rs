@@ -0,0 +1,100 @@
+//! Generic decoding of bech32 and Base58Check strings, for those of kinds waila
+//! doesn't know, such as new human-readable parts, vendor and legacy formats
+
+use crate::convert::hex;
+use crate::{checksum, Error, Result};
+use bitcoin::base58;
+use bitcoin::bech32::{self, FromBase32, Variant};
+use bitcoin::hashes::{sha256d, Hash};
+use serde::Serialize;
+
+/// The parts of a bech32 or bech32m string
+#[derive(Debug, Clone, Serialize)]
+pub struct Bech32 {
+    pub hrp: String,
+    /// `bech32` or `bech32m`
+    pub variant: &'static str,
+    /// Number of 5-bit values between the separator and the checksum
+    pub data_length: usize,
+    /// The values in 8-bit bytes, as hex. None if they leave padding bits set, as
+    /// the version prefixed program of a segwit address does.
+    pub payload: Option<String>,
+    /// The version and program, in hex, if the data reads as those of a witness
+    /// program after its first value
+    pub witness_version: Option<u8>,
+    pub witness_program: Option<String>,
+}
+
+/// The parts of a Base58Check string
+#[derive(Debug, Clone, Serialize)]
+pub struct Base58 {
+    /// The first byte, which tells the kind of most formats
+    pub version: u8,
+    /// The bytes after the version, as hex
+    pub payload: String,
+    /// The last 4 bytes, as hex
+    pub checksum: String,
+    pub checksum_valid: bool,
+    /// The checksum the data has, if it isn't the one given
+    pub expected_checksum: Option<String>,
+}
+
+/// The human-readable part, variant and payload of any bech32 string. One whose
+/// checksum fails is diagnosed like a query.
+pub fn bech32(s: &str) -> Result<Bech32> {
+    let s = s.trim();
+    let (hrp, data, variant) = bech32::decode(s).map_err(|e| {
+        match checksum::diagnose(s).filter(|diagnosis| diagnosis.encoding == "bech32") {
+            Some(diagnosis) => Error::Checksum(diagnosis, vec![]),
+            None => Error::InvalidPart(format!("not a bech32 string: {e}")),
+        }
+    })?;
+
+    // the version is a single value, the program packed in bytes after it
+    let witness = match data.split_first() {
+        Some((version, program)) if version.to_u8() <= 16 => Vec::<u8>::from_base32(program)
+            .ok()
+            .filter(|program| (2..=40).contains(&program.len()))
+            .map(|program| (version.to_u8(), hex(&program))),
+        _ => None,
+    };
+    let (witness_version, witness_program) = witness.unzip();
+
+    Ok(Bech32 {
+        hrp,
+        variant: match variant {
+            Variant::Bech32 => "bech32",
+            Variant::Bech32m => "bech32m",
+        },
+        data_length: data.len(),
+        payload: Vec::<u8>::from_base32(&data).ok().map(|bytes| hex(&bytes)),
+        witness_version,
+        witness_program,
+    })
+}
+
+/// The version, payload and checksum of any Base58Check string, telling whether the
+/// checksum is valid rather than failing if it isn't
+pub fn base58(s: &str) -> Result<Base58> {
+    let bytes = base58::decode(s.trim())
+        .map_err(|e| Error::InvalidPart(format!("not a base58 string: {e}")))?;
+    // a version byte and the checksum
+    if bytes.len() < 5 {
+        return Err(Error::InvalidPart(format!(
+            "{} bytes are too few for Base58Check, which has a version and a 4 byte checksum",
+            bytes.len()
+        )));
+    }
+    let (data, checksum) = bytes.split_at(bytes.len() - 4);
+    let hash = sha256d::Hash::hash(data);
+    let expected = &hash[..4];
+    let checksum_valid = checksum == expected;
+
+    Ok(Base58 {
+        version: data[0],
+        payload: hex(&data[1..]),
+        checksum: hex(checksum),
+        checksum_valid,
+        expected_checksum: (!checksum_valid).then(|| hex(expected)),
+    })
+}
@@ -0,0 +1,105 @@
+//! Links opening a payment string in one mobile wallet, through the URI scheme the app
+//! registers, rather than `bitcoin:` or `lightning:` which any installed wallet may claim.
+
+use crate::{kind, suggest, Error, Result};
+use bitcoin_waila::PaymentParams;
+use clap::ValueEnum;
+use serde::Serialize;
+use std::str::FromStr;
+
+/// Mobile wallets with a URI scheme of their own
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Wallet {
+    Phoenix,
+    Zeus,
+    Bluewallet,
+    Muun,
+}
+
+impl Wallet {
+    fn name(self) -> &'static str {
+        match self {
+            Wallet::Phoenix => "phoenix",
+            Wallet::Zeus => "zeus",
+            Wallet::Bluewallet => "bluewallet",
+            Wallet::Muun => "muun",
+        }
+    }
+
+    /// The scheme the app registers, put before the `bitcoin:` or `lightning:` URI
+    fn scheme(self) -> &'static str {
+        match self {
+            Wallet::Phoenix => "phoenix",
+            Wallet::Zeus => "zeusln",
+            Wallet::Bluewallet => "bluewallet",
+            Wallet::Muun => "muun",
+        }
+    }
+
+    /// The kinds of payment strings the app opens
+    fn opens(self) -> &'static [&'static str] {
+        match self {
+            Wallet::Phoenix => &[
+                "OnChain",
+                "UnifiedUri",
+                "Invoice",
+                "Offer",
+                "LnUrl",
+                "LnAddress",
+            ],
+            Wallet::Zeus | Wallet::Bluewallet => {
+                &["OnChain", "UnifiedUri", "Invoice", "LnUrl", "LnAddress"]
+            }
+            Wallet::Muun => &["OnChain", "UnifiedUri", "Invoice", "LnUrl"],
+        }
+    }
+}
+
+/// A payment string as a link for one wallet
+#[derive(Debug, Clone, Serialize)]
+pub struct Deeplink {
+    pub wallet: String,
+    pub kind: String,
+    /// The `bitcoin:` or `lightning:` URI of the string, which any wallet opens
+    pub uri: String,
+    /// The link opening the URI in the wallet
+    pub link: String,
+}
+
+/// The link opening `s` in `wallet`
+pub fn deeplink(s: &str, wallet: Wallet) -> Result<Deeplink> {
+    let s = s.trim();
+    let payment_params =
+        PaymentParams::from_str(s).map_err(|_| Error::Unrecognized(suggest::suggest(s)))?;
+    let kind = kind(&payment_params);
+    if !wallet.opens().contains(&kind) {
+        return Err(Error::InvalidPart(format!(
+            "{} doesn't open a {kind}, it opens {}",
+            wallet.name(),
+            wallet.opens().join(", ")
+        )));
+    }
+
+    let uri = match &payment_params {
+        PaymentParams::OnChain(_) => match payment_params.address() {
+            Some(addr) => format!("bitcoin:{addr}"),
+            None => format!("bitcoin:{s}"),
+        },
+        PaymentParams::Bip21(_) => s.to_string(),
+        _ => {
+            // the string may come with its scheme already, in either case
+            let bare = match s.get(..10) {
+                Some(scheme) if scheme.eq_ignore_ascii_case("lightning:") => &s[10..],
+                _ => s,
+            };
+            format!("lightning:{bare}")
+        }
+    };
+
+    Ok(Deeplink {
+        wallet: wallet.name().to_string(),
+        kind: kind.to_string(),
+        link: format!("{}:{uri}", wallet.scheme()),
+        uri,
+    })
+}
@@ -0,0 +1,344 @@
+//! Addresses derived from an extended public key or a descriptor, to check them
+//! against those a wallet shows. The SLIP-132 ypub and zpub variants select their
+//! script type, descriptors are read with full miniscript semantics.
+
+use crate::{secret, Error, Result};
+use bitcoin::base58;
+use bitcoin::bip32::{ChildNumber, DerivationPath, ExtendedPubKey};
+use bitcoin::secp256k1::{Secp256k1, Verification};
+use bitcoin::{Address, Network, PublicKey};
+use clap::ValueEnum;
+use miniscript::descriptor::{Descriptor, DescriptorPublicKey};
+use miniscript::ForEachKey;
+use serde_json::{json, Map, Value};
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+
+/// Version bytes of extended public keys, the script type a variant stands for and
+/// whether it is for mainnet
+const VERSIONS: [([u8; 4], Option<ScriptType>, bool); 6] = [
+    ([0x04, 0x88, 0xb2, 0x1e], None, true),
+    ([0x04, 0x9d, 0x7c, 0xb2], Some(ScriptType::ShWpkh), true),
+    ([0x04, 0xb2, 0x47, 0x46], Some(ScriptType::Wpkh), true),
+    ([0x04, 0x35, 0x87, 0xcf], None, false),
+    ([0x04, 0x4a, 0x52, 0x62], Some(ScriptType::ShWpkh), false),
+    ([0x04, 0x5f, 0x1c, 0xf6], Some(ScriptType::Wpkh), false),
+];
+
+const XPUB: [u8; 4] = [0x04, 0x88, 0xb2, 0x1e];
+const TPUB: [u8; 4] = [0x04, 0x35, 0x87, 0xcf];
+
+/// Script types of single key addresses
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScriptType {
+    /// Legacy, BIP-44
+    Pkh,
+    /// Nested segwit, BIP-49
+    ShWpkh,
+    /// Native segwit, BIP-84
+    Wpkh,
+    /// Taproot key path, BIP-86
+    Tr,
+}
+
+impl ScriptType {
+    pub const ALL: [ScriptType; 4] = [
+        ScriptType::Pkh,
+        ScriptType::ShWpkh,
+        ScriptType::Wpkh,
+        ScriptType::Tr,
+    ];
+
+    /// Name of the addresses in the output
+    fn as_str(self) -> &'static str {
+        match self {
+            ScriptType::Pkh => "p2pkh",
+            ScriptType::ShWpkh => "p2sh-p2wpkh",
+            ScriptType::Wpkh => "p2wpkh",
+            ScriptType::Tr => "p2tr",
+        }
+    }
+
+    fn address<C: Verification>(
+        self,
+        secp: &Secp256k1<C>,
+        key: PublicKey,
+        network: Network,
+    ) -> Result<Address> {
+        let uncompressed =
+            |_| Error::InvalidKey("an uncompressed key has no segwit address".into());
+        match self {
+            ScriptType::Pkh => Ok(Address::p2pkh(&key, network)),
+            ScriptType::ShWpkh => Address::p2shwpkh(&key, network).map_err(uncompressed),
+            ScriptType::Wpkh => Address::p2wpkh(&key, network).map_err(uncompressed),
+            ScriptType::Tr => Ok(Address::p2tr(secp, key.inner.into(), None, network)),
+        }
+    }
+}
+
+/// The first `count` addresses of `xpub` along `path`, e.g. `0/*`, whose `*` stands for
+/// the index. Without `types`, those of the script type of a ypub or zpub are given,
+/// and those of every type for an xpub or tpub.
+pub fn addresses(xpub: &str, path: &str, count: u32, types: &[ScriptType]) -> Result<Value> {
+    let (key, implied) = parse_xpub(xpub)?;
+    let types = match (types, implied) {
+        ([], Some(implied)) => vec![implied],
+        ([], None) => ScriptType::ALL.to_vec(),
+        (types, _) => types.to_vec(),
+    };
+    let (prefix, steps) = parse_path(path)?;
+
+    let secp = Secp256k1::verification_only();
+    let account = key
+        .derive_pub(&secp, &steps)
+        .map_err(|e| Error::InvalidKey(e.to_string()))?;
+    let mut rows = vec![];
+    for index in 0..count {
+        let child =
+            ChildNumber::from_normal_idx(index).map_err(|e| Error::InvalidKey(e.to_string()))?;
+        let derived = account
+            .ckd_pub(&secp, child)
+            .map_err(|e| Error::InvalidKey(e.to_string()))?;
+        let pubkey = PublicKey::new(derived.public_key);
+        let mut row = Map::new();
+        row.insert("path".to_string(), json!(format!("{prefix}{index}")));
+        for script_type in &types {
+            let address = script_type.address(&secp, pubkey, key.network)?;
+            row.insert(script_type.as_str().to_string(), json!(address.to_string()));
+        }
+        rows.push(Value::Object(row));
+    }
+
+    Ok(json!({
+        "xpub": xpub,
+        "network": key.network.to_string(),
+        "fingerprint": key.fingerprint().to_string(),
+        "addresses": rows,
+    }))
+}
+
+/// The key of `s`, an xpub, tpub or one of their SLIP-132 variants, and the script
+/// type a variant implies
+fn parse_xpub(s: &str) -> Result<(ExtendedPubKey, Option<ScriptType>)> {
+    let invalid = || Error::InvalidKey(format!("not an extended public key: {s}"));
+    let mut bytes = base58::decode_check(s).map_err(|_| invalid())?;
+    if bytes.len() != 78 {
+        return Err(invalid());
+    }
+    let Some((_, implied, mainnet)) = VERSIONS.iter().find(|(version, ..)| bytes[..4] == *version)
+    else {
+        return Err(invalid());
+    };
+    // the variants differ from the xpub and tpub only in their version
+    bytes[..4].copy_from_slice(if *mainnet { &XPUB } else { &TPUB });
+    let key = ExtendedPubKey::decode(&bytes).map_err(|_| invalid())?;
+
+    Ok((key, *implied))
+}
+
+/// The steps of `path`, e.g. `0/*`, before its trailing `*`, and their text
+fn parse_path(path: &str) -> Result<(String, DerivationPath)> {
+    let path = path.trim_start_matches("m/");
+    let Some(prefix) = path.strip_suffix('*') else {
+        return Err(Error::InvalidKey(format!(
+            "the path must end in /* for the index: {path}"
+        )));
+    };
+    if prefix.contains(['\'', 'h', 'H']) {
+        return Err(Error::InvalidKey(format!(
+            "an xpub can't derive hardened steps: {path}"
+        )));
+    }
+    let steps = match prefix.trim_end_matches('/') {
+        "" => DerivationPath::master(),
+        steps => DerivationPath::from_str(&format!("m/{steps}"))
+            .map_err(|e| Error::InvalidKey(format!("invalid path {path}: {e}")))?,
+    };
+
+    Ok((prefix.to_string(), steps))
+}
+
+/// The addresses of `descriptor` at each index of `range`, or its single address if it
+/// has no `*`. A multipath descriptor, e.g. with `<0;1>/*`, gives the addresses of each
+/// path. Without `network`, that of its keys is used, testnet for a tpub.
+pub fn descriptor_addresses(
+    descriptor: &str,
+    range: RangeInclusive<u32>,
+    network: Option<Network>,
+) -> Result<Value> {
+    if secret::redact_keys(descriptor).is_some() {
+        return Err(Error::InvalidKey(
+            "the descriptor holds private keys, give its public version".into(),
+        ));
+    }
+    let invalid = |e: &dyn std::fmt::Display| Error::InvalidKey(format!("invalid descriptor: {e}"));
+    let parsed =
+        Descriptor::<DescriptorPublicKey>::from_str(descriptor).map_err(|e| invalid(&e))?;
+    let network = network.unwrap_or_else(|| key_network(&parsed));
+    let singles = if parsed.is_multipath() {
+        parsed.into_single_descriptors().map_err(|e| invalid(&e))?
+    } else {
+        vec![parsed]
+    };
+
+    let mut descriptors = vec![];
+    for single in singles {
+        let indexes = if single.has_wildcard() {
+            range.clone()
+        } else {
+            0..=0
+        };
+        let mut rows = vec![];
+        for index in indexes {
+            let address = single
+                .at_derivation_index(index)
+                .map_err(|e| invalid(&e))?
+                .address(network)
+                .map_err(|e| invalid(&e))?;
+            rows.push(json!({
+                "index": single.has_wildcard().then_some(index),
+                "address": address.to_string(),
+            }));
+        }
+        descriptors.push(json!({
+            "descriptor": single.to_string(),
+            "addresses": rows,
+        }));
+    }
+
+    Ok(json!({
+        "network": network.to_string(),
+        "descriptors": descriptors,
+    }))
+}
+
+/// The network of the keys of `descriptor`, `None` unless it is a valid public descriptor
+pub fn descriptor_network(descriptor: &str) -> Option<Network> {
+    let parsed = Descriptor::<DescriptorPublicKey>::from_str(descriptor).ok()?;
+
+    Some(key_network(&parsed))
+}
+
+/// The network of the extended keys of `descriptor`, mainnet unless one is for a test network
+fn key_network(descriptor: &Descriptor<DescriptorPublicKey>) -> Network {
+    let mut network = Network::Bitcoin;
+    descriptor.for_each_key(|key| {
+        let xkey_network = match key {
+            DescriptorPublicKey::XPub(xpub) => Some(xpub.xkey.network),
+            DescriptorPublicKey::MultiXPub(xpub) => Some(xpub.xkey.network),
+            DescriptorPublicKey::Single(_) => None,
+        };
+        if let Some(test) = xkey_network.filter(|net| *net != Network::Bitcoin) {
+            network = test;
+        }
+        true
+    });
+
+    network
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the account keys of the BIP-84 and BIP-86 test vectors
+    const ZPUB: &str = "zpub6rFR7y4Q2AijBEqTUquhVz398htDFrtymD9xYYfG1m4wAcvPhXNfE3EfH1r1ADqtfSdVCToUG868RvUUkgDKf31mGDtKsAYz2oz2AGutZYs";
+    const XPUB: &str = "xpub6BgBgsespWvERF3LHQu6CnqdvfEvtMcQjYrcRzx53QJjSxarj2afYWcLteoGVky7D3UKDP9QyrLprQ3VCECoY49yfdDEHGCtMMj92pReUsQ";
+
+    #[test]
+    fn variants_give_their_script_type() {
+        let derived = addresses(ZPUB, "0/*", 2, &[]).unwrap();
+        assert_eq!(derived["network"], "bitcoin");
+        assert_eq!(derived["fingerprint"], "fd13aac9");
+        assert_eq!(
+            derived["addresses"],
+            json!([
+                {"path": "0/0", "p2wpkh": "bc1qcr8te4kr609gcawutmrza0j4xv80jy8z306fyu"},
+                {"path": "0/1", "p2wpkh": "bc1qnjg0jd8228aq7egyzacy8cys3knf9xvrerkf9g"},
+            ])
+        );
+    }
+
+    #[test]
+    fn xpubs_give_the_types_asked_for() {
+        let derived = addresses(XPUB, "m/1/*", 1, &[ScriptType::Tr]).unwrap();
+        assert_eq!(
+            derived["addresses"],
+            json!([{
+                "path": "1/0",
+                "p2tr": "bc1p3qkhfews2uk44qtvauqyr2ttdsw7svhkl9nkm9s9c3x4ax5h60wqwruhk7",
+            }])
+        );
+        // every type without any asked for
+        let derived = addresses(XPUB, "0/*", 1, &[]).unwrap();
+        let row = derived["addresses"][0].as_object().unwrap();
+        assert_eq!(row.len(), 1 + ScriptType::ALL.len());
+        assert_eq!(
+            row["p2tr"],
+            "bc1p5cyxnuxmeuwuvkwfem96lqzszd02n6xdcjrs20cac6yqjjwudpxqkedrcr"
+        );
+    }
+
+    #[test]
+    fn underivable_paths_are_refused() {
+        assert!(matches!(
+            addresses(XPUB, "0'/*", 1, &[]),
+            Err(Error::InvalidKey(_))
+        ));
+        assert!(matches!(
+            addresses(XPUB, "0/0", 1, &[]),
+            Err(Error::InvalidKey(_))
+        ));
+        assert!(matches!(
+            addresses("bc1qcr8te4kr609gcawutmrza0j4xv80jy8z306fyu", "0/*", 1, &[]),
+            Err(Error::InvalidKey(_))
+        ));
+    }
+
+    #[test]
+    fn descriptors_give_the_addresses_of_each_path() {
+        let derived = descriptor_addresses(&format!("tr({XPUB}/<0;1>/*)"), 0..=1, None).unwrap();
+        assert_eq!(derived["network"], "bitcoin");
+        let paths = |i: usize| {
+            derived["descriptors"][i]["addresses"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|row| row["address"].clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            paths(0),
+            [
+                "bc1p5cyxnuxmeuwuvkwfem96lqzszd02n6xdcjrs20cac6yqjjwudpxqkedrcr",
+                "bc1p4qhjn9zdvkux4e44uhx8tc55attvtyu358kutcqkudyccelu0was9fqzwh",
+            ]
+        );
+        assert_eq!(
+            paths(1),
+            [
+                "bc1p3qkhfews2uk44qtvauqyr2ttdsw7svhkl9nkm9s9c3x4ax5h60wqwruhk7",
+                "bc1ptdg60grjk9t3qqcqczp4tlyy3z47yrx9nhlrjsmw36q5a72lhdrs9f00nj",
+            ]
+        );
+
+        // a descriptor without a wildcard has a single address
+        let derived = descriptor_addresses(&format!("tr({XPUB}/0/0)"), 0..=9, None).unwrap();
+        assert_eq!(
+            derived["descriptors"][0]["addresses"],
+            json!([{
+                "index": null,
+                "address": "bc1p5cyxnuxmeuwuvkwfem96lqzszd02n6xdcjrs20cac6yqjjwudpxqkedrcr",
+            }])
+        );
+    }
+
+    #[test]
+    fn private_descriptors_are_refused() {
+        let xprv = "xprv9s21ZrQH143K3QTDL4LXw2F7HEK3wJUD2nW2nRk4stbPy6cq3jPPqjiChkVvvNKmPGJxWUtg6LnF5kejMRNNU3TGtRBeJgk33yuGBxrMPHi";
+        assert!(matches!(
+            descriptor_addresses(&format!("wpkh({xprv}/0/*)"), 0..=0, None),
+            Err(Error::InvalidKey(e)) if e.contains("private keys")
+        ));
+    }
+}
@@ -0,0 +1,192 @@
+//! Output script descriptors (BIP-380) and their checksums
+
+use serde_json::{json, Map, Value};
+
+const INPUT_CHARSET: &str =
+    "0123456789()[],'/*abcdefgh@:$%{}IJKLMNOPQRSTUVWXYZ&+-.;<=>?!^_|~ijklmnopqrstuvwxyzABCDEFGH`#\"\\ ";
+const CHECKSUM_CHARSET: &[u8; 32] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+
+/// Top level script expressions of BIP-380 to BIP-386
+const SCRIPT_EXPRESSIONS: [&str; 12] = [
+    "pk(",
+    "pkh(",
+    "wpkh(",
+    "sh(",
+    "wsh(",
+    "tr(",
+    "rawtr(",
+    "combo(",
+    "multi(",
+    "sortedmulti(",
+    "addr(",
+    "raw(",
+];
+
+/// A descriptor and its checksum
+#[derive(Debug, Clone)]
+pub struct Descriptor {
+    /// The descriptor without checksum
+    pub body: String,
+    /// The checksum that came with the descriptor, if any
+    pub given: Option<String>,
+    /// The correct checksum of `body`
+    pub checksum: String,
+}
+
+/// Try to read `s` as a descriptor, with or without checksum
+pub fn detect(s: &str) -> Option<Descriptor> {
+    if !SCRIPT_EXPRESSIONS.iter().any(|expr| s.starts_with(expr)) {
+        return None;
+    }
+    let (body, given) = match s.rsplit_once('#') {
+        Some((body, given)) => (body, Some(given.to_string())),
+        None => (s, None),
+    };
+    if !body.ends_with(')') {
+        return None;
+    }
+
+    Some(Descriptor {
+        body: body.to_string(),
+        given,
+        checksum: checksum(body)?,
+    })
+}
+
+impl Descriptor {
+    /// Whether a checksum came with the descriptor and is wrong
+    pub fn is_mismatch(&self) -> bool {
+        self.given
+            .as_ref()
+            .is_some_and(|given| *given != self.checksum)
+    }
+
+    /// Construct a json map describing the descriptor. The descriptor is given with
+    /// its correct checksum, which repairs one copied without it.
+    pub fn describe(&self) -> Map<String, Value> {
+        let mut map = Map::new();
+        map.insert(
+            "descriptor".to_string(),
+            json!(format!("{}#{}", self.body, self.checksum)),
+        );
+        map.insert("checksum".to_string(), json!(self.checksum));
+        map.insert("checksum_given".to_string(), json!(self.given.is_some()));
+
+        map
+    }
+}
+
+/// The BIP-380 checksum of the descriptor `body`, `None` if it has characters
+/// outside of the descriptor character set
+pub fn checksum(body: &str) -> Option<String> {
+    let mut c: u64 = 1;
+    let mut cls = 0;
+    let mut cls_count = 0;
+    for ch in body.chars() {
+        let pos = INPUT_CHARSET.find(ch)? as u64;
+        // symbols are fed in groups of 5 bits, their character class in groups of three
+        c = polymod(c, pos & 31);
+        cls = cls * 3 + (pos >> 5);
+        cls_count += 1;
+        if cls_count == 3 {
+            c = polymod(c, cls);
+            cls = 0;
+            cls_count = 0;
+        }
+    }
+    if cls_count > 0 {
+        c = polymod(c, cls);
+    }
+    for _ in 0..8 {
+        c = polymod(c, 0);
+    }
+    c ^= 1;
+
+    Some(
+        (0..8)
+            .map(|j| CHECKSUM_CHARSET[((c >> (5 * (7 - j))) & 31) as usize] as char)
+            .collect(),
+    )
+}
+
+fn polymod(c: u64, val: u64) -> u64 {
+    const GEN: [u64; 5] = [
+        0xf5_dee5_1989,
+        0xa9_fdca_3312,
+        0x1b_ab10_e32d,
+        0x37_06b1_677a,
+        0x64_4d62_6ffd,
+    ];
+    let top = c >> 35;
+    let mut c = ((c & 0x7_ffff_ffff) << 5) ^ val;
+    for (i, gen) in GEN.iter().enumerate() {
+        if (top >> i) & 1 == 1 {
+            c ^= gen;
+        }
+    }
+
+    c
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checksum_matches_bip380() {
+        assert_eq!(checksum("raw(deadbeef)").as_deref(), Some("89f8spxm"));
+        assert_eq!(
+            checksum("addr(mkmZxiEcEd8ZqjQWVZuC6so5dFMKEFpN2j)").as_deref(),
+            Some("02wpgw69")
+        );
+        assert_eq!(
+            checksum("pkh(02c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5)")
+                .as_deref(),
+            Some("8fhd9pwu")
+        );
+        // outside of the character set
+        assert_eq!(checksum("raw(Ü)"), None);
+    }
+
+    #[test]
+    fn valid_checksum() {
+        let desc = detect("raw(deadbeef)#89f8spxm").unwrap();
+        assert_eq!(desc.body, "raw(deadbeef)");
+        assert_eq!(desc.given.as_deref(), Some("89f8spxm"));
+        assert!(!desc.is_mismatch());
+    }
+
+    #[test]
+    fn invalid_checksum() {
+        for s in [
+            // an error in the payload
+            "raw(dedbeef)#89f8spxm",
+            // an error in the checksum
+            "raw(deadbeef)#89f8spxn",
+            // too long or short
+            "raw(deadbeef)#89f8spxmx",
+            "raw(deadbeef)#89f8spx",
+            // a separator without a checksum
+            "raw(deadbeef)#",
+        ] {
+            assert!(detect(s).unwrap().is_mismatch(), "{s}");
+        }
+    }
+
+    #[test]
+    fn missing_checksum_is_added() {
+        let desc = detect("raw(deadbeef)").unwrap();
+        assert_eq!(desc.given, None);
+        assert!(!desc.is_mismatch());
+        let map = desc.describe();
+        assert_eq!(map["descriptor"], "raw(deadbeef)#89f8spxm");
+        assert_eq!(map["checksum_given"], false);
+    }
+
+    #[test]
+    fn other_strings_are_not_descriptors() {
+        assert!(detect("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4").is_none());
+        assert!(detect("raw(deadbeef").is_none());
+        assert!(detect("raw(Ü)").is_none());
+    }
+}
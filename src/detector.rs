@@ -0,0 +1,87 @@
+//! Detectors of formats outside of bitcoin, e.g. in-house voucher codes or custom
+//! URIs. A detector registered with [`register`] is tried on strings that none of
+//! the built in formats recognize.
+
+use crate::Result;
+use serde_json::{Map, Value};
+use std::sync::RwLock;
+
+/// A custom format
+pub trait Detector: Send + Sync {
+    /// The kind reported for matching strings, e.g. `Voucher`
+    fn kind(&self) -> &'static str;
+
+    /// Whether `s` is of this format, which should be cheap to tell
+    fn matches(&self, s: &str) -> bool;
+
+    /// Describe `s`, which matched, as the fields of the output
+    fn parse(&self, s: &str) -> Result<Map<String, Value>>;
+}
+
+/// The registered detectors, in the order they are tried
+static DETECTORS: RwLock<Vec<&'static dyn Detector>> = RwLock::new(vec![]);
+
+/// Try `detector` on strings that are not otherwise recognized, after those
+/// registered before it
+pub fn register(detector: &'static dyn Detector) {
+    DETECTORS
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .push(detector);
+}
+
+/// The first registered detector matching `s`
+pub fn find(s: &str) -> Option<&'static dyn Detector> {
+    DETECTORS
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .find(|detector| detector.matches(s))
+        .copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{analyze, Error, Options};
+    use serde_json::json;
+
+    struct Voucher;
+
+    impl Detector for Voucher {
+        fn kind(&self) -> &'static str {
+            "Voucher"
+        }
+
+        fn matches(&self, s: &str) -> bool {
+            s.starts_with("VOUCHER-")
+        }
+
+        fn parse(&self, s: &str) -> Result<Map<String, Value>> {
+            let code = &s["VOUCHER-".len()..];
+            if !code.bytes().all(|c| c.is_ascii_digit()) {
+                return Err(Error::InvalidPart(format!("not a voucher code: {s}")));
+            }
+            let mut map = Map::new();
+            map.insert("code".to_string(), json!(code));
+
+            Ok(map)
+        }
+    }
+
+    #[test]
+    fn registered_detectors_parse_what_nothing_else_does() {
+        register(&Voucher);
+        assert_eq!(find("VOUCHER-1234").map(|d| d.kind()), Some("Voucher"));
+        assert!(find("VOUCH-1234").is_none());
+
+        let analysis = analyze("VOUCHER-1234", &Options::default()).unwrap();
+        assert_eq!(analysis.kind, "Voucher");
+        let fields = analysis.into_fields();
+        assert_eq!(fields["code"], "1234");
+        assert_eq!(fields["kind"], "Voucher");
+
+        let err = analyze("VOUCHER-12x4", &Options::default()).unwrap_err();
+        assert!(matches!(err, Error::InvalidPart(_)), "{err}");
+    }
+}
@@ -0,0 +1,115 @@
+//! Field by field differences of two payment strings, to tell whether one is the
+//! invoice or URI sent earlier. Both are read offline, and only the fields saying
+//! where the money goes, how much and until when are compared.
+
+use crate::{amount_msat, expiry, kind, node_id, suggest, Error, Result};
+use bitcoin_waila::PaymentParams;
+use serde_json::{json, Map, Value};
+use std::str::FromStr;
+
+/// Compared fields, in the order their differences are listed
+const FIELDS: [&str; 11] = [
+    "kind",
+    "network",
+    "address",
+    "node",
+    "payment_hash",
+    "lnurl",
+    "lnaddr",
+    "payjoin",
+    "amount_msat",
+    "expires_at",
+    "memo",
+];
+
+/// The differences of `a` and `b`. The verdict is `identical` for the same string,
+/// `equivalent` when only their encoding differs, e.g. in case or parameter order, and
+/// `different` otherwise.
+pub fn diff(a: &str, b: &str) -> Result<Value> {
+    let (first, second) = (fields(a)?, fields(b)?);
+    let mut same = vec![];
+    let mut differences = vec![];
+    for field in FIELDS {
+        match (first.get(field), second.get(field)) {
+            (None, None) => {}
+            (x, y) if x == y => same.push(field),
+            (x, y) => differences.push(json!({
+                "field": field,
+                "first": x,
+                "second": y,
+            })),
+        }
+    }
+    let verdict = if a.trim() == b.trim() {
+        "identical"
+    } else if differences.is_empty() {
+        "equivalent"
+    } else {
+        "different"
+    };
+
+    Ok(json!({
+        "first": a,
+        "second": b,
+        "verdict": verdict,
+        "same": same,
+        "differences": differences,
+    }))
+}
+
+/// The compared fields of `s` that it has
+fn fields(s: &str) -> Result<Map<String, Value>> {
+    let payment_params =
+        PaymentParams::from_str(s.trim()).map_err(|_| Error::Unrecognized(suggest::suggest(s)))?;
+    let payment_hash = payment_params.invoice().and_then(|inv| {
+        // convert to the correct type for our imports
+        let inv = lightning_invoice::Bolt11Invoice::from_str(&inv.to_string()).ok()?;
+        Some(inv.payment_hash().to_string())
+    });
+
+    let mut map = Map::new();
+    let mut insert = |key: &str, value: Option<Value>| {
+        if let Some(value) = value {
+            map.insert(key.to_string(), value);
+        }
+    };
+    insert("kind", Some(json!(kind(&payment_params))));
+    insert(
+        "network",
+        payment_params.network().map(|net| json!(net.to_string())),
+    );
+    // parsed values print in one case, so strings differing only in it compare equal
+    insert(
+        "address",
+        payment_params.address().map(|addr| json!(addr.to_string())),
+    );
+    insert("node", node_id(&payment_params).map(|node| json!(node)));
+    insert("payment_hash", payment_hash.map(|hash| json!(hash)));
+    insert(
+        "lnurl",
+        payment_params.lnurl().map(|lnurl| json!(lnurl.to_string())),
+    );
+    insert(
+        "lnaddr",
+        payment_params
+            .lightning_address()
+            .map(|lnaddr| json!(lnaddr.to_string())),
+    );
+    insert(
+        "payjoin",
+        payment_params
+            .payjoin_endpoint()
+            .map(|url| json!(url.to_string())),
+    );
+    insert(
+        "amount_msat",
+        amount_msat(&payment_params)?.map(|msat| json!(msat)),
+    );
+    insert(
+        "expires_at",
+        expiry::expires_at(&payment_params).map(|at| json!(at)),
+    );
+    insert("memo", payment_params.memo().map(|memo| json!(memo)));
+
+    Ok(map)
+}
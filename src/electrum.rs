@@ -0,0 +1,289 @@
+//! Chain lookups against an Electrum server (ElectrumX, Fulcrum, electrs)
+
+use crate::chain::{script_pubkey, Chain};
+use crate::convert::scripthash;
+use crate::tx::total_value;
+use crate::{net, Error, Result};
+use bitcoin::block::Header;
+use bitcoin::consensus::deserialize;
+use bitcoin::hashes::hex::FromHex;
+use bitcoin::{ScriptBuf, Transaction, TxOut};
+use serde_json::{json, Value};
+use std::cell::RefCell;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::sync::Arc;
+
+/// A connection that is read and written, plain or TLS
+trait Stream: Read + Write {}
+
+impl<T: Read + Write> Stream for T {}
+
+/// Client of an Electrum server
+pub struct Electrum {
+    server: String,
+    conn: RefCell<BufReader<Box<dyn Stream>>>,
+    next_id: RefCell<u64>,
+}
+
+impl Electrum {
+    /// Connect to the server at `url`, `tcp://host:port` or `ssl://host:port`.
+    /// TLS certificates are verified against the web PKI roots.
+    pub fn connect(url: &str) -> Result<Self> {
+        let (tls, addr) = if let Some(addr) = url.strip_prefix("ssl://") {
+            (true, addr)
+        } else if let Some(addr) = url.strip_prefix("tcp://") {
+            (false, addr)
+        } else {
+            return Err(Error::Resolve(format!(
+                "expected a tcp:// or ssl:// url: {url}"
+            )));
+        };
+        let Some((host, port)) = addr
+            .rsplit_once(':')
+            .and_then(|(host, port)| Some((host, port.parse::<u16>().ok()?)))
+        else {
+            return Err(Error::Resolve(format!("expected host:port: {addr}")));
+        };
+
+        let tcp = net::connect(host, port)?;
+        let stream: Box<dyn Stream> = if tls {
+            Box::new(tls_stream(host, tcp)?)
+        } else {
+            Box::new(tcp)
+        };
+
+        Ok(Self {
+            server: url.to_string(),
+            conn: RefCell::new(BufReader::new(stream)),
+            next_id: RefCell::new(0),
+        })
+    }
+
+    /// Send a JSON-RPC request and wait for its result, skipping notifications
+    fn call(&self, method: &str, params: Value) -> Result<Value> {
+        let id = {
+            let mut next_id = self.next_id.borrow_mut();
+            *next_id += 1;
+            *next_id
+        };
+        let request = json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params });
+        let mut conn = self.conn.borrow_mut();
+        writeln!(conn.get_mut(), "{request}")?;
+        conn.get_mut().flush()?;
+
+        loop {
+            let mut line = String::new();
+            if conn.read_line(&mut line)? == 0 {
+                return Err(Error::Resolve(format!(
+                    "electrum server {} closed the connection",
+                    self.server
+                )));
+            }
+            let mut resp: Value = serde_json::from_str(&line)?;
+            if resp["id"].as_u64() != Some(id) {
+                continue;
+            }
+            if let Some(err) = resp.get("error").filter(|err| !err.is_null()) {
+                let message = err["message"].as_str().map(str::to_string);
+                return Err(Error::Resolve(format!(
+                    "electrum {method}: {}",
+                    message.unwrap_or_else(|| err.to_string())
+                )));
+            }
+            return Ok(resp["result"].take());
+        }
+    }
+
+    fn transaction(&self, txid: &str) -> Result<Transaction> {
+        let hex = self.call("blockchain.transaction.get", json!([txid]))?;
+        Vec::<u8>::from_hex(hex.as_str().unwrap_or_default())
+            .ok()
+            .and_then(|bytes| deserialize::<Transaction>(&bytes).ok())
+            .ok_or_else(|| Error::Resolve(format!("malformed transaction {txid}")))
+    }
+
+    /// Entries `{tx_hash, height}` of the transactions touching `script`, oldest
+    /// first. Mempool transactions have a height of 0 or less.
+    fn history(&self, script: &ScriptBuf) -> Result<Vec<Value>> {
+        let history = self.call(
+            "blockchain.scripthash.get_history",
+            json!([scripthash(script)]),
+        )?;
+
+        Ok(history.as_array().cloned().unwrap_or_default())
+    }
+
+    /// The value in satoshis of the outputs of `tx` spent by its inputs
+    /// that pay to `script`, all of them if `script` is `None`
+    fn spent_value(&self, tx: &Transaction, script: Option<&ScriptBuf>) -> Result<u64> {
+        let mut value = 0;
+        for input in &tx.input {
+            if input.previous_output.is_null() {
+                continue;
+            }
+            let prev = self.transaction(&input.previous_output.txid.to_string())?;
+            let Some(out) = prev.output.get(input.previous_output.vout as usize) else {
+                continue;
+            };
+            let pays = match script {
+                Some(script) => &out.script_pubkey == script,
+                None => true,
+            };
+            if pays {
+                value = value.checked_add(out.value).ok_or_else(overflow)?;
+            }
+        }
+
+        Ok(value)
+    }
+}
+
+impl Chain for Electrum {
+    fn address_stats(&self, address: &str) -> Result<Value> {
+        let script = script_pubkey(address)?;
+        let balance = self.call(
+            "blockchain.scripthash.get_balance",
+            json!([scripthash(&script)]),
+        )?;
+        let tx_count = self.history(&script)?.len();
+
+        Ok(json!({
+            "electrum": self.server,
+            "confirmed_balance": balance["confirmed"].as_i64().unwrap_or_default(),
+            "unconfirmed_balance": balance["unconfirmed"].as_i64().unwrap_or_default(),
+            "tx_count": tx_count,
+            "used": tx_count > 0,
+        }))
+    }
+
+    fn address_history(&self, address: &str, n: usize) -> Result<Value> {
+        let script = script_pubkey(address)?;
+        let mut history = vec![];
+        for entry in self.history(&script)?.iter().rev().take(n) {
+            let txid = entry["tx_hash"].as_str().unwrap_or_default();
+            let tx = self.transaction(txid)?;
+            let received =
+                checked_value(tx.output.iter().filter(|out| out.script_pubkey == script))?;
+            let spent = self.spent_value(&tx, Some(&script))?;
+            let height = entry["height"].as_i64().filter(|h| *h > 0);
+            history.push(json!({
+                "txid": txid,
+                "height": height,
+                "net_amount": i64::try_from(received)
+                    .ok()
+                    .zip(i64::try_from(spent).ok())
+                    .map(|(received, spent)| received - spent),
+            }));
+        }
+
+        Ok(Value::Array(history))
+    }
+
+    fn tx_status(&self, txid: &str) -> Result<Value> {
+        let tx = self.transaction(txid)?;
+        // the server only indexes by script, find the height in the history of an output
+        let height = match tx.output.first() {
+            Some(out) => self
+                .history(&out.script_pubkey)?
+                .iter()
+                .find(|entry| entry["tx_hash"].as_str() == Some(txid))
+                .and_then(|entry| entry["height"].as_u64())
+                .filter(|h| *h > 0),
+            None => None,
+        };
+        let fee = self
+            .spent_value(&tx, None)?
+            .saturating_sub(checked_value(&tx.output)?);
+        let vsize = tx.vsize() as f64;
+        let feerate = if vsize > 0.0 {
+            (fee as f64 / vsize * 100.0).round() / 100.0
+        } else {
+            0.0
+        };
+
+        let mut out = json!({
+            "electrum": self.server,
+            "confirmed": height.is_some(),
+            "block_height": height,
+            "block_hash": null,
+            "confirmations": 0,
+            "fee": fee,
+            "feerate": feerate,
+        });
+        if let Some(height) = height {
+            let header = self.call("blockchain.block.header", json!([height]))?;
+            out["block_hash"] = json!(block_hash(&header));
+            let tip = self.call("blockchain.headers.subscribe", json!([]))?;
+            let tip = tip["height"].as_u64().unwrap_or(height);
+            out["confirmations"] = json!(tip.saturating_sub(height) + 1);
+        }
+
+        Ok(out)
+    }
+
+    fn suggested_feerates(&self) -> Result<Value> {
+        // estimates are in BTC/kvB, -1 when the server has none
+        let rate = |target: u32| -> Result<f64> {
+            let estimate = self.call("blockchain.estimatefee", json!([target]))?;
+            let sat_per_vb = estimate.as_f64().unwrap_or_default().max(0.0) * 100_000.0;
+            Ok((sat_per_vb * 100.0).round() / 100.0)
+        };
+
+        Ok(json!({
+            "fastest": rate(1)?,
+            "half_hour": rate(3)?,
+            "hour": rate(6)?,
+        }))
+    }
+}
+
+/// The value of `outputs` sent by the server, which fails if it overflows
+fn checked_value<'a>(outputs: impl IntoIterator<Item = &'a TxOut>) -> Result<u64> {
+    total_value(outputs).ok_or_else(overflow)
+}
+
+/// The error for values that overflow a u64
+fn overflow() -> Error {
+    Error::Backend("the server sent transactions worth more than 2^64 sat".to_string())
+}
+
+/// The hash of the hex encoded block header `header`
+fn block_hash(header: &Value) -> Option<String> {
+    let bytes = Vec::<u8>::from_hex(header.as_str()?).ok()?;
+    let header = deserialize::<Header>(&bytes).ok()?;
+
+    Some(header.block_hash().to_string())
+}
+
+/// Wrap `tcp` in a TLS session with `host`
+#[cfg(not(target_arch = "wasm32"))]
+fn tls_stream(
+    host: &str,
+    tcp: std::net::TcpStream,
+) -> Result<rustls::StreamOwned<rustls::ClientConnection, std::net::TcpStream>> {
+    let mut roots = rustls::RootCertStore::empty();
+    roots.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|ta| {
+        rustls::OwnedTrustAnchor::from_subject_spki_name_constraints(
+            ta.subject,
+            ta.spki,
+            ta.name_constraints,
+        )
+    }));
+    let config = rustls::ClientConfig::builder()
+        .with_safe_defaults()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    let name = rustls::ServerName::try_from(host)
+        .map_err(|_| Error::Resolve(format!("invalid TLS server name: {host}")))?;
+    let conn = rustls::ClientConnection::new(Arc::new(config), name)
+        .map_err(|e| Error::Resolve(format!("TLS: {e}")))?;
+
+    Ok(rustls::StreamOwned::new(conn, tcp))
+}
+
+#[cfg(target_arch = "wasm32")]
+fn tls_stream(_host: &str, _tcp: std::net::TcpStream) -> Result<std::net::TcpStream> {
+    Err(Error::Resolve(
+        "TLS is not available in WebAssembly".to_string(),
+    ))
+}
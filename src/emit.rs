@@ -0,0 +1,207 @@
+//! The node command paying a parsed string, and the string shaped for wallet tooling,
+//! for going from what it is to how to pay it. Amounts the string leaves out are
+//! written as a placeholder to fill in.
+
+use crate::{amount_msat, derive, Error, Result};
+use bitcoin::{Amount, Denomination};
+use bitcoin_waila::PaymentParams;
+use clap::ValueEnum;
+use serde_json::{json, Map, Value};
+use std::str::FromStr;
+
+/// Command line clients of nodes
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Tool {
+    /// LND
+    Lncli,
+    /// Bitcoin Core
+    BitcoinCli,
+    /// Core Lightning
+    LightningCli,
+}
+
+impl Tool {
+    fn name(self) -> &'static str {
+        match self {
+            Tool::Lncli => "lncli",
+            Tool::BitcoinCli => "bitcoin-cli",
+            Tool::LightningCli => "lightning-cli",
+        }
+    }
+
+    /// The flag selecting `network`, none for mainnet
+    fn network_flag(self, network: Option<&str>) -> Option<String> {
+        let network = network.filter(|net| *net != "bitcoin")?;
+        Some(match self {
+            Tool::Lncli | Tool::LightningCli => format!("--network={network}"),
+            Tool::BitcoinCli => format!("-{network}"),
+        })
+    }
+}
+
+/// The `tool` command paying the payment string of a query analyzed as `kind`, see
+/// [`Analysis::payment_string`](crate::Analysis::payment_string)
+pub fn command(payment: Option<&str>, kind: &'static str, tool: Tool) -> Result<String> {
+    // transactions, descriptors and secrets are not paid
+    let payment_params = payment
+        .and_then(|payment| PaymentParams::from_str(payment).ok())
+        .ok_or(Error::NoCommand(tool.name(), kind))?;
+    let msat = amount_msat(&payment_params)?;
+    let sat = || msat.map_or("SATS".to_string(), |msat| (msat / 1_000).to_string());
+    let address = payment_params.address().map(|addr| addr.to_string());
+    let invoice = payment_params.invoice().map(|inv| inv.to_string());
+    let pubkey = payment_params.node_pubkey().map(|pk| pk.to_string());
+    let offer = match &payment_params {
+        PaymentParams::Bolt12(offer) => Some(offer.to_string()),
+        _ => None,
+    };
+
+    // a unified URI is paid over lightning where the tool can
+    let args = match tool {
+        Tool::Lncli => match (invoice, pubkey, address) {
+            (Some(invoice), ..) if msat.is_some() => format!("payinvoice {invoice}"),
+            (Some(invoice), ..) => format!("payinvoice --amt {} {invoice}", sat()),
+            (None, Some(pubkey), _) => {
+                format!("sendpayment --keysend --dest {pubkey} --amt {}", sat())
+            }
+            (None, None, Some(address)) => format!("sendcoins --addr {address} --amt {}", sat()),
+            _ => return Err(Error::NoCommand(tool.name(), kind)),
+        },
+        Tool::BitcoinCli => match address {
+            Some(address) => {
+                let btc = msat.map_or("BTC".to_string(), |msat| {
+                    Amount::from_sat(msat / 1_000).to_string_in(Denomination::Bitcoin)
+                });
+                format!("sendtoaddress {address} {btc}")
+            }
+            None => return Err(Error::NoCommand(tool.name(), kind)),
+        },
+        Tool::LightningCli => {
+            let msat_arg = || msat.map_or("MSATS".to_string(), |msat| msat.to_string());
+            match (invoice, offer, pubkey, address) {
+                (Some(invoice), ..) if msat.is_some() => format!("pay {invoice}"),
+                (Some(invoice), ..) => format!("pay {invoice} {}", msat_arg()),
+                (None, Some(offer), ..) if msat.is_some() => format!("fetchinvoice {offer}"),
+                (None, Some(offer), ..) => format!("fetchinvoice {offer} {}", msat_arg()),
+                (None, None, Some(pubkey), _) => format!("keysend {pubkey} {}", msat_arg()),
+                (None, None, None, Some(address)) => format!("withdraw {address} {}", sat()),
+                _ => return Err(Error::NoCommand(tool.name(), kind)),
+            }
+        }
+    };
+
+    let network = payment_params.network().map(|net| net.to_string());
+    Ok(match tool.network_flag(network.as_deref()) {
+        Some(flag) => format!("{} {flag} {args}", tool.name()),
+        None => format!("{} {args}", tool.name()),
+    })
+}
+
+/// Shapes of the query for other tools
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Emit {
+    /// JSON for BDK based wallets, with a bdk-cli command
+    Bdk,
+}
+
+/// A query of `kind`, with the analysis `fields` and payment string `payment`, as BDK
+/// reads it: its descriptor or address, network and amount in satoshis, and the bdk-cli
+/// command acting on it. A descriptor is synced, an address paid from the wallet in
+/// place of `DESCRIPTOR`.
+pub fn bdk(
+    payment: Option<&str>,
+    kind: &'static str,
+    fields: &Map<String, Value>,
+) -> Result<Value> {
+    if kind == "Descriptor" {
+        let Some(descriptor) = fields.get("descriptor").and_then(Value::as_str) else {
+            return Err(Error::MissingField("descriptor".to_string()));
+        };
+        let network = derive::descriptor_network(descriptor).map(|net| net.to_string());
+        let command = format!(
+            "bdk-cli --network {} wallet --ext-descriptor \"{descriptor}\" sync",
+            network.as_deref().unwrap_or("NETWORK")
+        );
+        return Ok(json!({
+            "descriptor": descriptor,
+            "network": network,
+            "bdk_cli": command,
+        }));
+    }
+
+    let payment_params = payment
+        .and_then(|payment| PaymentParams::from_str(payment).ok())
+        .ok_or(Error::NoCommand("bdk-cli", kind))?;
+    let Some(address) = payment_params.address().map(|addr| addr.to_string()) else {
+        return Err(Error::NoCommand("bdk-cli", kind));
+    };
+    let network = payment_params
+        .network()
+        .map_or("bitcoin".to_string(), |net| net.to_string());
+    let sat = amount_msat(&payment_params)?.map(|msat| msat / 1_000);
+    let command = format!(
+        "bdk-cli --network {network} wallet --ext-descriptor DESCRIPTOR create_tx --to {address}:{}",
+        sat.map_or("SATS".to_string(), |sat| sat.to_string())
+    );
+
+    Ok(json!({
+        "address": address,
+        "network": network,
+        "amount_sat": sat,
+        "bdk_cli": command,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{analyze, Options};
+
+    const ADDRESS: &str = "tb1pwzv7fv35yl7ypwj8w7al2t8apd6yf4568cs772qjwper74xqc99sk8x7tk";
+    const NODE: &str = "03864ef025fde8fb587d989186ce6a4a186895ee44a926bfc370e2c366597a3f8f";
+
+    #[test]
+    fn commands_pay_the_payment_string() {
+        assert_eq!(
+            command(Some(ADDRESS), "OnChain", Tool::BitcoinCli).unwrap(),
+            format!("bitcoin-cli -testnet sendtoaddress {ADDRESS} BTC")
+        );
+        assert_eq!(
+            command(Some(ADDRESS), "OnChain", Tool::Lncli).unwrap(),
+            format!("lncli --network=testnet sendcoins --addr {ADDRESS} --amt SATS")
+        );
+        assert!(matches!(
+            command(None, "Secret", Tool::Lncli),
+            Err(Error::NoCommand("lncli", "Secret"))
+        ));
+    }
+
+    #[test]
+    fn node_uris_are_paid_by_their_pubkey() {
+        let analysis = analyze(&format!("{NODE}@203.0.113.1:9735"), &Options::default()).unwrap();
+        assert_eq!(analysis.payment_string.as_deref(), Some(NODE));
+        assert_eq!(
+            command(
+                analysis.payment_string.as_deref(),
+                analysis.kind,
+                Tool::Lncli
+            )
+            .unwrap(),
+            format!("lncli sendpayment --keysend --dest {NODE} --amt SATS")
+        );
+    }
+
+    #[test]
+    fn addresses_are_paid_from_a_bdk_wallet() {
+        let blob = bdk(Some(ADDRESS), "OnChain", &Map::new()).unwrap();
+        assert_eq!(blob["network"], "testnet");
+        assert_eq!(blob["amount_sat"], Value::Null);
+        assert_eq!(
+            blob["bdk_cli"],
+            format!(
+                "bdk-cli --network testnet wallet --ext-descriptor DESCRIPTOR create_tx --to {ADDRESS}:SATS"
+            )
+        );
+        assert!(bdk(None, "Secret", &Map::new()).is_err());
+    }
+}
@@ -0,0 +1,41 @@
+//! Strings encoded from what they hold, the inverse of parsing them
+
+use crate::{Error, Result};
+use bitcoin::bech32::{self, ToBase32, Variant};
+use serde::Serialize;
+use url::Url;
+
+/// An LNURL in the encodings wallets read
+#[derive(Debug, Clone, Serialize)]
+pub struct Lnurl {
+    pub url: String,
+    /// Lowercase bech32, e.g. for links
+    pub lnurl: String,
+    /// Uppercase bech32, which fits a smaller QR code
+    pub uppercase: String,
+}
+
+/// The LNURL of the pay, withdraw or other service at `url`. LUD-01 allows https, and
+/// plain http only to onion services.
+pub fn lnurl(url: &str) -> Result<Lnurl> {
+    let parsed =
+        Url::parse(url).map_err(|e| Error::InvalidPart(format!("invalid url {url}: {e}")))?;
+    let onion = parsed
+        .host_str()
+        .is_some_and(|host| host.ends_with(".onion"));
+    if parsed.scheme() != "https" && !(parsed.scheme() == "http" && onion) {
+        return Err(Error::InvalidPart(format!(
+            "an LNURL must be https or an http onion service: {url}"
+        )));
+    }
+
+    // LNURLs are bech32 without its length limit
+    let lnurl = bech32::encode("lnurl", url.as_bytes().to_base32(), Variant::Bech32)
+        .map_err(|e| Error::Encode(e.to_string()))?;
+
+    Ok(Lnurl {
+        url: url.to_string(),
+        uppercase: lnurl.to_ascii_uppercase(),
+        lnurl,
+    })
+}
@@ -0,0 +1,172 @@
+//! Chain lookups against an Esplora HTTP API
+
+#[cfg(feature = "network")]
+use {
+    crate::chain::Chain,
+    crate::{net, Result},
+    serde_json::{json, Value},
+};
+
+/// A public Esplora instance for the given network, as displayed by `Network`
+pub fn default_url(network: &str) -> Option<&'static str> {
+    match network {
+        "bitcoin" => Some("https://blockstream.info/api"),
+        "testnet" => Some("https://blockstream.info/testnet/api"),
+        "signet" => Some("https://mempool.space/signet/api"),
+        _ => None,
+    }
+}
+
+/// Parse a `NETWORK=URL` pair naming the Esplora instance of a network. The network
+/// is returned as displayed by `Network`, so `mainnet` is accepted for `bitcoin`.
+pub fn network_url(s: &str) -> core::result::Result<(String, String), String> {
+    let Some((network, url)) = s.split_once('=') else {
+        return Err("expected NETWORK=URL".to_string());
+    };
+    let network = match network.to_ascii_lowercase().as_str() {
+        "mainnet" | "bitcoin" => "bitcoin",
+        "testnet" => "testnet",
+        "signet" => "signet",
+        "regtest" => "regtest",
+        other => return Err(format!("unknown network {other}")),
+    };
+    if url.is_empty() {
+        return Err("missing url".to_string());
+    }
+
+    Ok((network.to_string(), url.to_string()))
+}
+
+/// Client of an Esplora instance
+#[cfg(feature = "network")]
+#[derive(Debug, Clone)]
+pub struct Esplora {
+    base: String,
+}
+
+#[cfg(feature = "network")]
+impl Esplora {
+    /// A client for the instance at `base`, e.g. `https://blockstream.info/api`
+    pub fn new(base: &str) -> Self {
+        Self {
+            base: base.trim_end_matches('/').to_string(),
+        }
+    }
+
+    fn get(&self, path: &str) -> Result<Value> {
+        net::get_json_cached(&format!("{}/{path}", self.base))
+    }
+}
+
+#[cfg(feature = "network")]
+impl Chain for Esplora {
+    fn address_stats(&self, address: &str) -> Result<Value> {
+        let resp = self.get(&format!("address/{address}"))?;
+        let balance = |stats: &Value| {
+            stats["funded_txo_sum"].as_i64().unwrap_or_default()
+                - stats["spent_txo_sum"].as_i64().unwrap_or_default()
+        };
+        let chain = &resp["chain_stats"];
+        let mempool = &resp["mempool_stats"];
+        let tx_count = chain["tx_count"].as_u64().unwrap_or_default()
+            + mempool["tx_count"].as_u64().unwrap_or_default();
+
+        Ok(json!({
+            "esplora": self.base,
+            "confirmed_balance": balance(chain),
+            "unconfirmed_balance": balance(mempool),
+            "tx_count": tx_count,
+            "used": tx_count > 0,
+        }))
+    }
+
+    fn address_history(&self, address: &str, n: usize) -> Result<Value> {
+        let mut txs = vec![];
+        let mut page = self.get(&format!("address/{address}/txs"))?;
+        loop {
+            let Value::Array(page_txs) = page else {
+                break;
+            };
+            let Some(last) = page_txs.last() else {
+                break;
+            };
+            let last_txid = last["txid"].as_str().unwrap_or_default().to_string();
+            txs.extend(page_txs);
+            if txs.len() >= n {
+                break;
+            }
+            page = self.get(&format!("address/{address}/txs/chain/{last_txid}"))?;
+        }
+
+        let history = txs
+            .iter()
+            .take(n)
+            .map(|tx| {
+                let received: i64 = tx["vout"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter(|out| out["scriptpubkey_address"].as_str() == Some(address))
+                    .map(|out| out["value"].as_i64().unwrap_or_default())
+                    .sum();
+                let spent: i64 = tx["vin"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .map(|input| &input["prevout"])
+                    .filter(|prevout| prevout["scriptpubkey_address"].as_str() == Some(address))
+                    .map(|prevout| prevout["value"].as_i64().unwrap_or_default())
+                    .sum();
+                json!({
+                    "txid": tx["txid"],
+                    "height": tx["status"]["block_height"],
+                    "net_amount": received - spent,
+                })
+            })
+            .collect();
+
+        Ok(Value::Array(history))
+    }
+
+    fn tx_status(&self, txid: &str) -> Result<Value> {
+        let tx = self.get(&format!("tx/{txid}"))?;
+        let status = &tx["status"];
+        let confirmed = status["confirmed"].as_bool().unwrap_or_default();
+        let fee = tx["fee"].as_u64().unwrap_or_default();
+        let vsize = tx["weight"].as_u64().unwrap_or_default() as f64 / 4.0;
+        let feerate = if vsize > 0.0 {
+            (fee as f64 / vsize * 100.0).round() / 100.0
+        } else {
+            0.0
+        };
+
+        let mut out = json!({
+            "esplora": self.base,
+            "confirmed": confirmed,
+            "block_height": status["block_height"],
+            "block_hash": status["block_hash"],
+            "confirmations": 0,
+            "fee": fee,
+            "feerate": feerate,
+        });
+        if let Some(height) = status["block_height"].as_u64() {
+            // the tip height is returned as plain text, which reads as a json number
+            let tip = self.get("blocks/tip/height")?.as_u64().unwrap_or(height);
+            out["confirmations"] = json!(tip.saturating_sub(height) + 1);
+        }
+
+        Ok(out)
+    }
+
+    fn suggested_feerates(&self) -> Result<Value> {
+        let estimates = self.get("fee-estimates")?;
+        let rate =
+            |target: &str| (estimates[target].as_f64().unwrap_or_default() * 100.0).round() / 100.0;
+
+        Ok(json!({
+            "fastest": rate("1"),
+            "half_hour": rate("3"),
+            "hour": rate("6"),
+        }))
+    }
+}
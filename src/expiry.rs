@@ -0,0 +1,57 @@
+//! Expiry of invoices and offers
+
+use bitcoin_waila::PaymentParams;
+use serde_json::{json, Value};
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Unix time at which the invoice or offer of `payment_params` expires, if it does
+pub fn expires_at(payment_params: &PaymentParams) -> Option<u64> {
+    if let PaymentParams::Bolt12(offer) = payment_params {
+        return offer.absolute_expiry().map(|expiry| expiry.as_secs());
+    }
+    let inv = payment_params.invoice()?;
+    // convert to the correct type for our imports
+    let inv = lightning_invoice::Bolt11Invoice::from_str(&inv.to_string()).ok()?;
+
+    Some((inv.duration_since_epoch() + inv.expiry_time()).as_secs())
+}
+
+/// Whether `expires_at` has passed
+pub fn is_expired(expires_at: u64) -> bool {
+    expires_at <= now()
+}
+
+/// The `expires_at`, `expired` and `time_remaining` fields for an expiry
+pub fn describe(expires_at: u64) -> [(&'static str, Value); 3] {
+    let remaining = expires_at.saturating_sub(now());
+    [
+        ("expires_at", json!(expires_at)),
+        ("expired", json!(remaining == 0)),
+        ("time_remaining", json!(format_duration(remaining))),
+    ]
+}
+
+/// A duration in seconds as days, hours, minutes and seconds, e.g. `1h 5m 0s`
+pub fn format_duration(secs: u64) -> String {
+    let units = [(86_400, "d"), (3_600, "h"), (60, "m"), (1, "s")];
+    let mut parts = vec![];
+    let mut rest = secs;
+    for (size, suffix) in units {
+        let n = rest / size;
+        rest %= size;
+        if n > 0 || !parts.is_empty() || size == 1 {
+            parts.push(format!("{n}{suffix}"));
+        }
+    }
+
+    parts.join(" ")
+}
+
+/// Seconds since the unix epoch
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
@@ -0,0 +1,92 @@
+//! C bindings, built into the cdylib with the `ffi` feature. Strings go in as NUL
+//! terminated UTF-8 and come out as json, to be released with [`waila_free`].
+//! The declarations are in `include/waila.h`.
+
+use crate::{analyze, Options};
+use serde_json::{json, Value};
+use std::ffi::{c_char, CStr, CString};
+
+/// Parse `query` into the json the command line prints, or `{"error": "..."}`
+///
+/// # Safety
+///
+/// `query` must be null or point to a NUL terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn waila_parse(query: *const c_char) -> *mut c_char {
+    answer(query, Options::default())
+}
+
+/// Like [`waila_parse`], with every field, null if absent
+///
+/// # Safety
+///
+/// `query` must be null or point to a NUL terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn waila_parse_all(query: *const c_char) -> *mut c_char {
+    let opts = Options {
+        all: true,
+        ..Options::default()
+    };
+
+    answer(query, opts)
+}
+
+/// The kind of `query`, e.g. `OnChain`, or null if it is not recognized
+///
+/// # Safety
+///
+/// `query` must be null or point to a NUL terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn waila_kind(query: *const c_char) -> *mut c_char {
+    let opts = Options {
+        kind_only: true,
+        ..Options::default()
+    };
+    match read(query).map(|query| analyze(query, &opts)) {
+        Some(Ok(analysis)) => into_raw(analysis.kind.to_string()),
+        _ => std::ptr::null_mut(),
+    }
+}
+
+/// Release a string returned by the functions above
+///
+/// # Safety
+///
+/// `s` must be null or returned by one of them, and not released before.
+#[no_mangle]
+pub unsafe extern "C" fn waila_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
+
+/// # Safety
+///
+/// `query` must be null or point to a NUL terminated string.
+unsafe fn answer(query: *const c_char, opts: Options) -> *mut c_char {
+    let reply = match read(query) {
+        Some(query) => match analyze(query, &opts) {
+            Ok(analysis) => Value::Object(analysis.into_fields()),
+            Err(e) => json!({ "error": e.to_string() }),
+        },
+        None => json!({ "error": "the query is null or not UTF-8" }),
+    };
+
+    into_raw(reply.to_string())
+}
+
+/// # Safety
+///
+/// `s` must be null or point to a NUL terminated string.
+unsafe fn read<'a>(s: *const c_char) -> Option<&'a str> {
+    if s.is_null() {
+        return None;
+    }
+
+    CStr::from_ptr(s).to_str().ok()
+}
+
+fn into_raw(s: String) -> *mut c_char {
+    // json and kinds hold no NUL
+    CString::new(s).map_or(std::ptr::null_mut(), CString::into_raw)
+}
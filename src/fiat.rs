@@ -0,0 +1,203 @@
+//! Conversion of bitcoin amounts to fiat currencies
+
+#[cfg(feature = "fiat")]
+use crate::net;
+use crate::{Error, Result};
+use bitcoin::Amount;
+use clap::ValueEnum;
+use serde_json::{json, Map, Value};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[cfg(feature = "fiat")]
+const COINGECKO_URL: &str = "https://api.coingecko.com/api/v3/simple/price";
+#[cfg(feature = "fiat")]
+const KRAKEN_URL: &str = "https://api.kraken.com/0/public/Ticker";
+
+/// A public exchange rate API
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Source {
+    /// The CoinGecko simple price API
+    Coingecko,
+    /// The Kraken ticker of each XBT pair
+    Kraken,
+}
+
+/// Where to fetch exchange rates from
+#[derive(Debug, Clone)]
+pub enum Provider {
+    /// One of the known APIs
+    Api(Source),
+    /// Any JSON API, queried once per currency. `{currency}` and `{CURRENCY}` in
+    /// the url and the JSON pointer to the price stand for the lower and upper
+    /// case currency code.
+    Custom { url: String, pointer: String },
+}
+
+/// The price of one bitcoin in a set of fiat currencies
+#[derive(Debug, Clone)]
+pub struct Rates {
+    /// Where the rates came from
+    pub source: String,
+    /// Unix time of the last rate update
+    pub timestamp: u64,
+    /// Unix time at which the rates were fetched
+    pub fetched_at: u64,
+    /// Currency code and price of one bitcoin
+    pub rates: Vec<(String, f64)>,
+}
+
+impl Rates {
+    /// Rates given on the command line, one per currency
+    pub fn from_user(currencies: &[String], rates: &[f64]) -> Result<Self> {
+        if currencies.len() != rates.len() {
+            return Err(Error::Rate(
+                "expected one --rate per --fiat currency".to_string(),
+            ));
+        }
+
+        Ok(Self {
+            source: "user".to_string(),
+            timestamp: now(),
+            fetched_at: now(),
+            rates: currencies
+                .iter()
+                .map(|c| c.to_uppercase())
+                .zip(rates.iter().copied())
+                .collect(),
+        })
+    }
+
+    /// Fetch current rates from `provider`
+    #[cfg(feature = "fiat")]
+    pub fn fetch(currencies: &[String], provider: &Provider) -> Result<Self> {
+        match provider {
+            Provider::Api(Source::Coingecko) => Self::fetch_coingecko(currencies),
+            Provider::Api(Source::Kraken) => Self::fetch_kraken(currencies),
+            Provider::Custom { url, pointer } => Self::fetch_custom(currencies, url, pointer),
+        }
+    }
+
+    #[cfg(not(feature = "fiat"))]
+    pub fn fetch(_currencies: &[String], _provider: &Provider) -> Result<Self> {
+        Err(Error::Unsupported("fiat"))
+    }
+
+    #[cfg(feature = "fiat")]
+    fn fetch_coingecko(currencies: &[String]) -> Result<Self> {
+        let vs_currencies = currencies
+            .iter()
+            .map(|c| c.to_lowercase())
+            .collect::<Vec<_>>()
+            .join(",");
+
+        let resp = net::get_json_cached(&format!(
+            "{COINGECKO_URL}?ids=bitcoin&vs_currencies={vs_currencies}&include_last_updated_at=true"
+        ))?;
+        let prices = &resp["bitcoin"];
+
+        let mut rates = vec![];
+        for c in currencies {
+            let Some(rate) = prices[c.to_lowercase().as_str()].as_f64() else {
+                return Err(Error::Rate(format!("no rate available for {c}")));
+            };
+            rates.push((c.to_uppercase(), rate));
+        }
+
+        Ok(Self {
+            source: "coingecko".to_string(),
+            timestamp: prices["last_updated_at"].as_u64().unwrap_or_else(now),
+            fetched_at: now(),
+            rates,
+        })
+    }
+
+    #[cfg(feature = "fiat")]
+    fn fetch_kraken(currencies: &[String]) -> Result<Self> {
+        let mut rates = vec![];
+        for c in currencies {
+            let code = c.to_uppercase();
+            let resp = net::get_json_cached(&format!("{KRAKEN_URL}?pair=XBT{code}"))?;
+            // the result is keyed by Kraken's name of the pair, e.g. XXBTZUSD
+            let last_trade = resp["result"]
+                .as_object()
+                .and_then(|pairs| pairs.values().next())
+                .map(|ticker| &ticker["c"][0]);
+            let Some(rate) = last_trade.and_then(as_f64) else {
+                return Err(Error::Rate(format!("no rate available for {c}")));
+            };
+            rates.push((code, rate));
+        }
+
+        Ok(Self {
+            source: "kraken".to_string(),
+            timestamp: now(),
+            fetched_at: now(),
+            rates,
+        })
+    }
+
+    #[cfg(feature = "fiat")]
+    fn fetch_custom(currencies: &[String], url: &str, pointer: &str) -> Result<Self> {
+        let fill = |template: &str, c: &str| {
+            template
+                .replace("{currency}", &c.to_lowercase())
+                .replace("{CURRENCY}", &c.to_uppercase())
+        };
+        let mut rates = vec![];
+        for c in currencies {
+            let resp = net::get_json_cached(&fill(url, c))?;
+            let Some(rate) = resp.pointer(&fill(pointer, c)).and_then(as_f64) else {
+                return Err(Error::Rate(format!(
+                    "no rate for {c} at {} in the response",
+                    fill(pointer, c)
+                )));
+            };
+            rates.push((c.to_uppercase(), rate));
+        }
+
+        Ok(Self {
+            source: url.to_string(),
+            timestamp: now(),
+            fetched_at: now(),
+            rates,
+        })
+    }
+
+    /// Convert `amt` to each currency. Values are rounded to two decimal places.
+    pub fn convert(&self, amt: Amount) -> Value {
+        let btc = amt.to_btc();
+        let mut rates = Map::new();
+        let mut values = Map::new();
+        for (currency, rate) in &self.rates {
+            rates.insert(currency.clone(), json!(rate));
+            values.insert(
+                currency.clone(),
+                json!((btc * rate * 100.0).round() / 100.0),
+            );
+        }
+
+        json!({
+            "source": self.source,
+            "timestamp": self.timestamp,
+            "fetched_at": self.fetched_at,
+            "rates": rates,
+            "values": values,
+        })
+    }
+}
+
+/// Seconds since the unix epoch
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// Read a price that may be encoded as a string
+#[cfg(feature = "fiat")]
+fn as_f64(value: &Value) -> Option<f64> {
+    value
+        .as_f64()
+        .or_else(|| value.as_str().and_then(|s| s.parse().ok()))
+}
@@ -0,0 +1,187 @@
+//! Addresses of other chains pasted where a bitcoin one was meant. They are told apart
+//! so the error names the chain, rather than calling them not a bitcoin string.
+
+use bitcoin::base58;
+use bitcoin::bech32;
+use serde::Serialize;
+use std::fmt;
+
+/// Characters of CashAddr payloads, those of bech32
+const CASHADDR_CHARSET: &[u8; 32] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+
+/// Version bytes of Litecoin P2PKH (L) and P2SH (M) addresses. Its 3... P2SH
+/// addresses and testnet ones are the same as bitcoin's.
+const LITECOIN_VERSIONS: [u8; 2] = [0x30, 0x32];
+
+/// Chains whose addresses are mistaken for bitcoin ones
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Chain {
+    Litecoin,
+    BitcoinCash,
+    Ethereum,
+    Monero,
+}
+
+impl fmt::Display for Chain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Chain::Litecoin => "Litecoin",
+            Chain::BitcoinCash => "Bitcoin Cash",
+            Chain::Ethereum => "Ethereum",
+            Chain::Monero => "Monero",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// The chain `s` is an address of, if it is clearly one of another chain
+pub fn detect(s: &str) -> Option<Chain> {
+    let s = s.trim();
+    if is_litecoin(s) {
+        Some(Chain::Litecoin)
+    } else if is_cashaddr(s) {
+        Some(Chain::BitcoinCash)
+    } else if is_ethereum(s) {
+        Some(Chain::Ethereum)
+    } else if is_monero(s) {
+        Some(Chain::Monero)
+    } else {
+        None
+    }
+}
+
+/// A bech32 address of hrp `ltc` or `tltc`, or a L... or M... Base58Check one
+fn is_litecoin(s: &str) -> bool {
+    if let Ok((hrp, _, _)) = bech32::decode(s) {
+        return hrp == "ltc" || hrp == "tltc";
+    }
+    match base58::decode_check(s) {
+        Ok(bytes) => bytes.len() == 21 && LITECOIN_VERSIONS.contains(&bytes[0]),
+        Err(_) => false,
+    }
+}
+
+/// A CashAddr address, with its `bitcoincash:` or `bchtest:` prefix or without it
+fn is_cashaddr(s: &str) -> bool {
+    // the case may be all upper or all lower, like bech32
+    if s.bytes().any(|b| b.is_ascii_lowercase()) && s.bytes().any(|b| b.is_ascii_uppercase()) {
+        return false;
+    }
+    let s = s.to_ascii_lowercase();
+    let prefixed = |prefix: &str, payload: &str| {
+        let Some(values) = payload
+            .bytes()
+            .map(|c| {
+                CASHADDR_CHARSET
+                    .iter()
+                    .position(|&x| x == c)
+                    .map(|v| v as u8)
+            })
+            .collect::<Option<Vec<u8>>>()
+        else {
+            return false;
+        };
+        // the 34 or more characters of a hash and the 8 of the checksum
+        let data = prefix.bytes().map(|b| b & 0x1f).chain([0]).chain(values);
+        payload.len() >= 42 && cashaddr_polymod(data) == 0
+    };
+    match s.split_once(':') {
+        Some((prefix @ ("bitcoincash" | "bchtest"), payload)) => prefixed(prefix, payload),
+        Some(_) => false,
+        // a bare CashAddr starts with q or p, for a key or script hash
+        None if s.starts_with(['q', 'p']) => prefixed("bitcoincash", &s),
+        None => false,
+    }
+}
+
+/// The checksum of CashAddr, 0 for a valid address
+fn cashaddr_polymod(values: impl Iterator<Item = u8>) -> u64 {
+    const GENERATOR: [u64; 5] = [
+        0x98_f2bc_8e61,
+        0x79_b76d_99e2,
+        0xf3_3e5f_b3c4,
+        0xae_2eab_e2a8,
+        0x1e_4f43_e470,
+    ];
+    let mut c: u64 = 1;
+    for value in values {
+        let top = c >> 35;
+        c = ((c & 0x07_ffff_ffff) << 5) ^ u64::from(value);
+        for (i, g) in GENERATOR.iter().enumerate() {
+            if (top >> i) & 1 == 1 {
+                c ^= g;
+            }
+        }
+    }
+
+    c ^ 1
+}
+
+/// `0x` and the 40 hex digits of an account
+fn is_ethereum(s: &str) -> bool {
+    match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => hex.len() == 40 && hex.bytes().all(|b| b.is_ascii_hexdigit()),
+        None => false,
+    }
+}
+
+/// A standard address or subaddress of 95 characters starting with 4 or 8, or an
+/// integrated address of 106 starting with 4, all base58. The checksum is a keccak
+/// hash, which isn't checked, but no bitcoin string looks like this.
+fn is_monero(s: &str) -> bool {
+    let base58 = s
+        .bytes()
+        .all(|b| b.is_ascii_alphanumeric() && !matches!(b, b'0' | b'O' | b'I' | b'l'));
+    let shape = match s.len() {
+        95 => s.starts_with(['4', '8']),
+        106 => s.starts_with('4'),
+        _ => false,
+    };
+
+    base58 && shape
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chains_are_told_apart() {
+        for (address, chain) in [
+            ("ltc1qw508d6qejxtdg4y5r3zarvary0c5xw7kgmn4n9", Chain::Litecoin),
+            ("LVuDpNCSSj6pQ7t9Pv6d6sUkLKoqDEVUnJ", Chain::Litecoin),
+            ("MJaRnao1s62a2zAKSkmG582KbLKianqb7v", Chain::Litecoin),
+            (
+                "bitcoincash:qpm2qsznhks23z7629mms6s4cwef74vcwvy22gdx6a",
+                Chain::BitcoinCash,
+            ),
+            (
+                "BITCOINCASH:QPM2QSZNHKS23Z7629MMS6S4CWEF74VCWVY22GDX6A",
+                Chain::BitcoinCash,
+            ),
+            ("qpm2qsznhks23z7629mms6s4cwef74vcwvy22gdx6a", Chain::BitcoinCash),
+            ("0xde0B295669a9FD93d5F28D9Ec85E40f4cb697BAe", Chain::Ethereum),
+            (
+                "44AFFq5kSiGBoZ4NMDwYtN18obc8AemS33DBLWs3H7otXft3XjrpDtQGv7SqSsaBYBb98uNbr2VBBEt7f2wfn3RVGQBEP3A",
+                Chain::Monero,
+            ),
+        ] {
+            assert_eq!(detect(address), Some(chain), "{address}");
+        }
+    }
+
+    #[test]
+    fn bitcoin_strings_are_not_foreign() {
+        for s in [
+            "1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa",
+            "3J98t1WpEZ73CNmQviecrnyiWrnqRhWNLy",
+            "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4",
+            // a checksum off by one character
+            "bitcoincash:qpm2qsznhks23z7629mms6s4cwef74vcwvy22gdx6b",
+            "0xde0B295669a9FD93d5F28D9Ec85E40f4cb697BA",
+        ] {
+            assert_eq!(detect(s), None, "{s}");
+        }
+    }
+}
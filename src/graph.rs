@@ -0,0 +1,78 @@
+//! Lightning node information from public graph explorers
+
+use clap::ValueEnum;
+#[cfg(feature = "network")]
+use {
+    crate::{net, Error, Result},
+    serde_json::{json, Value},
+};
+
+#[cfg(feature = "network")]
+const MEMPOOL_URL: &str = "https://mempool.space/api";
+#[cfg(feature = "network")]
+const AMBOSS_URL: &str = "https://api.amboss.space/graphql";
+
+#[cfg(feature = "network")]
+const AMBOSS_QUERY: &str = "query GetNode($pubkey: String!) {
+  getNode(pubkey: $pubkey) {
+    graph_info {
+      node { alias }
+      channels { total_capacity num_channels }
+    }
+  }
+}";
+
+/// A lightning graph explorer
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Graph {
+    /// The mempool.space lightning API
+    Mempool,
+    /// The Amboss GraphQL API
+    Amboss,
+}
+
+#[cfg(feature = "network")]
+impl Graph {
+    /// Look up the node `pubkey`, at `url` if given instead of the public instance.
+    /// Returns the alias, capacity in satoshis and number of channels.
+    pub fn node_info(self, pubkey: &str, url: Option<&str>) -> Result<Value> {
+        match self {
+            Graph::Mempool => {
+                let base = url.unwrap_or(MEMPOOL_URL).trim_end_matches('/');
+                let node = net::get_json_cached(&format!("{base}/v1/lightning/nodes/{pubkey}"))?;
+                Ok(json!({
+                    "source": "mempool",
+                    "alias": node["alias"],
+                    "capacity": node["capacity"].as_u64(),
+                    "channel_count": node["active_channel_count"].as_u64(),
+                }))
+            }
+            Graph::Amboss => {
+                let body = json!({
+                    "query": AMBOSS_QUERY,
+                    "variables": { "pubkey": pubkey },
+                });
+                let resp = net::post_json_cached(url.unwrap_or(AMBOSS_URL), body)?;
+                let info = &resp["data"]["getNode"]["graph_info"];
+                if info.is_null() {
+                    return Err(Error::Resolve(format!("node not found: {pubkey}")));
+                }
+                let channels = &info["channels"];
+                Ok(json!({
+                    "source": "amboss",
+                    "alias": info["node"]["alias"],
+                    "capacity": as_u64(&channels["total_capacity"]),
+                    "channel_count": as_u64(&channels["num_channels"]),
+                }))
+            }
+        }
+    }
+}
+
+/// Read an integer that may be encoded as a string
+#[cfg(feature = "network")]
+fn as_u64(value: &Value) -> Option<u64> {
+    value
+        .as_u64()
+        .or_else(|| value.as_str().and_then(|s| s.parse().ok()))
+}
@@ -0,0 +1,186 @@
+//! A gRPC service mirroring `waila-cli serve`, defined in `proto/waila.proto`
+
+use serde_json::Value;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{Request, Response, Status, Streaming};
+use waila_cli::{Analysis, Error, Options, Result};
+
+mod proto {
+    tonic::include_proto!("waila.v1");
+}
+
+use proto::waila_server::{Waila, WailaServer};
+use proto::{ParseRequest, ParseResponse};
+
+/// Responses buffered per stream before the client reads them
+const STREAM_BUFFER: usize = 16;
+
+/// Answer gRPC requests on `listen` until the process is stopped. With a
+/// `token`, calls must carry `authorization: Bearer <token>` metadata.
+pub fn serve(listen: SocketAddr, token: Option<String>, opts: Options) -> Result<()> {
+    let runtime = tokio::runtime::Runtime::new()?;
+    let expected = token.map(|token| format!("Bearer {token}"));
+    let service = WailaServer::with_interceptor(Service { opts }, move |req| {
+        authorize(req, expected.as_deref())
+    });
+    eprintln!("listening on {listen}");
+    runtime
+        .block_on(
+            tonic::transport::Server::builder()
+                .add_service(service)
+                .serve(listen),
+        )
+        .map_err(|e| Error::Backend(format!("gRPC server: {e}")))
+}
+
+/// Let `req` through if it carries the `expected` authorization, if any
+fn authorize(
+    req: Request<()>,
+    expected: Option<&str>,
+) -> core::result::Result<Request<()>, Status> {
+    let Some(expected) = expected else {
+        return Ok(req);
+    };
+    let authorization = req
+        .metadata()
+        .get("authorization")
+        .and_then(|value| value.to_str().ok());
+    if authorization != Some(expected) {
+        return Err(Status::unauthenticated("missing or wrong bearer token"));
+    }
+
+    Ok(req)
+}
+
+struct Service {
+    opts: Options,
+}
+
+#[tonic::async_trait]
+impl Waila for Service {
+    async fn parse(
+        &self,
+        req: Request<ParseRequest>,
+    ) -> core::result::Result<Response<ParseResponse>, Status> {
+        let opts = Options {
+            resolve: false,
+            ..self.opts.clone()
+        };
+        answer(req.into_inner().query, opts)
+            .await
+            .map(Response::new)
+    }
+
+    async fn resolve(
+        &self,
+        req: Request<ParseRequest>,
+    ) -> core::result::Result<Response<ParseResponse>, Status> {
+        let opts = Options {
+            resolve: true,
+            ..self.opts.clone()
+        };
+        answer(req.into_inner().query, opts)
+            .await
+            .map(Response::new)
+    }
+
+    type ParseStreamStream = Pin<
+        Box<dyn tokio_stream::Stream<Item = core::result::Result<ParseResponse, Status>> + Send>,
+    >;
+
+    async fn parse_stream(
+        &self,
+        req: Request<Streaming<ParseRequest>>,
+    ) -> core::result::Result<Response<Self::ParseStreamStream>, Status> {
+        let mut inbound = req.into_inner();
+        let opts = self.opts.clone();
+        let (tx, rx) = tokio::sync::mpsc::channel(STREAM_BUFFER);
+        tokio::spawn(async move {
+            loop {
+                let reply = match inbound.message().await {
+                    Ok(Some(req)) => answer(req.query, opts.clone()).await,
+                    Ok(None) => break,
+                    Err(status) => Err(status),
+                };
+                // the client went away
+                if tx.send(reply).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(Response::new(Box::pin(ReceiverStream::new(rx))))
+    }
+}
+
+/// Parse `query` off the async runtime, lookups block
+async fn answer(query: String, opts: Options) -> core::result::Result<ParseResponse, Status> {
+    let analysis = tokio::task::spawn_blocking(move || waila_cli::analyze(&query, &opts))
+        .await
+        .map_err(|e| Status::internal(e.to_string()))?;
+
+    match analysis {
+        Ok(analysis) => Ok(response(analysis)),
+        Err(e) if e.is_input_error() => Ok(ParseResponse {
+            error: e.to_string(),
+            ..Default::default()
+        }),
+        Err(e) => Err(Status::internal(e.to_string())),
+    }
+}
+
+fn response(analysis: Analysis) -> ParseResponse {
+    let kind = analysis.kind.to_string();
+    let fields = analysis.into_fields();
+    let text = |key: &str| {
+        fields
+            .get(key)
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string()
+    };
+
+    ParseResponse {
+        kind,
+        network: text("network"),
+        address: text("address"),
+        invoice: text("invoice"),
+        pubkey: text("pubkey"),
+        amount_msat: fields
+            .get("amounts")
+            .and_then(|amounts| amounts["msat"].as_u64()),
+        memo: text("memo"),
+        lnurl: text("lnurl"),
+        lnaddr: text("lnaddr"),
+        payjoin: text("payjoin"),
+        json: Value::Object(fields.clone()).to_string(),
+        error: String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call(authorization: Option<&str>) -> Request<()> {
+        let mut req = Request::new(());
+        if let Some(authorization) = authorization {
+            req.metadata_mut()
+                .insert("authorization", authorization.parse().unwrap());
+        }
+        req
+    }
+
+    #[test]
+    fn calls_need_the_token() {
+        let expected = Some("Bearer hunter2");
+        assert!(authorize(call(Some("Bearer hunter2")), expected).is_ok());
+        for authorization in [None, Some("Bearer hunter3"), Some("hunter2")] {
+            let status = authorize(call(authorization), expected).unwrap_err();
+            assert_eq!(status.code(), tonic::Code::Unauthenticated);
+        }
+        assert!(authorize(call(None), None).is_ok());
+    }
+}
@@ -0,0 +1,176 @@
+//! An opt-in log of the strings parsed, for reconstructing what was checked. Each line
+//! of `waila/queries.jsonl` under the state directory records the time, input or its
+//! sha256, and the kind or error code. Secrets, and strings that failed to parse which
+//! may be mistyped secrets, are logged by kind or error only.
+
+use bitcoin::hashes::{sha256, Hash};
+use serde_json::{json, Value};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use waila_cli::{secret, Error, Result};
+
+/// `waila` under `$XDG_STATE_HOME` or `~/.local/state`
+pub fn state_dir() -> Option<PathBuf> {
+    std::env::var_os("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| {
+            std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local").join("state"))
+        })
+        .map(|state| state.join("waila"))
+}
+
+fn path() -> Option<PathBuf> {
+    state_dir().map(|dir| dir.join("queries.jsonl"))
+}
+
+/// Append the query `s` and the kind it was found to be, or the error, to the log. The
+/// input is replaced by its sha256 if `hash`.
+pub fn record(s: &str, result: core::result::Result<&'static str, &Error>, hash: bool) {
+    let Some(path) = path() else {
+        return;
+    };
+    // parsing goes on if the log can't be written
+    if let Err(e) = append(&path, &entry(s, result, hash)) {
+        tracing::warn!("could not write the history log: {e}");
+    }
+}
+
+/// The log entry of the query `s`. The input, even hashed, is only kept if it parsed as
+/// something other than a secret: an error may be that of a secret the analysis reads
+/// other than `s` as given, trimmed, after a ₿ or with a typo.
+fn entry(s: &str, result: core::result::Result<&'static str, &Error>, hash: bool) -> Value {
+    let mut entry = json!({
+        "time": SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_secs()),
+    });
+    let logged = match result {
+        Ok(kind) => {
+            entry["kind"] = json!(kind);
+            kind != "Secret" && secret::detect(s).is_none() && secret::redact_keys(s).is_none()
+        }
+        Err(e) => {
+            entry["error"] = json!(e.code());
+            false
+        }
+    };
+    if logged {
+        if hash {
+            entry["sha256"] = json!(sha256::Hash::hash(s.as_bytes()).to_string());
+        } else {
+            entry["input"] = json!(s);
+        }
+    }
+
+    entry
+}
+
+/// Append a line of `entry` to the log at `path`, created readable by the user alone
+fn append(path: &Path, entry: &Value) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut options = std::fs::OpenOptions::new();
+    options.create(true).append(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options.open(path)?;
+    // a single write of the whole line, so lines of parallel parses don't interleave
+    file.write_all(format!("{entry}\n").as_bytes())?;
+
+    Ok(())
+}
+
+/// The logged entries whose input contains `pattern`, or whose hashed input is
+/// `pattern`, and of `kind` if given, the last `limit` of them
+pub fn search(pattern: Option<&str>, kind: Option<&str>, limit: usize) -> Result<Vec<Value>> {
+    let Some(file) = path().and_then(|path| std::fs::File::open(path).ok()) else {
+        return Ok(vec![]);
+    };
+    let hashed = pattern.map(|pattern| sha256::Hash::hash(pattern.as_bytes()).to_string());
+
+    let mut found = vec![];
+    for line in BufReader::new(file).lines() {
+        // a torn line of an interrupted write is skipped
+        let Ok(entry) = serde_json::from_str::<Value>(&line?) else {
+            continue;
+        };
+        let field = |key: &str| entry.get(key).and_then(Value::as_str);
+        if let Some(pattern) = pattern {
+            let input = field("input").is_some_and(|input| input.contains(pattern));
+            if !input && field("sha256") != hashed.as_deref() {
+                continue;
+            }
+        }
+        if let Some(kind) = kind {
+            if !field("kind").is_some_and(|found| found.eq_ignore_ascii_case(kind)) {
+                continue;
+            }
+        }
+        found.push(entry);
+    }
+    let skip = found.len().saturating_sub(limit);
+
+    Ok(found.split_off(skip))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NSEC: &str = "nsec1vl029mgpspedva04g90vltkh6fvh240zqtv9k0t9af8935ke9laqsnlfe5";
+
+    #[test]
+    fn parsed_strings_are_logged() {
+        let logged = entry(
+            "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4",
+            Ok("OnChain"),
+            false,
+        );
+        assert_eq!(logged["kind"], "OnChain");
+        assert_eq!(
+            logged["input"],
+            "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4"
+        );
+
+        let logged = entry(
+            "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4",
+            Ok("OnChain"),
+            true,
+        );
+        assert!(logged.get("input").is_none());
+        assert!(logged["sha256"].is_string());
+    }
+
+    #[test]
+    fn secrets_are_never_logged() {
+        for (s, result) in [
+            (NSEC, Ok("Secret")),
+            // read as a secret after the prefix that the log doesn't strip
+            (&*format!("₿{NSEC}"), Ok("Secret")),
+            (&*format!("  {NSEC}  "), Err(&Error::Strict("padded"))),
+            (NSEC, Err(&Error::Timeout("secret"))),
+            (NSEC, Err(&Error::InputTooLarge(63, 16))),
+        ] {
+            for hash in [false, true] {
+                let logged = entry(s, result, hash);
+                assert!(logged.get("input").is_none(), "{logged}");
+                assert!(logged.get("sha256").is_none(), "{logged}");
+            }
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn log_is_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("waila-history-{}", std::process::id()));
+        let path = dir.join("queries.jsonl");
+        append(&path, &json!({ "kind": "OnChain" })).unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+}
@@ -0,0 +1,359 @@
+//! Translations of the messages people read: errors, warnings and the labels of the
+//! markdown table. Codes, keys and values stay as they are in every language, so
+//! scripts read the same output. A message without a translation is left in English.
+
+use clap::ValueEnum;
+use serde_json::{Map, Value};
+use std::sync::OnceLock;
+use waila_cli::Error;
+
+/// Languages of messages
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Lang {
+    En,
+    Es,
+    De,
+    Pt,
+    Ja,
+}
+
+impl Lang {
+    /// The language of the locale in `LC_ALL`, `LC_MESSAGES` or `LANG`, English if
+    /// there is no translation for it
+    pub fn from_env() -> Self {
+        let locale = ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .filter_map(|var| std::env::var(var).ok())
+            .find(|locale| !locale.is_empty())
+            .unwrap_or_default();
+        // e.g. pt_BR.UTF-8
+        match locale.get(..2) {
+            Some("es") => Lang::Es,
+            Some("de") => Lang::De,
+            Some("pt") => Lang::Pt,
+            Some("ja") => Lang::Ja,
+            _ => Lang::En,
+        }
+    }
+}
+
+static LANG: OnceLock<Lang> = OnceLock::new();
+
+/// Print messages in `lang` from now on
+pub fn set(lang: Lang) {
+    let _ = LANG.set(lang);
+}
+
+fn lang() -> Lang {
+    LANG.get().copied().unwrap_or(Lang::En)
+}
+
+/// English messages, `{}` standing for the values put in, and their translations to
+/// es, de, pt and ja, `{0}`, `{1}` standing for the values in the order of the English.
+/// More specific messages come before those they would also match.
+const MESSAGES: [(&str, [&str; 4]); 29] = [
+    (
+        "network access is disabled, pass --online to allow it",
+        [
+            "el acceso a la red está desactivado, pasa --online para permitirlo",
+            "Netzwerkzugriff ist deaktiviert, --online erlaubt ihn",
+            "o acesso à rede está desativado, use --online para permiti-lo",
+            "ネットワークアクセスは無効です。許可するには --online を指定してください",
+        ],
+    ),
+    (
+        "not a bitcoin string{}",
+        [
+            "no es una cadena de bitcoin{0}",
+            "keine Bitcoin-Zeichenkette{0}",
+            "não é uma string de bitcoin{0}",
+            "ビットコインの文字列ではありません{0}",
+        ],
+    ),
+    (
+        "{} address, not a bitcoin one",
+        [
+            "dirección de {0}, no de bitcoin",
+            "{0}-Adresse, keine Bitcoin-Adresse",
+            "endereço de {0}, não de bitcoin",
+            "{0} のアドレスです。ビットコインのアドレスではありません",
+        ],
+    ),
+    (
+        "expected kind {}, found {}",
+        [
+            "se esperaba el tipo {0}, se encontró {1}",
+            "Art {0} erwartet, {1} gefunden",
+            "esperado o tipo {0}, encontrado {1}",
+            "種類 {0} を期待しましたが、{1} でした",
+        ],
+    ),
+    (
+        "expected network {}, found no network",
+        [
+            "se esperaba la red {0}, no se encontró ninguna",
+            "Netzwerk {0} erwartet, keines gefunden",
+            "esperada a rede {0}, nenhuma encontrada",
+            "ネットワーク {0} を期待しましたが、ネットワークがありません",
+        ],
+    ),
+    (
+        "expected network {}, found {}",
+        [
+            "se esperaba la red {0}, se encontró {1}",
+            "Netzwerk {0} erwartet, {1} gefunden",
+            "esperada a rede {0}, encontrada {1}",
+            "ネットワーク {0} を期待しましたが、{1} でした",
+        ],
+    ),
+    (
+        "expired at unix time {}",
+        [
+            "expiró en el tiempo unix {0}",
+            "abgelaufen zur Unix-Zeit {0}",
+            "expirou no tempo unix {0}",
+            "Unix 時刻 {0} に期限切れになりました",
+        ],
+    ),
+    (
+        "amount out of range: no amount",
+        [
+            "importe fuera de rango: sin importe",
+            "Betrag außerhalb des Bereichs: kein Betrag",
+            "valor fora do intervalo: sem valor",
+            "金額が範囲外です: 金額がありません",
+        ],
+    ),
+    (
+        "amount out of range: {} msat",
+        [
+            "importe fuera de rango: {0} msat",
+            "Betrag außerhalb des Bereichs: {0} msat",
+            "valor fora do intervalo: {0} msat",
+            "金額が範囲外です: {0} msat",
+        ],
+    ),
+    (
+        "URI must not be used, unknown required parameter {}",
+        [
+            "no se debe usar la URI, parámetro obligatorio desconocido {0}",
+            "URI darf nicht verwendet werden, unbekannter Pflichtparameter {0}",
+            "a URI não deve ser usada, parâmetro obrigatório desconhecido {0}",
+            "URI を使用してはいけません。不明な必須パラメータ {0}",
+        ],
+    ),
+    (
+        "invalid descriptor checksum, expected {}",
+        [
+            "suma de verificación del descriptor no válida, se esperaba {0}",
+            "ungültige Deskriptor-Prüfsumme, {0} erwartet",
+            "checksum do descritor inválido, esperado {0}",
+            "ディスクリプタのチェックサムが無効です。正しくは {0} です",
+        ],
+    ),
+    (
+        "rejected by --strict: {}",
+        [
+            "rechazado por --strict: {0}",
+            "von --strict abgelehnt: {0}",
+            "rejeitado por --strict: {0}",
+            "--strict により拒否されました: {0}",
+        ],
+    ),
+    (
+        "the query holds secrets, pass --show-secrets to encode it in a QR code or copy it",
+        [
+            "la consulta contiene secretos, pasa --show-secrets para codificarla en un código QR o copiarla",
+            "die Abfrage enthält Geheimnisse, --show-secrets erlaubt, sie als QR-Code zu kodieren oder zu kopieren",
+            "a consulta contém segredos, use --show-secrets para codificá-la em um código QR ou copiá-la",
+            "クエリに秘密情報が含まれています。QR コードにするかコピーするには --show-secrets を指定してください",
+        ],
+    ),
+    (
+        "field not present: {}",
+        [
+            "campo ausente: {0}",
+            "Feld nicht vorhanden: {0}",
+            "campo ausente: {0}",
+            "フィールドがありません: {0}",
+        ],
+    ),
+    (
+        "no raw value for kind {}",
+        [
+            "no hay valor sin formato para el tipo {0}",
+            "kein Rohwert für die Art {0}",
+            "não há valor bruto para o tipo {0}",
+            "種類 {0} には生の値がありません",
+        ],
+    ),
+    (
+        "input of {} bytes is longer than the limit of {}",
+        [
+            "la entrada de {0} bytes supera el límite de {1}",
+            "Eingabe von {0} Bytes ist länger als das Limit von {1}",
+            "a entrada de {0} bytes excede o limite de {1}",
+            "{0} バイトの入力は上限の {1} を超えています",
+        ],
+    ),
+    (
+        "the {} detector ran over its time budget",
+        [
+            "el detector {0} superó su tiempo límite",
+            "der Detektor {0} hat sein Zeitbudget überschritten",
+            "o detector {0} excedeu seu tempo limite",
+            "検出器 {0} が制限時間を超えました",
+        ],
+    ),
+    (
+        "invalid amount: {}",
+        [
+            "importe no válido: {0}",
+            "ungültiger Betrag: {0}",
+            "valor inválido: {0}",
+            "無効な金額: {0}",
+        ],
+    ),
+    (
+        "could not copy to the clipboard: {}",
+        [
+            "no se pudo copiar al portapapeles: {0}",
+            "Kopieren in die Zwischenablage fehlgeschlagen: {0}",
+            "não foi possível copiar para a área de transferência: {0}",
+            "クリップボードにコピーできませんでした: {0}",
+        ],
+    ),
+    (
+        "{} sat is below the dust limit of {} sat, the payment won't relay on chain",
+        [
+            "{0} sat está por debajo del límite de polvo de {1} sat, el pago no se retransmitirá en la cadena",
+            "{0} sat liegt unter der Dust-Grenze von {1} sat, die Zahlung wird on-chain nicht weitergeleitet",
+            "{0} sat está abaixo do limite de poeira de {1} sat, o pagamento não será retransmitido na cadeia",
+            "{0} sat はダスト上限の {1} sat 未満のため、支払いはオンチェーンで中継されません",
+        ],
+    ),
+    (
+        "the invoice or offer has expired",
+        [
+            "la factura u oferta ha expirado",
+            "die Rechnung oder das Angebot ist abgelaufen",
+            "a fatura ou oferta expirou",
+            "インボイスまたはオファーの有効期限が切れています",
+        ],
+    ),
+    (
+        "the description does not match the description hash of the invoice",
+        [
+            "la descripción no coincide con el hash de descripción de la factura",
+            "die Beschreibung passt nicht zum Beschreibungs-Hash der Rechnung",
+            "a descrição não corresponde ao hash de descrição da fatura",
+            "説明がインボイスの説明ハッシュと一致しません",
+        ],
+    ),
+    (
+        "the invoice is for {}, the address is not",
+        [
+            "la factura es para {0}, la dirección no",
+            "die Rechnung gilt für {0}, die Adresse nicht",
+            "a fatura é para {0}, o endereço não",
+            "インボイスは {0} 用ですが、アドレスはそうではありません",
+        ],
+    ),
+    (
+        "input {} spends with an uncompressed public key",
+        [
+            "la entrada {0} gasta con una clave pública sin comprimir",
+            "Eingang {0} gibt mit einem unkomprimierten öffentlichen Schlüssel aus",
+            "a entrada {0} gasta com uma chave pública não comprimida",
+            "入力 {0} は非圧縮の公開鍵で使用されています",
+        ],
+    ),
+    (
+        "the address uses witness version {}, which has no consensus meaning yet and can be spent by anyone",
+        [
+            "la dirección usa la versión de testigo {0}, que aún no tiene significado de consenso y cualquiera puede gastarla",
+            "die Adresse verwendet die Witness-Version {0}, die noch keine Konsensbedeutung hat und von jedem ausgegeben werden kann",
+            "o endereço usa a versão de testemunha {0}, que ainda não tem significado de consenso e pode ser gasta por qualquer um",
+            "アドレスはまだコンセンサス上の意味がないウィットネスバージョン {0} を使っており、誰でも使用できます",
+        ],
+    ),
+    (
+        "output {} uses witness version {}, which has no consensus meaning yet and can be spent by anyone",
+        [
+            "la salida {0} usa la versión de testigo {1}, que aún no tiene significado de consenso y cualquiera puede gastarla",
+            "Ausgang {0} verwendet die Witness-Version {1}, die noch keine Konsensbedeutung hat und von jedem ausgegeben werden kann",
+            "a saída {0} usa a versão de testemunha {1}, que ainda não tem significado de consenso e pode ser gasta por qualquer um",
+            "出力 {0} はまだコンセンサス上の意味がないウィットネスバージョン {1} を使っており、誰でも使用できます",
+        ],
+    ),
+    (
+        "this is an extended private key: anyone who sees it can spend from all its addresses, don't share it",
+        [
+            "esta es una clave privada extendida: cualquiera que la vea puede gastar desde todas sus direcciones, no la compartas",
+            "dies ist ein erweiterter privater Schlüssel: wer ihn sieht, kann von allen seinen Adressen ausgeben, gib ihn nicht weiter",
+            "esta é uma chave privada estendida: qualquer um que a veja pode gastar de todos os seus endereços, não a compartilhe",
+            "これは拡張秘密鍵です。見た人は誰でもそのすべてのアドレスから送金できます。共有しないでください",
+        ],
+    ),
+    ("Field", ["Campo", "Feld", "Campo", "フィールド"]),
+    ("Value", ["Valor", "Wert", "Valor", "値"]),
+];
+
+/// `message` in the language set, as it is if it has no translation
+pub fn localize(message: &str) -> String {
+    let i = match lang() {
+        Lang::En => return message.to_string(),
+        Lang::Es => 0,
+        Lang::De => 1,
+        Lang::Pt => 2,
+        Lang::Ja => 3,
+    };
+    for (english, translations) in MESSAGES {
+        if let Some(values) = fill_ins(english, message) {
+            let mut translated = translations[i].to_string();
+            for (n, value) in values.iter().enumerate() {
+                translated = translated.replace(&format!("{{{n}}}"), value);
+            }
+            return translated;
+        }
+    }
+
+    message.to_string()
+}
+
+/// The message of `e` in the language set
+pub fn error(e: &Error) -> String {
+    localize(&e.to_string())
+}
+
+/// Translate the messages of the warnings of the result `map`
+pub fn localize_warnings(map: &mut Map<String, Value>) {
+    let Some(Value::Array(warnings)) = map.get_mut("warnings") else {
+        return;
+    };
+    for warning in warnings {
+        if let Some(Value::String(message)) = warning.get_mut("message") {
+            *message = localize(message);
+        }
+    }
+}
+
+/// The values filling in the `{}` of `template` to make `message`, if it is made so
+fn fill_ins<'a>(template: &str, message: &'a str) -> Option<Vec<&'a str>> {
+    let mut pieces = template.split("{}");
+    let mut rest = message.strip_prefix(pieces.next()?)?;
+    let pieces: Vec<&str> = pieces.collect();
+    let mut values = vec![];
+    for (i, piece) in pieces.iter().enumerate() {
+        // the last value runs up to the end of the template, the others to the next piece
+        let end = if i + 1 == pieces.len() {
+            rest.strip_suffix(piece)?.len()
+        } else {
+            rest.find(piece)?
+        };
+        values.push(&rest[..end]);
+        rest = &rest[end + piece.len()..];
+    }
+
+    rest.is_empty().then_some(values)
+}
@@ -0,0 +1,1488 @@
+//! What am I looking at? Detection and description of bitcoin strings: addresses,
+//! BIP-21 URIs, lightning invoices and offers, LNURLs, node pubkeys, nostr keys,
+//! transactions, descriptors and secrets.
+//!
+//! [`analyze`] parses a string into an [`Analysis`], its kind and a json map of
+//! its fields, configured by [`Options`]. Network access is disabled unless
+//! allowed with [`net::set_online`].
+
+use amount::{amounts, msat_to_amount, numeric_amount};
+use bitcoin::hashes::{sha256, Hash};
+use bitcoin::Denomination;
+use bitcoin_waila::PaymentParams;
+#[cfg(feature = "nostr")]
+use nostr::{
+    key::XOnlyPublicKey,
+    nips::nip19::{self, ToBech32},
+};
+use serde::{Serialize, Serializer};
+use serde_json::{json, Map, Value};
+use std::fmt;
+use std::io;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::Duration;
+use tracing::debug;
+
+pub mod amount;
+pub mod bip353;
+#[cfg(feature = "network")]
+pub mod bitcoind;
+pub mod cache;
+pub mod candidates;
+pub mod canonical;
+#[cfg(feature = "network")]
+pub mod chain;
+pub mod checksum;
+#[cfg(feature = "network")]
+pub mod cln;
+pub mod compare;
+pub mod compose;
+pub mod convert;
+pub mod decode;
+pub mod deeplink;
+pub mod derive;
+pub mod descriptor;
+pub mod detector;
+pub mod diff;
+#[cfg(feature = "network")]
+pub mod electrum;
+pub mod emit;
+pub mod encode;
+pub mod esplora;
+pub mod expiry;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod fiat;
+pub mod foreign;
+pub mod graph;
+#[cfg(feature = "network")]
+pub mod lnurl;
+pub mod net;
+pub mod output;
+#[cfg(feature = "network")]
+pub mod payjoin;
+pub mod probe;
+pub mod secret;
+pub mod suggest;
+pub mod tx;
+pub mod uri;
+pub mod warnings;
+#[cfg(target_arch = "wasm32")]
+pub mod wasm;
+
+/// Longest input analyzed by default, in bytes, which fits the largest standard transaction
+pub const DEFAULT_MAX_INPUT: usize = 1 << 20;
+
+/// What to look up and check while analyzing, and how to present amounts
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// Include absent fields as null
+    pub all: bool,
+    /// Read nostr keys, which are otherwise not reported
+    pub nostr: bool,
+    /// Denomination of amounts
+    pub unit: Denomination,
+    /// Give amounts as numbers in `unit`, with a separate 'unit' field
+    pub numeric_amounts: bool,
+    /// Rates to convert amounts to fiat currencies with
+    pub rates: Option<fiat::Rates>,
+    /// Query LNURL endpoints and BIP-353 names
+    pub resolve: bool,
+    /// Request an invoice of the amount in millisatoshis, which offers may leave out
+    pub request_invoice: Option<Option<u64>>,
+    /// JSON-RPC socket of a Core Lightning node to request offer invoices with
+    pub cln_rpc: Option<PathBuf>,
+    /// clnrest API of a Core Lightning node and the rune authorizing requests
+    pub cln_rest: Option<String>,
+    pub cln_rune: Option<String>,
+    /// DNS server for BIP-353 lookups
+    pub dns_server: SocketAddr,
+    /// DNS-over-HTTPS resolver for BIP-353 lookups instead of `dns_server`
+    pub doh: Option<String>,
+    /// Look up addresses and transactions on chain, nodes in the graph and payjoin endpoints
+    pub lookup: bool,
+    /// Number of recent transactions of an address to look up
+    pub history: Option<usize>,
+    /// Esplora API for all networks, and per network as (network, url)
+    pub esplora: Option<String>,
+    pub esplora_urls: Vec<(String, String)>,
+    /// Electrum server to look up on chain state with instead of Esplora
+    pub electrum: Option<String>,
+    /// bitcoind JSON-RPC server to look up on chain state with instead of Esplora
+    pub bitcoind: Option<String>,
+    pub bitcoind_cookie: Option<PathBuf>,
+    /// rpcuser and rpcpassword as USER:PASSWORD
+    pub bitcoind_auth: Option<String>,
+    /// Lightning graph explorer and its API
+    pub graph: graph::Graph,
+    pub graph_url: Option<String>,
+    /// Try to connect to the host of a node URI, with a BOLT 8 handshake if `handshake`
+    pub probe: bool,
+    pub handshake: bool,
+    /// Fail unless the string is of this kind, case insensitive
+    pub kind: Option<String>,
+    /// Fail unless the string is for this network, mainnet, testnet, signet or regtest
+    pub expect_network: Option<String>,
+    /// Fail if the invoice or offer has expired
+    pub reject_expired: bool,
+    /// Fail unless the amount in millisatoshis is within the range
+    pub min_amount: Option<u64>,
+    pub max_amount: Option<u64>,
+    /// Text whose sha256 an invoice committing to its description by hash should have
+    pub description: Option<String>,
+    /// Accept only strings that follow their spec exactly
+    pub strict: bool,
+    /// Give secrets as they are instead of masking them
+    pub show_secrets: bool,
+    /// Stop once the kind is known and checked, leaving the fields empty
+    pub kind_only: bool,
+    /// Fields to compute, all if empty, so those not selected cost nothing
+    pub fields: Vec<String>,
+    /// Longest input in bytes, longer ones are rejected before any detector runs
+    pub max_input: usize,
+    /// Time each detector may take on the input before it is rejected
+    pub time_budget: Option<Duration>,
+}
+
+impl Options {
+    /// Whether the output needs any of `fields`
+    fn wants(&self, fields: &[&str]) -> bool {
+        self.fields.is_empty() || self.fields.iter().any(|f| fields.contains(&f.as_str()))
+    }
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            all: false,
+            nostr: false,
+            unit: Denomination::Satoshi,
+            numeric_amounts: false,
+            rates: None,
+            resolve: false,
+            request_invoice: None,
+            cln_rpc: None,
+            cln_rest: None,
+            cln_rune: None,
+            dns_server: bip353::DEFAULT_DNS_SERVER
+                .parse()
+                .expect("valid default DNS server"),
+            doh: None,
+            lookup: false,
+            history: None,
+            esplora: None,
+            esplora_urls: vec![],
+            electrum: None,
+            bitcoind: None,
+            bitcoind_cookie: None,
+            bitcoind_auth: None,
+            graph: graph::Graph::Mempool,
+            graph_url: None,
+            probe: false,
+            handshake: false,
+            kind: None,
+            expect_network: None,
+            reject_expired: false,
+            min_amount: None,
+            max_amount: None,
+            description: None,
+            strict: false,
+            show_secrets: false,
+            kind_only: false,
+            fields: vec![],
+            max_input: DEFAULT_MAX_INPUT,
+            time_budget: None,
+        }
+    }
+}
+
+/// A parsed bitcoin string: its payment instructions typed, the rest of its fields as
+/// JSON. The output, sparse or with nulls as `all` has it, is that of [`Analysis::into_fields`].
+#[derive(Debug, Clone)]
+pub struct Analysis {
+    /// The kind, e.g. `OnChain` or `Invoice`
+    pub kind: &'static str,
+    /// The payment instructions of a payment string
+    pub payment: Option<output::Payment>,
+    /// The payment string read: the instructions a ₿user@domain name resolved to,
+    /// or the pubkey of a node URI
+    pub payment_string: Option<String>,
+    /// The other fields describing the string, keyed by output name
+    pub fields: Map<String, Value>,
+    /// Whether the absent values of `payment` are given as null
+    pub all: bool,
+}
+
+impl Analysis {
+    /// A string of `kind` described by `fields` alone
+    fn new(kind: &'static str, fields: Map<String, Value>) -> Self {
+        Self {
+            kind,
+            payment: None,
+            payment_string: None,
+            fields,
+            all: false,
+        }
+    }
+
+    /// All the fields keyed by output name, those of the payment instructions included
+    pub fn into_fields(self) -> Map<String, Value> {
+        let mut map = Map::new();
+        if let Some(payment) = &self.payment {
+            output::insert_fields(&mut map, payment, self.all).expect("strings serialize");
+        }
+        // fields computed from the payment, such as a numeric amount, replace its own
+        map.extend(self.fields);
+
+        map
+    }
+}
+
+impl Serialize for Analysis {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        self.clone().into_fields().serialize(serializer)
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Serialize(serde_json::Error),
+    #[cfg(feature = "nostr")]
+    Bech32(nip19::Error),
+    Io(io::Error),
+    #[cfg(feature = "network")]
+    Http(Box<ureq::Error>),
+    /// Network access is needed but not allowed
+    Offline,
+    /// The proxy url is not usable
+    Proxy(String),
+    /// An exchange rate could not be determined
+    Rate(String),
+    /// A remote endpoint returned an error or an unexpected response
+    Resolve(String),
+    #[cfg(feature = "qr")]
+    Qr(qrcode::types::QrError),
+    #[cfg(feature = "png")]
+    Image(image::ImageError),
+    /// The QR code file has an unsupported extension
+    QrFormat(String),
+    /// Failed to encode a binary output format
+    Encode(String),
+    /// No clipboard tool was found, or it failed
+    Clipboard(String),
+    /// The node backend is not configured or returned an error
+    Backend(String),
+    /// The config file could not be read
+    Config(String),
+    /// The functionality needs the given cargo feature, which the build left out
+    Unsupported(&'static str),
+    /// An amount could not be parsed
+    InvalidAmount(String),
+    /// A key or address given for conversion is malformed
+    InvalidKey(String),
+    /// A part given to compose a URI or encode a string is malformed or doesn't fit the
+    /// others, or a string given to decode is malformed
+    InvalidPart(String),
+    /// The input is not a recognized bitcoin string, with hints for near misses
+    Unrecognized(Vec<suggest::Suggestion>),
+    /// The input looks like a bech32 or Base58Check string, but its checksum fails
+    Checksum(checksum::Diagnosis, Vec<suggest::Suggestion>),
+    /// The input is an address of the given other chain
+    ForeignChain(foreign::Chain),
+    /// The input parsed as a kind other than the one requested
+    UnexpectedKind(String, &'static str),
+    /// The input is for a network other than the one requested, or for none
+    UnexpectedNetwork(String, Option<String>),
+    /// The invoice or offer expired at the given unix time
+    Expired(u64),
+    /// The amount in millisatoshis is missing or outside of the `--min-amount` and
+    /// `--max-amount` range
+    AmountOutOfRange(Option<u64>),
+    /// The URI has required parameters that are not understood
+    UnknownRequiredParams(Vec<String>),
+    /// The descriptor came with a checksum other than the given correct one
+    DescriptorChecksum(String),
+    /// The input breaks the given rule of `--strict`
+    Strict(&'static str),
+    /// A QR code or copy of the query would reveal the secrets masked in the output
+    Redacted,
+    /// The selected field is not present in the result
+    MissingField(String),
+    /// The kind has no single value to print with `--raw`
+    NoRawValue(&'static str),
+    /// The input of the given length in bytes is longer than the limit
+    InputTooLarge(usize, usize),
+    /// The given detector took longer than the time budget on the input
+    Timeout(&'static str),
+    /// The node client has no command paying the kind, for `--emit-command`
+    NoCommand(&'static str, &'static str),
+}
+
+impl Error {
+    /// Exit status of the class of the error, documented in the README and stable
+    /// across releases. 2 is also that of usage errors reported by clap.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::Serialize(_) | Error::Encode(_) | Error::Io(_) | Error::Clipboard(_) => 1,
+            #[cfg(feature = "qr")]
+            Error::Qr(_) => 1,
+            #[cfg(feature = "png")]
+            Error::Image(_) => 1,
+            Error::Config(_)
+            | Error::Unsupported(_)
+            | Error::Proxy(_)
+            | Error::QrFormat(_)
+            | Error::InvalidAmount(_)
+            | Error::InvalidKey(_)
+            | Error::InvalidPart(_) => 2,
+            #[cfg(feature = "nostr")]
+            Error::Bech32(_) => 2,
+            Error::Expired(_) => 3,
+            Error::Unrecognized(_) | Error::Checksum(..) | Error::ForeignChain(_) => 4,
+            Error::UnexpectedKind(..)
+            | Error::UnexpectedNetwork(..)
+            | Error::AmountOutOfRange(_)
+            | Error::UnknownRequiredParams(_)
+            | Error::DescriptorChecksum(_)
+            | Error::Strict(_)
+            | Error::Redacted
+            | Error::InputTooLarge(..)
+            | Error::Timeout(_) => 5,
+            Error::Offline | Error::Resolve(_) | Error::Backend(_) | Error::Rate(_) => 6,
+            #[cfg(feature = "network")]
+            Error::Http(_) => 6,
+            Error::MissingField(_) | Error::NoRawValue(_) | Error::NoCommand(..) => 7,
+        }
+    }
+
+    /// A stable identifier of the error, e.g. `E_UNRECOGNIZED`
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::Serialize(_) | Error::Encode(_) => "E_ENCODE",
+            #[cfg(feature = "nostr")]
+            Error::Bech32(_) => "E_BECH32",
+            Error::Io(_) => "E_IO",
+            Error::Clipboard(_) => "E_CLIPBOARD",
+            #[cfg(feature = "network")]
+            Error::Http(_) => "E_HTTP",
+            Error::Offline => "E_OFFLINE",
+            Error::Proxy(_) => "E_PROXY",
+            Error::Rate(_) => "E_RATE",
+            Error::Resolve(_) => "E_RESOLVE",
+            #[cfg(feature = "qr")]
+            Error::Qr(_) => "E_QR",
+            #[cfg(feature = "png")]
+            Error::Image(_) => "E_QR",
+            Error::QrFormat(_) => "E_QR",
+            Error::Backend(_) => "E_BACKEND",
+            Error::Config(_) => "E_CONFIG",
+            Error::Unsupported(_) => "E_UNSUPPORTED",
+            Error::InvalidAmount(_) => "E_INVALID_AMOUNT",
+            Error::InvalidKey(_) => "E_INVALID_KEY",
+            Error::InvalidPart(_) => "E_INVALID_PART",
+            Error::Unrecognized(_) => "E_UNRECOGNIZED",
+            Error::Checksum(..) => "E_CHECKSUM",
+            Error::ForeignChain(_) => "E_FOREIGN_CHAIN",
+            Error::UnexpectedKind(..) => "E_UNEXPECTED_KIND",
+            Error::UnexpectedNetwork(..) => "E_UNEXPECTED_NETWORK",
+            Error::Expired(_) => "E_EXPIRED",
+            Error::AmountOutOfRange(_) => "E_AMOUNT_OUT_OF_RANGE",
+            Error::UnknownRequiredParams(_) => "E_UNKNOWN_REQUIRED_PARAMS",
+            Error::DescriptorChecksum(_) => "E_DESCRIPTOR_CHECKSUM",
+            Error::Strict(_) => "E_STRICT",
+            Error::Redacted => "E_REDACTED",
+            Error::MissingField(_) => "E_MISSING_FIELD",
+            Error::NoRawValue(_) => "E_NO_RAW_VALUE",
+            Error::NoCommand(..) => "E_NO_COMMAND",
+            Error::InputTooLarge(..) => "E_INPUT_TOO_LARGE",
+            Error::Timeout(_) => "E_TIMEOUT",
+        }
+    }
+
+    /// Hints on what the input may have been meant as
+    pub fn suggestions(&self) -> &[suggest::Suggestion] {
+        match self {
+            Error::Unrecognized(suggestions) | Error::Checksum(_, suggestions) => suggestions,
+            _ => &[],
+        }
+    }
+
+    /// Whether the error concerns a single input rather than the program as a whole
+    pub fn is_input_error(&self) -> bool {
+        match self {
+            #[cfg(feature = "network")]
+            Error::Http(_) => true,
+            _ => matches!(
+                self,
+                Error::Offline
+                    | Error::Resolve(_)
+                    | Error::Backend(_)
+                    | Error::Unrecognized(_)
+                    | Error::Checksum(..)
+                    | Error::ForeignChain(_)
+                    | Error::UnexpectedKind(..)
+                    | Error::UnexpectedNetwork(..)
+                    | Error::Expired(_)
+                    | Error::AmountOutOfRange(_)
+                    | Error::Strict(_)
+                    | Error::DescriptorChecksum(_)
+                    | Error::UnknownRequiredParams(_)
+                    | Error::Redacted
+                    | Error::MissingField(_)
+                    | Error::NoRawValue(_)
+                    | Error::NoCommand(..)
+                    | Error::InputTooLarge(..)
+                    | Error::Timeout(_)
+            ),
+        }
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Error::Serialize(e)
+    }
+}
+
+#[cfg(feature = "nostr")]
+impl From<nip19::Error> for Error {
+    fn from(e: nip19::Error) -> Self {
+        Error::Bech32(e)
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+#[cfg(feature = "qr")]
+impl From<qrcode::types::QrError> for Error {
+    fn from(e: qrcode::types::QrError) -> Self {
+        Error::Qr(e)
+    }
+}
+
+#[cfg(feature = "png")]
+impl From<image::ImageError> for Error {
+    fn from(e: image::ImageError) -> Self {
+        Error::Image(e)
+    }
+}
+
+#[cfg(feature = "network")]
+impl From<ureq::Error> for Error {
+    fn from(e: ureq::Error) -> Self {
+        Error::Http(Box::new(e))
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Serialize(e) => write!(f, "{e}"),
+            #[cfg(feature = "nostr")]
+            Error::Bech32(e) => write!(f, "{e}"),
+            Error::Io(e) => write!(f, "{e}"),
+            #[cfg(feature = "network")]
+            Error::Http(e) => write!(f, "{e}"),
+            Error::Offline => write!(f, "network access is disabled, pass --online to allow it"),
+            Error::Proxy(e) => write!(f, "{e}"),
+            Error::Rate(e) => write!(f, "{e}"),
+            Error::Resolve(e) => write!(f, "{e}"),
+            #[cfg(feature = "qr")]
+            Error::Qr(e) => write!(f, "{e}"),
+            #[cfg(feature = "png")]
+            Error::Image(e) => write!(f, "{e}"),
+            Error::Encode(e) => write!(f, "{e}"),
+            Error::Clipboard(e) => write!(f, "could not copy to the clipboard: {e}"),
+            Error::QrFormat(path) => write!(f, "QR code file must end in .png or .svg: {path}"),
+            Error::Backend(e) => write!(f, "{e}"),
+            Error::Config(e) => write!(f, "config file {e}"),
+            Error::Unsupported(feature) => {
+                write!(f, "waila-cli was built without the {feature} feature")
+            }
+            Error::InvalidAmount(s) => write!(f, "invalid amount: {s}"),
+            Error::InvalidKey(e) => write!(f, "{e}"),
+            Error::InvalidPart(e) => write!(f, "{e}"),
+            Error::Unrecognized(suggestions) => {
+                write!(f, "not a bitcoin string")?;
+                write_suggestions(f, suggestions)
+            }
+            Error::Checksum(diagnosis, suggestions) => {
+                write!(f, "{diagnosis}")?;
+                write_suggestions(f, suggestions)
+            }
+            Error::ForeignChain(chain) => {
+                write!(f, "{chain} address, not a bitcoin one")
+            }
+            Error::UnexpectedKind(expected, found) => {
+                write!(f, "expected kind {expected}, found {found}")
+            }
+            Error::UnexpectedNetwork(expected, Some(found)) => {
+                write!(f, "expected network {expected}, found {found}")
+            }
+            Error::UnexpectedNetwork(expected, None) => {
+                write!(f, "expected network {expected}, found no network")
+            }
+            Error::Expired(at) => write!(f, "expired at unix time {at}"),
+            Error::AmountOutOfRange(Some(msat)) => {
+                write!(f, "amount out of range: {msat} msat")
+            }
+            Error::AmountOutOfRange(None) => write!(f, "amount out of range: no amount"),
+            Error::UnknownRequiredParams(params) => write!(
+                f,
+                "URI must not be used, unknown required parameter {}",
+                params.join(", ")
+            ),
+            Error::DescriptorChecksum(expected) => {
+                write!(f, "invalid descriptor checksum, expected {expected}")
+            }
+            Error::Strict(rule) => write!(f, "rejected by --strict: {rule}"),
+            Error::Redacted => write!(
+                f,
+                "the query holds secrets, pass --show-secrets to encode it in a QR code or copy it"
+            ),
+            Error::MissingField(field) => write!(f, "field not present: {field}"),
+            Error::NoRawValue(kind) => write!(f, "no raw value for kind {kind}"),
+            Error::NoCommand(tool, kind) => write!(f, "{tool} has no command paying kind {kind}"),
+            Error::InputTooLarge(len, max) => {
+                write!(f, "input of {len} bytes is longer than the limit of {max}")
+            }
+            Error::Timeout(detector) => {
+                write!(f, "the {detector} detector ran over its time budget")
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+fn write_suggestions(
+    f: &mut fmt::Formatter<'_>,
+    suggestions: &[suggest::Suggestion],
+) -> fmt::Result {
+    for suggestion in suggestions {
+        write!(f, "; {suggestion}")?;
+    }
+
+    Ok(())
+}
+
+pub type Result<T> = core::result::Result<T, Error>;
+
+/// Parse a single bitcoin string into its kind and a json map of its fields
+pub fn analyze(s: &str, opts: &Options) -> Result<Analysis> {
+    let (unit, rates) = (opts.unit, opts.rates.as_ref());
+    #[cfg(not(feature = "network"))]
+    if opts.resolve || opts.request_invoice.is_some() || opts.lookup || opts.probe {
+        return Err(Error::Unsupported("network"));
+    }
+    #[cfg(not(feature = "nostr"))]
+    if opts.nostr {
+        return Err(Error::Unsupported("nostr"));
+    }
+    if s.len() > opts.max_input {
+        return Err(Error::InputTooLarge(s.len(), opts.max_input));
+    }
+    if opts.strict && s.trim() != s {
+        return Err(Error::Strict("no whitespace around the string"));
+    }
+
+    // a human readable name, ₿user@domain, stands for the payment instructions found in DNS
+    let mut bip353 = None;
+    let query = match s.strip_prefix('₿') {
+        Some(name) if opts.resolve => {
+            let resolution = resolve_name(name, opts)?;
+            let uri = resolution.uri.clone();
+            bip353 = Some(resolution);
+            uri
+        }
+        Some(_) if opts.strict => {
+            return Err(Error::Strict(
+                "a BIP-353 name is only read as its payment instructions, with --resolve",
+            ))
+        }
+        Some(name) => name.to_string(),
+        None => s.to_string(),
+    };
+
+    if let Some(secret) = timed(opts, "secret", {
+        let query = query.clone();
+        move || secret::detect(&query)
+    })? {
+        debug!(secret = secret.kind.as_str(), "detected secret material");
+        return analyze_secret(&secret, opts);
+    }
+
+    let unknown = uri::unknown_required(&query);
+    if !unknown.is_empty() {
+        return Err(Error::UnknownRequiredParams(unknown));
+    }
+
+    let payment = timed(opts, "payment string", {
+        let query = query.clone();
+        move || PaymentParams::from_str(&query).ok()
+    })?
+    .map(|pp| (pp, query.clone()))
+    .or_else(|| {
+        // a node URI reads as its pubkey
+        if opts.strict {
+            return None;
+        }
+        let pubkey = probe::NodeUri::parse(&query)?.pubkey.to_string();
+        Some((PaymentParams::from_str(&pubkey).ok()?, pubkey))
+    })
+    // don't expose nostr results unsolicited
+    .filter(|(pp, _)| opts.nostr || !matches!(pp, PaymentParams::Nostr(_)));
+    let Some((payment_params, payment_string)) = payment else {
+        debug!("not a payment string, trying descriptors");
+        if let Some(desc) = timed(opts, "descriptor", {
+            let query = query.clone();
+            move || descriptor::detect(&query)
+        })? {
+            debug!("detected descriptor");
+            return analyze_descriptor(&desc, opts);
+        }
+        debug!("not a descriptor, trying transactions");
+        return match timed(opts, "transaction", {
+            let query = query.clone();
+            move || tx::detect(&query)
+        })? {
+            Some(tx) => {
+                debug!("detected transaction");
+                analyze_tx(&tx, opts)
+            }
+            None if opts.strict && probe::NodeUri::parse(&query).is_some() => Err(Error::Strict(
+                "a node URI is not a payment string, give the pubkey alone",
+            )),
+            None => {
+                if let Some(detector) = detector::find(&query) {
+                    debug!(kind = detector.kind(), "matched custom detector");
+                    return analyze_custom(detector, &query, opts);
+                }
+                if let Some(chain) = foreign::detect(&query) {
+                    debug!(%chain, "address of another chain");
+                    return Err(Error::ForeignChain(chain));
+                }
+                // the slowest detector, only run once nothing else matched
+                let diagnosis = timed(opts, "checksum", {
+                    let query = query.clone();
+                    move || checksum::diagnose(&query)
+                })?;
+                match diagnosis {
+                    Some(mut diagnosis) => {
+                        // a repaired secret is as sensitive as the secret
+                        let secret = diagnosis.candidate.as_deref().and_then(secret::detect);
+                        if secret.is_some() && !opts.show_secrets {
+                            diagnosis.candidate = None;
+                        }
+                        debug!("checksum failed: {diagnosis}");
+                        Err(Error::Checksum(diagnosis, suggest::suggest(&query)))
+                    }
+                    None => {
+                        debug!("unrecognized");
+                        Err(Error::Unrecognized(suggest::suggest(&query)))
+                    }
+                }
+            }
+        };
+    };
+
+    /* Build a `serde_json::Map` with the following keys. All fields, if applicable, are of type String,
+    or `Map<String, String>` in the case of 'nostr'. With `--numeric-amounts` the amount is a Number,
+    and 'amounts' holds the amount in btc (String), sat and msat (Number). 'fiat' holds the
+    rates and converted values requested with `--fiat`, and 'resolved' the details fetched
+    with `--resolve`. 'expires_at' (Number), 'expired' (Bool) and 'time_remaining' tell the
+    expiry of an invoice or offer. 'description_hash' is the sha256 an invoice gives instead of
+    its description, and 'description_matches' (Bool) whether that of `--description` is it.
+    'bip353' describes the DNS lookup of a ₿user@domain name.
+    'requested_invoice' is the parsed result of `--request-invoice`. 'chain' the on-chain
+    state, 'payjoin_status' the payjoin endpoint health and 'node' the lightning node are
+    found with `--lookup`. 'probe' is the connectivity
+    report of `--probe`. 'warnings' lists non-fatal issues as objects with a 'code',
+    'severity' (info, warning or critical), 'field' they concern and 'message'.
+    Transactions are described by `tx::TxInput::describe`, descriptors by
+    `descriptor::Descriptor::describe` and secrets by `secret::Secret::describe`.
+        kind
+        bip353
+        network
+        address
+        invoice
+        pubkey
+        amount
+        unit
+        amounts
+        fiat
+        expires_at
+        expired
+        time_remaining
+        memo
+        description_hash
+        description_matches
+        lnurl
+        lnaddr
+        payjoin
+        resolved
+        requested_invoice
+        chain
+        payjoin_status
+        node
+        probe
+        nostr
+        warnings
+    */
+    let mut map = Map::new();
+
+    let kind = kind(&payment_params);
+    debug!(kind, "parsed payment string");
+    expect_kind(kind, opts)?;
+    expect_network(payment_params.network().map(|net| net.to_string()), opts)?;
+    let msat = amount_msat(&payment_params)?;
+    expect_amount(msat, opts)?;
+    let expires_at = expiry::expires_at(&payment_params);
+    if let (true, Some(at)) = (opts.reject_expired, expires_at) {
+        if expiry::is_expired(at) {
+            return Err(Error::Expired(at));
+        }
+    }
+    if opts.kind_only {
+        return Ok(Analysis::new(kind, Map::new()));
+    }
+    map.insert("kind".to_string(), Value::String(kind.to_string()));
+    if let Some(resolution) = &bip353 {
+        map.insert("bip353".to_string(), resolution.to_json());
+    }
+
+    if opts.wants(&["amounts", "fiat"]) {
+        if let Some(msat) = msat {
+            map.insert("amounts".to_string(), amounts(msat));
+            if let Some(rates) = rates.filter(|_| opts.wants(&["fiat"])) {
+                map.insert("fiat".to_string(), rates.convert(msat_to_amount(msat)));
+            }
+        } else if opts.all {
+            map.insert("amounts".to_string(), json!(null));
+            if rates.is_some() {
+                map.insert("fiat".to_string(), json!(null));
+            }
+        }
+    }
+
+    let expiry_fields = ["expires_at", "expired", "time_remaining"];
+    if opts.wants(&expiry_fields) {
+        if let Some(at) = expires_at {
+            for (key, value) in expiry::describe(at) {
+                map.insert(key.to_string(), value);
+            }
+        } else if opts.all {
+            for key in expiry_fields {
+                map.insert(key.to_string(), json!(null));
+            }
+        }
+    }
+
+    let description_fields = ["description_hash", "description_matches"];
+    let mut description_matches = None;
+    if opts.wants(&description_fields) {
+        if let Some(hash) = description_hash(&payment_params) {
+            if let Some(text) = &opts.description {
+                let matches = sha256::Hash::hash(text.as_bytes()).to_string() == hash;
+                description_matches = Some(matches);
+                map.insert("description_matches".to_string(), Value::Bool(matches));
+            }
+            map.insert("description_hash".to_string(), Value::String(hash));
+        } else if opts.all {
+            for key in description_fields {
+                map.insert(key.to_string(), json!(null));
+            }
+        }
+    }
+
+    if opts.numeric_amounts {
+        if let Some(msat) = msat {
+            map.insert("amount".to_string(), numeric_amount(msat, unit));
+            map.insert("unit".to_string(), Value::String(unit.to_string()));
+        } else if opts.all {
+            map.insert("unit".to_string(), json!(null));
+        }
+    }
+
+    #[cfg(feature = "network")]
+    map.extend(online_fields(&payment_params, &query, opts)?);
+
+    #[cfg(feature = "nostr")]
+    if opts.nostr && opts.wants(&["nostr"]) {
+        map.insert("nostr".to_string(), parse_nostr(&payment_params)?);
+    }
+
+    if opts.wants(&["warnings"]) {
+        let warnings = collect_warnings(&payment_params, expires_at, description_matches);
+        if !warnings.is_empty() || opts.all {
+            map.insert("warnings".to_string(), serde_json::to_value(&warnings)?);
+        }
+    }
+
+    Ok(Analysis {
+        kind,
+        payment: Some(output::Payment::new(&payment_params, unit)),
+        payment_string: Some(payment_string),
+        fields: map,
+        all: opts.all,
+    })
+}
+
+/// Detector threads that may run at once. A detector over its budget is left to
+/// finish, which the detectors do in time linear in the input or bounded otherwise,
+/// and inputs are rejected while this many are still running.
+const MAX_DETECTOR_THREADS: usize = 32;
+
+/// Detector threads running
+static DETECTOR_THREADS: AtomicUsize = AtomicUsize::new(0);
+
+/// The result of the detector `f`, failing once it runs over the time budget of `opts`.
+/// It runs on a thread of its own, which is left to finish in the background.
+fn timed<T: Send + 'static>(
+    opts: &Options,
+    detector: &'static str,
+    f: impl FnOnce() -> T + Send + 'static,
+) -> Result<T> {
+    // without a budget there is nothing to time, nor threads on every platform
+    let Some(budget) = opts.time_budget else {
+        return Ok(f());
+    };
+    if DETECTOR_THREADS.fetch_add(1, Ordering::SeqCst) >= MAX_DETECTOR_THREADS {
+        DETECTOR_THREADS.fetch_sub(1, Ordering::SeqCst);
+        debug!(detector, "too many detectors running");
+        return Err(Error::Timeout(detector));
+    }
+    /// Counts the thread as running until it ends, panicking or not
+    struct Running;
+    impl Drop for Running {
+        fn drop(&mut self) {
+            DETECTOR_THREADS.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+
+    let (sender, receiver) = mpsc::channel();
+    let handle = std::thread::spawn(move || {
+        let _running = Running;
+        // the receiver is gone if the budget ran out
+        let _ = sender.send(f());
+    });
+
+    match receiver.recv_timeout(budget) {
+        Ok(result) => Ok(result),
+        Err(RecvTimeoutError::Timeout) => {
+            debug!(detector, ?budget, "over the time budget");
+            Err(Error::Timeout(detector))
+        }
+        // the detector panicked
+        Err(RecvTimeoutError::Disconnected) => match handle.join() {
+            Err(panic) => std::panic::resume_unwind(panic),
+            Ok(()) => unreachable!("the detector sends its result before returning"),
+        },
+    }
+}
+
+/// Describe a transaction or txid, with its confirmation status if `--lookup` is set
+fn analyze_tx(tx: &tx::TxInput, opts: &Options) -> Result<Analysis> {
+    let kind = tx.kind();
+    expect_kind(kind, opts)?;
+    // a transaction doesn't tell its network
+    expect_network(None, opts)?;
+    expect_amount(None, opts)?;
+    if opts.kind_only {
+        return Ok(Analysis::new(kind, Map::new()));
+    }
+
+    let mut map = tx.describe();
+    map.insert("kind".to_string(), Value::String(kind.to_string()));
+    if let (tx::TxInput::Raw(raw), true) = (tx, opts.wants(&["warnings"])) {
+        let warnings = warnings::for_tx(raw);
+        if !warnings.is_empty() || opts.all {
+            map.insert("warnings".to_string(), serde_json::to_value(&warnings)?);
+        }
+    }
+    #[cfg(feature = "network")]
+    if opts.lookup && opts.wants(&["chain"]) {
+        // a txid doesn't tell its network
+        let backend = chain_for(opts, Some("bitcoin"))?;
+        map.insert(
+            "chain".to_string(),
+            backend.tx_status(&tx.txid().to_string())?,
+        );
+    }
+
+    Ok(Analysis::new(kind, map))
+}
+
+/// Describe a descriptor, failing if it came with a wrong checksum
+fn analyze_descriptor(desc: &descriptor::Descriptor, opts: &Options) -> Result<Analysis> {
+    let kind = "Descriptor";
+    expect_kind(kind, opts)?;
+    // the keys may be for any network
+    expect_network(None, opts)?;
+    expect_amount(None, opts)?;
+    if desc.is_mismatch() {
+        return Err(Error::DescriptorChecksum(desc.checksum.clone()));
+    }
+    if opts.kind_only {
+        return Ok(Analysis::new(kind, Map::new()));
+    }
+
+    let mut map = desc.describe();
+    map.insert("kind".to_string(), Value::String(kind.to_string()));
+    let redacted = map["descriptor"].as_str().and_then(secret::redact_keys);
+    if let (false, Some(redacted)) = (opts.show_secrets, redacted) {
+        map.insert("descriptor".to_string(), json!(redacted));
+        map.insert("redacted".to_string(), json!(true));
+    }
+
+    Ok(Analysis::new(kind, map))
+}
+
+/// Describe a string of a format registered with [`detector::register`]
+fn analyze_custom(detector: &dyn detector::Detector, s: &str, opts: &Options) -> Result<Analysis> {
+    let kind = detector.kind();
+    expect_kind(kind, opts)?;
+    expect_network(None, opts)?;
+    expect_amount(None, opts)?;
+    if opts.kind_only {
+        return Ok(Analysis::new(kind, Map::new()));
+    }
+
+    let mut map = detector.parse(s)?;
+    map.insert("kind".to_string(), Value::String(kind.to_string()));
+
+    Ok(Analysis::new(kind, map))
+}
+
+/// Describe secret material, masked unless `--show-secrets` is set
+fn analyze_secret(secret: &secret::Secret, opts: &Options) -> Result<Analysis> {
+    let kind = "Secret";
+    expect_kind(kind, opts)?;
+    expect_network(secret.network.map(str::to_string), opts)?;
+    expect_amount(None, opts)?;
+    if opts.kind_only {
+        return Ok(Analysis::new(kind, Map::new()));
+    }
+
+    let mut map = secret.describe(opts.show_secrets);
+    map.insert("kind".to_string(), Value::String(kind.to_string()));
+    if secret.kind == secret::Kind::Xprv && opts.wants(&["warnings"]) {
+        map.insert("warnings".to_string(), json!([warnings::xprv()]));
+    }
+
+    Ok(Analysis::new(kind, map))
+}
+
+/// Non-fatal issues of the payment: its address script, dust, expiry and an
+/// invoice for another network than the address
+fn collect_warnings(
+    payment_params: &PaymentParams,
+    expires_at: Option<u64>,
+    description_matches: Option<bool>,
+) -> Vec<warnings::Warning> {
+    let mut collected = vec![];
+    if let Some(addr) = payment_params.address() {
+        let addr = addr.to_string();
+        collected.extend(warnings::for_address(&addr));
+        if let Some(amount) = payment_params.amount() {
+            collected.extend(warnings::dust(amount.to_sat()));
+        }
+        if let Some(inv) = payment_params.invoice() {
+            // convert to the correct type for our imports
+            if let Ok(inv) = lightning_invoice::Bolt11Invoice::from_str(&inv.to_string()) {
+                collected.extend(warnings::network_mismatch(
+                    &addr,
+                    &inv.network().to_string(),
+                ));
+            }
+        }
+    }
+    collected.extend(warnings::expired(
+        expires_at.is_some_and(expiry::is_expired),
+    ));
+    collected.extend(warnings::description_mismatch(
+        description_matches == Some(false),
+    ));
+
+    collected
+}
+
+/// The kind of `payment_params`, as output in the `kind` field
+fn kind(payment_params: &PaymentParams) -> &'static str {
+    // Any additional `PaymentParams` variants must be included here
+    match payment_params {
+        PaymentParams::OnChain(_) => "OnChain",
+        PaymentParams::Bip21(_) => "UnifiedUri",
+        PaymentParams::Bolt11(_) => "Invoice",
+        PaymentParams::Bolt12(_) => "Offer",
+        PaymentParams::NodePubkey(_) => "PublicKey",
+        PaymentParams::LnUrl(_) => "LnUrl",
+        PaymentParams::LightningAddress(_) => "LnAddress",
+        PaymentParams::Nostr(_) => "NostrValue",
+    }
+}
+
+/// The lightning node to pay, given directly or as the payee of an invoice
+fn node_id(payment_params: &PaymentParams) -> Option<String> {
+    if let Some(pk) = payment_params.node_pubkey() {
+        return Some(pk.to_string());
+    }
+    let inv = payment_params.invoice()?;
+    // convert to the correct type for our imports
+    let inv = lightning_invoice::Bolt11Invoice::from_str(&inv.to_string()).ok()?;
+    let pk = inv
+        .payee_pub_key()
+        .copied()
+        .unwrap_or_else(|| inv.recover_payee_pub_key());
+
+    Some(pk.to_string())
+}
+
+/// The sha256 of its description in hex that the invoice of `payment_params` gives, if it
+/// gives that rather than the description
+fn description_hash(payment_params: &PaymentParams) -> Option<String> {
+    let inv = payment_params.invoice()?;
+    // convert to the correct type for our imports
+    let inv = lightning_invoice::Bolt11Invoice::from_str(&inv.to_string()).ok()?;
+    match inv.description() {
+        lightning_invoice::Bolt11InvoiceDescription::Hash(hash) => Some(hash.0.to_string()),
+        lightning_invoice::Bolt11InvoiceDescription::Direct(_) => None,
+    }
+}
+
+/// Fail unless `kind` is the one requested with [`Options::kind`]
+fn expect_kind(kind: &'static str, opts: &Options) -> Result<()> {
+    match &opts.kind {
+        Some(expected) if !kind.eq_ignore_ascii_case(expected) => {
+            Err(Error::UnexpectedKind(expected.clone(), kind))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Fail unless `network`, as displayed by `Network`, is the one requested with
+/// [`Options::expect_network`]. An input without a network never matches.
+fn expect_network(network: Option<String>, opts: &Options) -> Result<()> {
+    let Some(expected) = &opts.expect_network else {
+        return Ok(());
+    };
+    let expected = expected.to_ascii_lowercase();
+    let display = if expected == "mainnet" {
+        "bitcoin"
+    } else {
+        expected.as_str()
+    };
+    match network {
+        Some(network) if network == display => Ok(()),
+        network => Err(Error::UnexpectedNetwork(expected, network)),
+    }
+}
+
+/// Fail unless `msat` is within [`Options::min_amount`] and [`Options::max_amount`].
+/// Without an amount, the range is never met.
+fn expect_amount(msat: Option<u64>, opts: &Options) -> Result<()> {
+    if opts.min_amount.is_none() && opts.max_amount.is_none() {
+        return Ok(());
+    }
+    match msat {
+        Some(msat)
+            if !opts.min_amount.is_some_and(|min| msat < min)
+                && !opts.max_amount.is_some_and(|max| msat > max) =>
+        {
+            Ok(())
+        }
+        msat => Err(Error::AmountOutOfRange(msat)),
+    }
+}
+
+/// Time a lookup of [`online_fields`] may take with all its requests
+#[cfg(feature = "network")]
+const LOOKUP_DEADLINE: Duration = Duration::from_secs(20);
+
+/// A lookup of output fields that goes online
+#[cfg(feature = "network")]
+type Lookup<'a> = Box<dyn FnOnce() -> Result<Map<String, Value>> + Send + 'a>;
+
+/// The fields of `payment_params` found online. The lookups are independent and run
+/// concurrently, each bounded by [`LOOKUP_DEADLINE`] as well as its requests by the
+/// network timeout. The error of the first lookup that failed, in the order of the
+/// output, is returned.
+///
+/// The lookups run on scoped threads rather than an async runtime, on purpose: the
+/// HTTP, DNS, Electrum and node clients all block, a string has at most six lookups,
+/// and a runtime would be linked into every build without making them any more
+/// concurrent. The gRPC server, which has one, runs whole queries on its blocking pool.
+#[cfg(feature = "network")]
+fn online_fields(
+    payment_params: &PaymentParams,
+    query: &str,
+    opts: &Options,
+) -> Result<Map<String, Value>> {
+    // lookups of fields that aren't selected are skipped, they are the slowest part
+    let mut lookups: Vec<Lookup> = vec![];
+    if (opts.resolve || opts.request_invoice.is_some())
+        && opts.wants(&["resolved", "requested_invoice"])
+    {
+        lookups.push(Box::new(|| lnurl_fields(payment_params, opts)));
+    }
+    if let (Some(amount), PaymentParams::Bolt12(offer), true) = (
+        opts.request_invoice,
+        payment_params,
+        opts.wants(&["requested_invoice"]),
+    ) {
+        lookups.push(Box::new(move || {
+            let backend = cln_backend(opts)?;
+            let invoice = backend.fetch_invoice(&offer.to_string(), amount)?;
+            let invoice = backend.describe_invoice(&invoice)?;
+            Ok(optional_field("requested_invoice", Some(invoice), false))
+        }));
+    }
+    if opts.lookup && opts.wants(&["chain"]) {
+        lookups.push(Box::new(|| {
+            let chain = payment_params
+                .address()
+                .map(|addr| address_status(&addr.to_string(), payment_params, opts))
+                .transpose()?;
+            Ok(optional_field("chain", chain, opts.all))
+        }));
+    }
+    if opts.lookup && opts.wants(&["payjoin_status"]) {
+        lookups.push(Box::new(|| {
+            let status = payment_params
+                .payjoin_endpoint()
+                .map(|url| payjoin::check(&url.to_string()))
+                .transpose()?;
+            Ok(optional_field("payjoin_status", status, opts.all))
+        }));
+    }
+    if opts.lookup && opts.wants(&["node"]) {
+        lookups.push(Box::new(|| {
+            let node = node_id(payment_params)
+                .map(|pubkey| opts.graph.node_info(&pubkey, opts.graph_url.as_deref()))
+                .transpose()?;
+            Ok(optional_field("node", node, opts.all))
+        }));
+    }
+    if opts.probe && opts.wants(&["probe"]) {
+        lookups.push(Box::new(|| {
+            let probe = probe::NodeUri::parse(query)
+                .map(|node| probe::probe(&node, opts.handshake))
+                .transpose()?;
+            Ok(optional_field("probe", probe, opts.all))
+        }));
+    }
+
+    let deadline = std::time::Instant::now() + LOOKUP_DEADLINE;
+    let results: Vec<Result<Map<String, Value>>> = match lookups.len() {
+        // a single lookup needs no thread
+        0 | 1 => lookups
+            .into_iter()
+            .map(|lookup| net::with_deadline(deadline, lookup))
+            .collect(),
+        _ => std::thread::scope(|scope| {
+            let handles: Vec<_> = lookups
+                .into_iter()
+                .map(|lookup| scope.spawn(move || net::with_deadline(deadline, lookup)))
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| {
+                    handle
+                        .join()
+                        .unwrap_or_else(|e| std::panic::resume_unwind(e))
+                })
+                .collect()
+        }),
+    };
+    let mut map = Map::new();
+    for fields in results {
+        map.extend(fields?);
+    }
+
+    Ok(map)
+}
+
+/// The LNURL endpoint described with `--resolve` and its invoice with `--request-invoice`
+#[cfg(feature = "network")]
+fn lnurl_fields(payment_params: &PaymentParams, opts: &Options) -> Result<Map<String, Value>> {
+    let Some(url) = lnurl::endpoint(payment_params) else {
+        return Ok(optional_field("resolved", None, opts.all));
+    };
+    let resp = lnurl::fetch(&url)?;
+    let mut map = Map::new();
+    if opts.resolve {
+        map.insert("resolved".to_string(), lnurl::describe(&url, &resp)?);
+    }
+    if let Some(amount) = opts.request_invoice {
+        let Some(msat) = amount else {
+            return Err(Error::Resolve("LNURL-pay needs an amount".to_string()));
+        };
+        let pr = lnurl::request_invoice(&resp, msat)?;
+        map.insert(
+            "requested_invoice".to_string(),
+            describe_invoice(&pr, opts.unit)?,
+        );
+    }
+
+    Ok(map)
+}
+
+/// The balance, history and suggested feerates of `addr`
+#[cfg(feature = "network")]
+fn address_status(addr: &str, payment_params: &PaymentParams, opts: &Options) -> Result<Value> {
+    let network = payment_params.network().map(|net| net.to_string());
+    let backend = chain_for(opts, network.as_deref())?;
+    let mut chain = backend.address_stats(addr)?;
+    if let Some(n) = opts.history {
+        chain["history"] = backend.address_history(addr, n)?;
+    }
+    chain["suggested_feerates"] = backend.suggested_feerates()?;
+
+    Ok(chain)
+}
+
+/// A map of `key` and `value`, empty without a value unless `all` is set
+#[cfg(feature = "network")]
+fn optional_field(key: &str, value: Option<Value>, all: bool) -> Map<String, Value> {
+    let mut map = Map::new();
+    if value.is_some() || all {
+        map.insert(key.to_string(), value.unwrap_or(Value::Null));
+    }
+
+    map
+}
+
+/// The payment instructions of the BIP-353 name `user@domain`, from the configured resolver
+#[cfg(feature = "network")]
+fn resolve_name(name: &str, opts: &Options) -> Result<bip353::Resolution> {
+    let resolver = match &opts.doh {
+        Some(url) => bip353::Resolver::Https(url.clone()),
+        None => bip353::Resolver::Tcp(opts.dns_server),
+    };
+
+    bip353::resolve(name, &resolver)
+}
+
+#[cfg(not(feature = "network"))]
+fn resolve_name(_name: &str, _opts: &Options) -> Result<bip353::Resolution> {
+    Err(Error::Unsupported("network"))
+}
+
+/// The backend to query about `network`, as displayed by `Network`: bitcoind or
+/// the Electrum server if given, otherwise an Esplora instance
+#[cfg(feature = "network")]
+fn chain_for(opts: &Options, network: Option<&str>) -> Result<Box<dyn chain::Chain>> {
+    if let Some(url) = &opts.bitcoind {
+        return Ok(Box::new(bitcoind::Bitcoind::new(
+            url,
+            &bitcoind_auth(opts)?,
+        )?));
+    }
+    match &opts.electrum {
+        Some(url) => Ok(Box::new(electrum::Electrum::connect(url)?)),
+        None => Ok(Box::new(esplora_for(opts, network)?)),
+    }
+}
+
+/// The credentials for `--bitcoind`, the cookie file of a mainnet node by default
+#[cfg(feature = "network")]
+fn bitcoind_auth(opts: &Options) -> Result<bitcoind::Auth> {
+    if let Some(auth) = &opts.bitcoind_auth {
+        let Some((user, password)) = auth.split_once(':') else {
+            return Err(Error::Backend(
+                "expected USER:PASSWORD for --bitcoind-auth".to_string(),
+            ));
+        };
+        return Ok(bitcoind::Auth::UserPass(
+            user.to_string(),
+            password.to_string(),
+        ));
+    }
+    if let Some(path) = &opts.bitcoind_cookie {
+        return Ok(bitcoind::Auth::Cookie(path.clone()));
+    }
+    match std::env::var_os("HOME") {
+        Some(home) => Ok(bitcoind::Auth::Cookie(
+            PathBuf::from(home).join(".bitcoin").join(".cookie"),
+        )),
+        None => Err(Error::Backend(
+            "no bitcoind credentials, use --bitcoind-cookie or --bitcoind-auth".to_string(),
+        )),
+    }
+}
+
+/// The Esplora instance to query about `network`, as displayed by `Network`.
+/// An instance configured for the network wins over `--esplora`.
+#[cfg(feature = "network")]
+fn esplora_for(opts: &Options, network: Option<&str>) -> Result<esplora::Esplora> {
+    let network = network.unwrap_or_default();
+    let configured = opts
+        .esplora_urls
+        .iter()
+        .rev()
+        .find(|(net, _)| net == network)
+        .map(|(_, url)| url);
+    if let Some(url) = configured.or(opts.esplora.as_ref()) {
+        return Ok(esplora::Esplora::new(url));
+    }
+    match esplora::default_url(network) {
+        Some(url) => Ok(esplora::Esplora::new(url)),
+        None => Err(Error::Resolve(format!(
+            "no default Esplora for network {network}, use --esplora-url {network}=URL"
+        ))),
+    }
+}
+
+/// The Core Lightning node configured to request offer invoices with
+#[cfg(feature = "network")]
+fn cln_backend(opts: &Options) -> Result<cln::Backend> {
+    if let Some(path) = &opts.cln_rpc {
+        return Ok(cln::Backend::Rpc(path.clone()));
+    }
+    match (&opts.cln_rest, &opts.cln_rune) {
+        (Some(url), Some(rune)) => Ok(cln::Backend::Rest {
+            url: url.clone(),
+            rune: rune.clone(),
+        }),
+        _ => Err(Error::Backend(
+            "requesting an invoice from an offer needs --cln-rpc or --cln-rest".to_string(),
+        )),
+    }
+}
+
+/// Parse a bolt11 invoice obtained from a remote endpoint into a json object
+#[cfg(feature = "network")]
+fn describe_invoice(pr: &str, unit: Denomination) -> Result<Value> {
+    let Ok(payment_params) = PaymentParams::from_str(pr) else {
+        return Err(Error::Resolve(format!("not a bitcoin string: {pr}")));
+    };
+    let mut map = Map::new();
+    map.insert("kind".to_string(), Value::String("Invoice".to_string()));
+    output::insert_fields(
+        &mut map,
+        &output::Payment::new(&payment_params, unit),
+        false,
+    )?;
+    if let Some(msat) = amount_msat(&payment_params)? {
+        map.insert("amounts".to_string(), amounts(msat));
+    }
+    map.insert("verified".to_string(), Value::Bool(true));
+
+    Ok(Value::Object(map))
+}
+
+/// The amount in millisatoshis. Lightning invoices may carry sub-satoshi
+/// precision which is lost by [`PaymentParams::amount`].
+///
+/// ## Errors
+/// If the amount is too large to count in millisatoshis
+fn amount_msat(payment_params: &PaymentParams) -> Result<Option<u64>> {
+    if let Some(msat) = payment_params
+        .invoice()
+        .and_then(|inv| inv.amount_milli_satoshis())
+    {
+        return Ok(Some(msat));
+    }
+    let Some(amt) = payment_params.amount() else {
+        return Ok(None);
+    };
+
+    amt.to_sat()
+        .checked_mul(1_000)
+        .map(Some)
+        .ok_or_else(|| Error::InvalidAmount(format!("{amt} is out of range")))
+}
+
+/// Attempts to parse a nostr pubkey from [`PaymentParams`].
+/// Returns both hex and bech32 encoding.
+///
+/// ## Errors
+/// If unable to encode bech32
+#[cfg(feature = "nostr")]
+fn parse_nostr(payment_params: &PaymentParams) -> Result<serde_json::Value> {
+    let Some(k) = payment_params.nostr_pubkey() else {
+        return Ok(json!(null));
+    };
+
+    // convert to the correct type for our imports
+    let mykey = XOnlyPublicKey::from_str(&k.to_string()).expect("same value");
+    let bech32 = mykey.to_bech32()?;
+
+    let key = output::NostrKey {
+        hex: k.to_string(),
+        bech32,
+    };
+
+    Ok(serde_json::to_value(key)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn budget(ms: u64) -> Options {
+        Options {
+            time_budget: Some(Duration::from_millis(ms)),
+            ..Options::default()
+        }
+    }
+
+    #[test]
+    fn longer_inputs_than_max_input_are_rejected() {
+        let opts = Options {
+            max_input: 16,
+            ..Options::default()
+        };
+        let err = analyze(&"q".repeat(17), &opts).unwrap_err();
+        assert!(matches!(err, Error::InputTooLarge(17, 16)), "{err}");
+        assert!(err.is_input_error());
+    }
+
+    #[test]
+    fn detectors_over_budget_time_out() {
+        let start = std::time::Instant::now();
+        let result = timed(&budget(10), "slow", || {
+            std::thread::sleep(Duration::from_secs(2));
+        });
+        assert!(matches!(result, Err(Error::Timeout("slow"))));
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn detectors_within_budget_answer() {
+        assert_eq!(timed(&budget(5_000), "fast", || 42).unwrap(), 42);
+        assert_eq!(timed(&Options::default(), "unbudgeted", || 42).unwrap(), 42);
+    }
+
+    #[test]
+    fn long_garbled_strings_fit_the_budget() {
+        let query = format!("lnbc1{}", "q".repeat(100_000));
+        let opts = Options {
+            max_input: 1 << 20,
+            ..budget(5_000)
+        };
+        let err = analyze(&query, &opts).unwrap_err();
+        assert!(!matches!(err, Error::Timeout(_)), "{err}");
+    }
+
+    #[test]
+    fn amounts_too_large_for_msat_are_out_of_range() {
+        let address = "tb1pwzv7fv35yl7ypwj8w7al2t8apd6yf4568cs772qjwper74xqc99sk8x7tk";
+        // u64::MAX satoshis
+        let uri = format!("bitcoin:{address}?amount=184467440737.09551615");
+        let err = analyze(&uri, &Options::default()).unwrap_err();
+        assert!(matches!(err, Error::InvalidAmount(_)), "{err}");
+
+        let uri = format!("bitcoin:{address}?amount=0.015");
+        let analysis = analyze(&uri, &Options::default()).unwrap();
+        assert_eq!(analysis.into_fields()["amounts"]["msat"], 1_500_000_000);
+    }
+}
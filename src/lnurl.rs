@@ -0,0 +1,206 @@
+//! LNURL decoding and resolution (LUD-01, LUD-06, LUD-16, LUD-17)
+
+use crate::{net, Error, Result};
+use bitcoin::bech32::{self, FromBase32};
+use bitcoin::hashes::{sha256, Hash};
+use bitcoin_waila::PaymentParams;
+use lightning_invoice::{Bolt11Invoice, Bolt11InvoiceDescription};
+use serde_json::{json, Map, Value};
+use std::str::FromStr;
+
+/// The url behind an LNURL or lightning address
+pub fn endpoint(payment_params: &PaymentParams) -> Option<String> {
+    if let Some(lnurl) = payment_params.lnurl() {
+        return decode(&lnurl.to_string());
+    }
+    if let Some(lnaddr) = payment_params.lightning_address() {
+        return lnaddr_url(&lnaddr.to_string());
+    }
+
+    None
+}
+
+/// Decode a bech32 LNURL, or a LUD-17 url with a `lnurlp://` style scheme
+pub fn decode(s: &str) -> Option<String> {
+    if let Some((scheme, rest)) = s.split_once("://") {
+        return match scheme.to_ascii_lowercase().as_str() {
+            "https" | "http" => Some(s.to_string()),
+            "lnurlp" | "lnurlw" | "lnurlc" | "keyauth" => Some(with_scheme(rest)),
+            _ => None,
+        };
+    }
+
+    let s = s
+        .strip_prefix("lightning:")
+        .or_else(|| s.strip_prefix("LIGHTNING:"))
+        .unwrap_or(s);
+    let (hrp, data, _) = bech32::decode(s).ok()?;
+    if hrp != "lnurl" {
+        return None;
+    }
+    let bytes = Vec::<u8>::from_base32(&data).ok()?;
+
+    String::from_utf8(bytes).ok()
+}
+
+/// The LNURL-pay url of a lightning address (LUD-16)
+fn lnaddr_url(lnaddr: &str) -> Option<String> {
+    let (user, domain) = lnaddr.split_once('@')?;
+
+    Some(with_scheme(&format!("{domain}/.well-known/lnurlp/{user}")))
+}
+
+/// Prefix a url without scheme with https, or http for onion services
+fn with_scheme(rest: &str) -> String {
+    let host = rest.split(['/', ':']).next().unwrap_or_default();
+    if host.ends_with(".onion") {
+        format!("http://{rest}")
+    } else {
+        format!("https://{rest}")
+    }
+}
+
+/// Query an LNURL endpoint, failing on an error response (LUD-06)
+pub fn fetch(url: &str) -> Result<Value> {
+    // a withdraw request holds the k1 secret, which isn't to be left in the cache
+    let resp = net::get_json_cached_if(url, |resp| resp["tag"] != "withdrawRequest")?;
+    check_status(&resp)?;
+
+    Ok(resp)
+}
+
+fn check_status(resp: &Value) -> Result<()> {
+    if resp["status"].as_str() == Some("ERROR") {
+        let reason = resp["reason"].as_str().unwrap_or("unknown error");
+        return Err(Error::Resolve(reason.to_string()));
+    }
+
+    Ok(())
+}
+
+/// Describe the response `resp` of the LNURL endpoint at `url`
+pub fn describe(url: &str, resp: &Value) -> Result<Value> {
+    let mut obj = match resp["tag"].as_str() {
+        Some("payRequest") => pay_request(resp),
+        Some("withdrawRequest") => withdraw_request(resp),
+        Some(tag) => return Err(Error::Resolve(format!("unsupported LNURL tag {tag}"))),
+        None => return Err(Error::Resolve("not an LNURL response".to_string())),
+    };
+    obj.insert("url".to_string(), Value::String(url.to_string()));
+
+    Ok(Value::Object(obj))
+}
+
+/// Complete the LNURL-pay flow by requesting an invoice for `msat` from the
+/// callback of the pay request `resp`. The invoice is checked to be for the requested
+/// amount and to commit to the metadata of the pay request through its description hash.
+pub fn request_invoice(resp: &Value, msat: u64) -> Result<String> {
+    if resp["tag"].as_str() != Some("payRequest") {
+        return Err(Error::Resolve("not an LNURL-pay endpoint".to_string()));
+    }
+    let min = resp["minSendable"].as_u64().unwrap_or_default();
+    let max = resp["maxSendable"].as_u64().unwrap_or(u64::MAX);
+    if msat < min || msat > max {
+        return Err(Error::Resolve(format!(
+            "amount of {msat} msat is outside the sendable range {min}-{max} msat"
+        )));
+    }
+
+    let Some(callback) = resp["callback"].as_str() else {
+        return Err(Error::Resolve("pay request has no callback".to_string()));
+    };
+    let sep = if callback.contains('?') { '&' } else { '?' };
+    let invoice_resp = net::get_json(&format!("{callback}{sep}amount={msat}"))?;
+    check_status(&invoice_resp)?;
+    let Some(pr) = invoice_resp["pr"].as_str() else {
+        return Err(Error::Resolve("callback returned no invoice".to_string()));
+    };
+
+    let invoice = Bolt11Invoice::from_str(pr)
+        .map_err(|e| Error::Resolve(format!("callback returned an invalid invoice: {e}")))?;
+    if invoice.amount_milli_satoshis() != Some(msat) {
+        return Err(Error::Resolve(
+            "invoice amount differs from the requested amount".to_string(),
+        ));
+    }
+    let metadata = resp["metadata"].as_str().unwrap_or_default();
+    let expected = sha256::Hash::hash(metadata.as_bytes());
+    match invoice.description() {
+        Bolt11InvoiceDescription::Hash(h) if h.0 == expected => {}
+        _ => {
+            return Err(Error::Resolve(
+                "invoice description hash does not commit to the metadata".to_string(),
+            ))
+        }
+    }
+
+    Ok(pr.to_string())
+}
+
+/// Describe an LNURL-pay response (LUD-06). Amounts are in millisatoshis.
+fn pay_request(resp: &Value) -> Map<String, Value> {
+    let mut obj = Map::new();
+    obj.insert("tag".to_string(), json!("payRequest"));
+    obj.insert("callback".to_string(), resp["callback"].clone());
+    obj.insert("min_sendable".to_string(), resp["minSendable"].clone());
+    obj.insert("max_sendable".to_string(), resp["maxSendable"].clone());
+    if let Some(n) = resp["commentAllowed"].as_u64() {
+        obj.insert("comment_allowed".to_string(), json!(n));
+    }
+
+    let metadata = resp["metadata"].as_str().unwrap_or("[]");
+    for (key, value) in parse_metadata(metadata) {
+        obj.insert(key.to_string(), Value::String(value));
+    }
+
+    obj
+}
+
+/// Describe an LNURL-withdraw response (LUD-03). Amounts are in millisatoshis.
+/// The `k1` secret which authorizes the withdrawal is left out.
+fn withdraw_request(resp: &Value) -> Map<String, Value> {
+    let mut obj = Map::new();
+    obj.insert("tag".to_string(), json!("withdrawRequest"));
+    obj.insert("callback".to_string(), resp["callback"].clone());
+    obj.insert(
+        "min_withdrawable".to_string(),
+        resp["minWithdrawable"].clone(),
+    );
+    obj.insert(
+        "max_withdrawable".to_string(),
+        resp["maxWithdrawable"].clone(),
+    );
+    obj.insert(
+        "default_description".to_string(),
+        resp["defaultDescription"].clone(),
+    );
+
+    obj
+}
+
+/// Pick the known entries out of LNURL-pay metadata, a json array of
+/// `[mime type, content]` pairs encoded as a string
+fn parse_metadata(metadata: &str) -> Vec<(&'static str, String)> {
+    let Ok(Value::Array(entries)) = serde_json::from_str::<Value>(metadata) else {
+        return vec![];
+    };
+
+    let mut out = vec![];
+    for entry in entries {
+        let (Some(mime), Some(content)) = (entry[0].as_str(), entry[1].as_str()) else {
+            continue;
+        };
+        match mime {
+            "text/plain" => out.push(("description", content.to_string())),
+            "text/long-desc" => out.push(("long_description", content.to_string())),
+            "text/identifier" | "text/email" => out.push(("identifier", content.to_string())),
+            "image/png;base64" | "image/jpeg;base64" => {
+                let mime = mime.trim_end_matches(";base64");
+                out.push(("image", format!("data:{mime};base64,{content}")));
+            }
+            _ => {}
+        }
+    }
+
+    out
+}
@@ -1,22 +1,132 @@
-use bitcoin::{Amount, Denomination};
-use bitcoin_waila::PaymentParams;
-use clap::{command, Parser};
-use nostr::{
-    key::XOnlyPublicKey,
-    nips::nip19::{self, ToBech32},
+use bitcoin::{Denomination, Network};
+use clap::{
+    builder::PossibleValuesParser, command, ArgGroup, ArgMatches, CommandFactory, FromArgMatches,
+    Parser, Subcommand, ValueEnum,
 };
+use serde::Serialize;
 use serde_json::{json, Map, Value};
-use std::fmt;
-use std::str::FromStr;
+use std::io::{self, BufRead, BufReader, IsTerminal, Write};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing_subscriber::EnvFilter;
+use waila_cli::{
+    amount, bip353, cache, candidates, canonical, compare, compose, convert, decode, deeplink,
+    derive, diff, emit, encode, esplora, fiat, graph, net, secret, Error, Options, Result,
+};
+
+mod clipboard;
+mod config;
+#[cfg(feature = "detectors")]
+mod detectors {
+    //! The detectors of your own, from the file at `WAILA_DETECTORS` when building
+    include!(env!(
+        "WAILA_DETECTORS",
+        "set WAILA_DETECTORS to the file registering your detectors, see detectors/voucher.rs"
+    ));
+}
+#[cfg(feature = "grpc")]
+mod grpc;
+mod history;
+mod i18n;
+mod man;
+mod qr;
+mod render;
+#[cfg(feature = "repl")]
+mod repl;
+mod selftest;
+mod serve;
 
 #[derive(Parser, Debug)]
-#[command(author, version, about, long_about = None)]
+#[command(author, version, about, long_about = None, after_help = config::ENV_HELP)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[arg(
+        short,
+        long,
+        action = clap::ArgAction::Count,
+        global = true,
+        help = "Log detection attempts, network calls and cache hits to stderr, more with -vv and -vvv [default: RUST_LOG]"
+    )]
+    verbose: u8,
+
+    #[arg(
+        long,
+        value_enum,
+        global = true,
+        help = "Language of error messages, warnings and table labels [default: that of LC_ALL, LC_MESSAGES or LANG]"
+    )]
+    lang: Option<i18n::Lang>,
+
+    #[command(flatten)]
+    style: Style,
+
+    /// Parsing a string is the default command
+    #[command(flatten)]
+    args: Args,
+}
+
+/// How JSON output is laid out, by every command that prints it
+#[derive(clap::Args, Debug, Clone, Copy)]
+struct Style {
+    #[arg(
+        short = 'f',
+        long,
+        global = true,
+        help = "Remove extra whitespace in JSON output, the default unless printing to a terminal"
+    )]
+    flatten: bool,
+
+    #[arg(
+        long,
+        global = true,
+        help = "Indent JSON output even when it isn't printed to a terminal",
+        conflicts_with = "flatten"
+    )]
+    pretty: bool,
+
+    #[arg(
+        long,
+        value_enum,
+        value_name = "WHEN",
+        global = true,
+        help = "Color indented JSON output",
+        default_value_t = render::Color::Auto
+    )]
+    color: render::Color,
+
+    #[arg(
+        long,
+        value_enum,
+        global = true,
+        help = "Naming convention of output keys",
+        default_value_t = render::KeyCase::Snake
+    )]
+    key_case: render::KeyCase,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Self {
+            flatten: false,
+            pretty: false,
+            color: render::Color::Auto,
+            key_case: render::KeyCase::Snake,
+        }
+    }
+}
+
+#[derive(clap::Args, Debug)]
+#[command(group(ArgGroup::new("input").multiple(true).args(["query", "file"])))]
 struct Args {
     #[arg(
         short = 'a',
         long,
         help = "Show all results including None type",
-        requires = "query"
+        requires = "input"
     )]
     all: bool,
 
@@ -24,304 +134,1823 @@ struct Args {
         short = 'n',
         long,
         help = "Parse a nostr pubkey in hex and bech32 (experimental)",
-        requires = "query"
+        requires = "input"
     )]
     nostr: bool,
 
-    #[arg(
-        short = 'f',
-        long,
-        help = "Remove extra whitespace in JSON output",
-        requires = "query"
-    )]
-    flatten: bool,
+    /// The layout of the output, given as options of the root command
+    #[arg(skip = Style::default())]
+    style: Style,
 
     #[arg(
         short = 'u',
         long = "units",
-        help = "Bitcoin denomination to display (btc, mbtc, sat, msat)",
+        value_name = "UNIT",
+        help = "Bitcoin denomination to display",
+        value_parser = PossibleValuesParser::new(UNITS),
+        ignore_case = true,
         default_value("sat"),
-        requires = "query"
+        requires = "input"
     )]
     unit: String,
 
-    #[arg(help = "bitcoin string to parse", required(true))]
-    query: String,
+    #[arg(
+        long,
+        help = "Show amounts as JSON numbers in the selected unit, with a separate 'unit' field",
+        requires = "input"
+    )]
+    numeric_amounts: bool,
+
+    #[arg(
+        long,
+        value_name = "CURRENCY",
+        value_delimiter = ',',
+        help = "Convert amounts to fiat currencies, e.g. USD,EUR",
+        requires = "input"
+    )]
+    fiat: Vec<String>,
+
+    #[arg(
+        long,
+        value_delimiter = ',',
+        help = "Price of one bitcoin for each --fiat currency, instead of fetching it",
+        requires = "fiat"
+    )]
+    rate: Vec<f64>,
+
+    #[arg(
+        long,
+        value_enum,
+        help = "Exchange rate API to fetch --fiat rates from",
+        default_value_t = fiat::Source::Coingecko
+    )]
+    rate_source: fiat::Source,
+
+    #[arg(
+        long,
+        value_name = "URL",
+        help = "JSON API to fetch each --fiat rate from instead, {currency} stands for the currency code",
+        requires = "rate_pointer",
+        conflicts_with = "rate"
+    )]
+    rate_url: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "POINTER",
+        help = "JSON pointer to the price in the --rate-url response, e.g. /data/{CURRENCY}",
+        requires = "rate_url"
+    )]
+    rate_pointer: Option<String>,
+
+    #[arg(
+        long,
+        help = "Query LNURL and lightning address endpoints for pay or withdraw details",
+        requires = "input"
+    )]
+    resolve: bool,
+
+    #[arg(
+        long,
+        value_name = "AMOUNT",
+        value_parser = amount_arg,
+        num_args = 0..=1,
+        help = "Request an invoice from an LNURL-pay endpoint, or from an offer through the node backend. The amount, e.g. 1000sat, may be left out for offers that set one"
+    )]
+    request_invoice: Option<Option<u64>>,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "JSON-RPC socket of a Core Lightning node to request offer invoices with",
+        conflicts_with = "cln_rest"
+    )]
+    cln_rpc: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "URL",
+        help = "clnrest API of a Core Lightning node to request offer invoices with",
+        requires = "cln_rune"
+    )]
+    cln_rest: Option<String>,
+
+    #[arg(long, help = "Rune authorizing clnrest requests")]
+    cln_rune: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "ADDR",
+        help = "DNS server for BIP-353 lookups",
+        default_value = bip353::DEFAULT_DNS_SERVER
+    )]
+    dns_server: SocketAddr,
+
+    #[arg(
+        long,
+        value_name = "URL",
+        help = "DNS-over-HTTPS resolver for BIP-353 lookups instead of --dns-server, e.g. https://dns.google/dns-query",
+        conflicts_with = "dns_server"
+    )]
+    doh: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "URL",
+        help = "Send all network traffic through a SOCKS5 proxy, e.g. socks5://127.0.0.1:9050 for Tor"
+    )]
+    proxy: Option<String>,
+
+    #[arg(
+        long,
+        help = "Allow network access, which --resolve, --lookup, --probe, --fiat and --request-invoice need"
+    )]
+    online: bool,
+
+    #[arg(
+        long,
+        help = "Forbid network access, the default",
+        conflicts_with = "online"
+    )]
+    offline: bool,
+
+    #[arg(
+        long,
+        help = "Always query endpoints instead of using recent answers from the cache"
+    )]
+    no_cache: bool,
+
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        help = "How long cached answers of LNURL endpoints, DNS, exchange rates and explorers are used",
+        default_value_t = cache::DEFAULT_TTL
+    )]
+    cache_ttl: u64,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Directory of the cache, defaults to ~/.cache/waila-cli"
+    )]
+    cache_dir: Option<PathBuf>,
+
+    #[arg(
+        long,
+        help = "Look up addresses and transactions on chain, lightning nodes in the graph, and check payjoin endpoints",
+        requires = "input"
+    )]
+    lookup: bool,
+
+    #[arg(
+        long,
+        value_name = "N",
+        num_args = 0..=1,
+        default_missing_value = "10",
+        help = "With --lookup, include the N most recent transactions of an address [default: 10]",
+        requires = "lookup"
+    )]
+    history: Option<usize>,
+
+    #[arg(
+        long,
+        value_name = "URL",
+        help = "Esplora API to use for --lookup, defaults to a public instance for the network"
+    )]
+    esplora: Option<String>,
+
+    #[arg(
+        long = "esplora-url",
+        value_name = "NETWORK=URL",
+        value_parser = esplora::network_url,
+        help = "Esplora API to use for --lookup on one network, e.g. regtest=http://127.0.0.1:3002, may be repeated"
+    )]
+    esplora_urls: Vec<(String, String)>,
+
+    #[arg(
+        long,
+        value_name = "URL",
+        help = "Electrum server to use for --lookup instead of Esplora, tcp://host:port or ssl://host:port",
+        conflicts_with_all = ["esplora", "esplora_urls"]
+    )]
+    electrum: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "URL",
+        help = "bitcoind JSON-RPC server to use for --lookup instead of Esplora, e.g. http://127.0.0.1:8332",
+        conflicts_with_all = ["esplora", "esplora_urls", "electrum"]
+    )]
+    bitcoind: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Cookie file authenticating to bitcoind [default: ~/.bitcoin/.cookie]",
+        requires = "bitcoind"
+    )]
+    bitcoind_cookie: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "USER:PASSWORD",
+        help = "rpcuser and rpcpassword authenticating to bitcoind, instead of the cookie",
+        requires = "bitcoind",
+        conflicts_with = "bitcoind_cookie"
+    )]
+    bitcoind_auth: Option<String>,
+
+    #[arg(
+        long,
+        value_enum,
+        help = "Lightning graph explorer to use for --lookup",
+        default_value_t = graph::Graph::Mempool
+    )]
+    graph: graph::Graph,
+
+    #[arg(
+        long,
+        value_name = "URL",
+        help = "API of the lightning graph explorer, defaults to the public instance"
+    )]
+    graph_url: Option<String>,
+
+    #[arg(
+        long,
+        help = "Try to connect to the host of a node URI, pubkey@host:port",
+        requires = "input"
+    )]
+    probe: bool,
+
+    #[arg(
+        long,
+        help = "With --probe, also verify the node key through the BOLT 8 handshake",
+        requires = "probe"
+    )]
+    handshake: bool,
+
+    #[arg(
+        short = 's',
+        long,
+        value_name = "FIELD",
+        value_delimiter = ',',
+        help = "Print only the values of the given fields, e.g. address,amount, may be repeated",
+        value_parser = PossibleValuesParser::new(FIELDS),
+        requires = "input"
+    )]
+    select: Vec<String>,
+
+    #[arg(
+        long,
+        value_enum,
+        value_name = "SHAPE",
+        help = "How the values of several --select fields are printed",
+        default_value_t = SelectAs::Tsv,
+        requires = "select"
+    )]
+    select_as: SelectAs,
+
+    #[arg(
+        short = 'r',
+        long,
+        help = "Print only the primary value (address, invoice, etc.) without JSON",
+        conflicts_with = "select",
+        requires = "input"
+    )]
+    raw: bool,
+
+    #[arg(
+        long,
+        value_name = "TOOL",
+        help = "Print the node command paying the query instead, placeholders for amounts it leaves out",
+        value_enum,
+        conflicts_with_all = ["select", "raw"],
+        requires = "input"
+    )]
+    emit_command: Option<emit::Tool>,
+
+    #[arg(
+        long,
+        value_name = "SHAPE",
+        help = "Print the query shaped for other tools instead, bdk for BDK wallets",
+        value_enum,
+        conflicts_with_all = ["select", "raw", "emit_command"],
+        requires = "input"
+    )]
+    emit: Option<emit::Emit>,
+
+    #[arg(
+        short = 'q',
+        long,
+        help = "Print nothing, exit status 0 if the query parsed and that of the error otherwise",
+        requires = "input"
+    )]
+    quiet: bool,
+
+    #[arg(
+        short = 'k',
+        long,
+        help = "Fail unless the query is of the given kind",
+        value_parser = PossibleValuesParser::new(KINDS),
+        ignore_case = true,
+        requires = "input"
+    )]
+    kind: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "NETWORK",
+        help = "Fail unless the query is for the given network",
+        value_parser = PossibleValuesParser::new(NETWORKS),
+        ignore_case = true,
+        requires = "input"
+    )]
+    expect_network: Option<String>,
+
+    #[arg(
+        long,
+        help = "Fail with exit status 3 if the invoice or offer has expired",
+        requires = "input"
+    )]
+    reject_expired: bool,
+
+    #[arg(
+        long,
+        value_name = "AMOUNT",
+        value_parser = amount_arg,
+        help = "Fail unless the query has an amount of at least AMOUNT, e.g. 1000sat",
+        requires = "input"
+    )]
+    min_amount: Option<u64>,
+
+    #[arg(
+        long,
+        value_name = "AMOUNT",
+        value_parser = amount_arg,
+        help = "Fail unless the query has an amount of at most AMOUNT, e.g. 0.01btc",
+        requires = "input"
+    )]
+    max_amount: Option<u64>,
+
+    #[arg(
+        long,
+        value_name = "TEXT",
+        help = "Check the description hash of an invoice against the sha256 of TEXT, reporting it in description_matches",
+        requires = "input"
+    )]
+    description: Option<String>,
+
+    #[arg(
+        long,
+        help = "Accept only strings that follow their spec exactly, without lenient readings like node URIs as pubkeys",
+        requires = "input"
+    )]
+    strict: bool,
+
+    #[arg(
+        long,
+        value_name = "BYTES",
+        help = "Reject strings longer than BYTES before parsing them",
+        default_value_t = waila_cli::DEFAULT_MAX_INPUT,
+        requires = "input"
+    )]
+    max_input: usize,
+
+    #[arg(
+        long,
+        value_name = "MS",
+        help = "Reject a string once a detector takes longer than MS milliseconds on it",
+        requires = "input"
+    )]
+    time_budget: Option<u64>,
+
+    #[arg(
+        long,
+        help = "Print private keys, mnemonics and credentials as they are instead of masking them",
+        requires = "input"
+    )]
+    show_secrets: bool,
+
+    #[arg(
+        long,
+        help = "List every valid reading of an ambiguous query with a confidence score",
+        requires = "input"
+    )]
+    candidates: bool,
+
+    #[arg(
+        long,
+        help = "Include the input, the time it was parsed and the waila-cli version in each result, the default with several queries or --file",
+        requires = "input"
+    )]
+    metadata: bool,
+
+    #[arg(
+        long,
+        help = "Leave the input, parse time and version out of the results of several queries",
+        conflicts_with = "metadata",
+        requires = "input"
+    )]
+    no_metadata: bool,
+
+    #[arg(
+        long,
+        value_enum,
+        help = "Output format",
+        default_value_t = Format::Json,
+        requires = "input"
+    )]
+    format: Format,
+
+    #[arg(
+        long,
+        help = "Group the digits of amounts by the separators of the locale, e.g. 1,234,567 sat, in html, markdown and --select output",
+        requires = "input"
+    )]
+    group_digits: bool,
+
+    #[arg(
+        short = 'F',
+        long,
+        value_name = "PATH",
+        help = "Read bitcoin strings from a file, one per line ('-' for stdin)"
+    )]
+    file: Option<String>,
+
+    #[arg(
+        short = 'j',
+        long,
+        value_name = "N",
+        help = "Parse up to N strings at once, defaults to the number of CPUs",
+        value_parser = clap::value_parser!(u16).range(1..)
+    )]
+    jobs: Option<u16>,
+
+    #[arg(
+        long,
+        help = "Write the output of each string as soon as it is parsed, for following a pipe like tail -f"
+    )]
+    stream: bool,
+
+    #[arg(
+        long,
+        help = "Print a QR code of the query after the output",
+        requires = "input"
+    )]
+    qr_out: bool,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Write a QR code of the query to a .png or .svg file",
+        requires = "input"
+    )]
+    qr_file: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "FIELD",
+        help = "Encode the value of the given field in the QR code instead of the query",
+        value_parser = PossibleValuesParser::new(FIELDS),
+        requires = "input"
+    )]
+    qr_field: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "PIXELS",
+        help = "Minimum width of the QR code image",
+        default_value_t = 256,
+        requires = "qr_file"
+    )]
+    qr_size: u32,
+
+    #[arg(
+        long,
+        value_enum,
+        help = "Error correction level of the QR code image",
+        default_value_t = qr::ErrorCorrection::Medium,
+        requires = "qr_file"
+    )]
+    qr_ec: qr::ErrorCorrection,
+
+    #[arg(
+        long,
+        value_name = "FIELD",
+        num_args = 0..=1,
+        help = "Copy the value of the given field to the clipboard, or the canonical encoding of the query",
+        value_parser = PossibleValuesParser::new(FIELDS),
+        requires = "input"
+    )]
+    copy: Option<Option<String>>,
+
+    #[arg(
+        short = 'o',
+        long,
+        value_name = "PATH",
+        help = "Write results to a file instead of stdout"
+    )]
+    output: Option<PathBuf>,
+
+    #[arg(
+        long,
+        help = "Append to the --output file instead of overwriting it",
+        requires = "output"
+    )]
+    append: bool,
+
+    #[arg(
+        long,
+        value_name = "KIND=CODE",
+        value_parser = exit_kind_arg,
+        help = "Exit with status CODE, from 8 to 125, if a query is of KIND and none failed, e.g. invoice=10, may be repeated",
+        requires = "input"
+    )]
+    exit_kind: Vec<(String, i32)>,
+
+    #[arg(
+        long,
+        help = "Print the JSON Schema of the output and exit",
+        exclusive = true
+    )]
+    schema: bool,
+
+    #[arg(
+        help = "bitcoin string(s) to parse",
+        required_unless_present_any = ["file", "schema"]
+    )]
+    query: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Read defaults from PATH instead of ~/.config/waila/config.toml"
+    )]
+    config: Option<PathBuf>,
+
+    #[arg(long, help = "Ignore the config file", conflicts_with = "config")]
+    no_config: bool,
+
+    #[arg(
+        long,
+        value_name = "NAME",
+        help = "Apply the [profile.NAME] section of the config file over its other keys",
+        conflicts_with = "no_config"
+    )]
+    profile: Option<String>,
+
+    #[arg(
+        long,
+        help = "Log the time, input and kind of each string to ~/.local/state/waila/queries.jsonl, for waila-cli history"
+    )]
+    record_history: bool,
+
+    #[arg(long, help = "Log the sha256 of each input instead of the input")]
+    hash_inputs: bool,
+
+    /// Print only the QR code of the query, set by the qr command
+    #[arg(skip)]
+    qr_only: bool,
+
+    /// Whether output goes to a terminal, for output meant for reading there
+    #[arg(skip)]
+    terminal: bool,
 }
 
-macro_rules! bail {
-    ($err:expr) => {
-        println!($err);
-        std::process::exit(1);
-    };
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Parse bitcoin strings, the same as giving them without a command
+    Parse(Args),
+    /// Parse bitcoin strings and resolve LNURLs and BIP-353 names, the same as --resolve
+    Resolve(Args),
+    /// Print a QR code of bitcoin strings
+    Qr(Args),
+    /// Convert keys between their encodings
+    #[command(subcommand)]
+    Convert(Convert),
+    /// Encode strings from what they hold
+    #[command(subcommand)]
+    Encode(Encode),
+    /// Decode strings of kinds waila doesn't know into their parts
+    #[command(subcommand)]
+    Decode(Decode),
+    /// List the first addresses of an extended public key or a descriptor, to check
+    /// them against those a wallet shows
+    #[command(group(ArgGroup::new("source").required(true).args(["xpub", "descriptor"])))]
+    Derive {
+        /// The xpub, tpub, or a ypub or zpub standing for its script type
+        #[arg(long)]
+        xpub: Option<String>,
+        /// Path from the key to the addresses, `*` standing for the index
+        #[arg(long, default_value = "0/*", requires = "xpub")]
+        path: String,
+        /// Number of addresses
+        #[arg(long, default_value_t = 20, requires = "xpub")]
+        count: u32,
+        /// Script type of the addresses, may be repeated [default: all, or that of a ypub or zpub]
+        #[arg(long = "type", value_enum, value_name = "TYPE", requires = "xpub")]
+        types: Vec<derive::ScriptType>,
+        /// An output descriptor, e.g. wsh(sortedmulti(2,xpub.../0/*,xpub.../0/*))
+        #[arg(long)]
+        descriptor: Option<String>,
+        /// Indexes of the descriptor addresses, e.g. 0-9 or 5
+        #[arg(long, value_name = "FROM-TO", value_parser = range_arg, default_value = "0-9", requires = "descriptor")]
+        range: (u32, u32),
+        /// Network of the descriptor addresses [default: that of its keys]
+        #[arg(long, value_parser = PossibleValuesParser::new(NETWORKS), ignore_case = true, requires = "descriptor")]
+        network: Option<String>,
+    },
+    /// Answer POST /parse and POST /resolve requests over HTTP with the same JSON
+    Serve {
+        /// Address to listen on
+        #[arg(long, value_name = "ADDR", default_value = "127.0.0.1:8080")]
+        listen: SocketAddr,
+        /// Require `Authorization: Bearer <TOKEN>` on every request
+        #[arg(long)]
+        token: Option<String>,
+        /// Allow network access, which /resolve needs
+        #[arg(long)]
+        online: bool,
+        /// Time each detector may take on a query, in milliseconds
+        #[arg(long, value_name = "MS", default_value_t = 100)]
+        time_budget: u64,
+    },
+    /// Answer Parse, Resolve and ParseStream calls of the gRPC service in proto/waila.proto
+    #[cfg(feature = "grpc")]
+    Grpc {
+        /// Address to listen on
+        #[arg(long, value_name = "ADDR", default_value = "127.0.0.1:50051")]
+        listen: SocketAddr,
+        /// Require `authorization: Bearer <TOKEN>` metadata on every call
+        #[arg(long)]
+        token: Option<String>,
+        /// Allow network access, which Resolve needs
+        #[arg(long)]
+        online: bool,
+        /// Time each detector may take on a query, in milliseconds
+        #[arg(long, value_name = "MS", default_value_t = 100)]
+        time_budget: u64,
+    },
+    /// Convert an amount between units exactly, and to fiat currencies
+    Amount {
+        /// The amount, e.g. 0.015btc, 1500sat or 2000msat, satoshis without a unit
+        #[arg(value_parser = amount_arg)]
+        amount: u64,
+        /// Units to convert to
+        #[arg(
+            long,
+            value_name = "UNITS",
+            value_delimiter = ',',
+            value_parser = PossibleValuesParser::new(UNITS),
+            ignore_case = true,
+            default_value = "btc,mbtc,sat,msat"
+        )]
+        to: Vec<String>,
+        /// Fiat currencies to convert to, e.g. USD,EUR
+        #[arg(long, value_name = "CURRENCY", value_delimiter = ',')]
+        fiat: Vec<String>,
+        /// Price of one bitcoin for each --fiat currency, instead of fetching it
+        #[arg(long, value_delimiter = ',', requires = "fiat")]
+        rate: Vec<f64>,
+        /// Exchange rate API to fetch --fiat rates from
+        #[arg(long, value_enum, default_value_t = fiat::Source::Coingecko)]
+        rate_source: fiat::Source,
+        /// Allow network access, which fetching rates needs
+        #[arg(long)]
+        online: bool,
+    },
+    /// Compare two addresses to catch a lookalike planted by address poisoning
+    Compare {
+        /// The address you mean to pay
+        first: String,
+        /// The address to check against it
+        second: String,
+    },
+    /// A link opening a payment string in one mobile wallet, for sending to its user
+    Deeplink {
+        input: String,
+        /// The wallet to open it in
+        #[arg(long, value_enum)]
+        wallet: deeplink::Wallet,
+        /// Also print a QR code of the link
+        #[arg(long)]
+        qr: bool,
+    },
+    /// Report how two payment strings differ, to tell whether one is the invoice or URI sent earlier
+    Diff { first: String, second: String },
+    /// Print the canonical encoding of a string, for storing it or making a QR code of it
+    Canonicalize {
+        input: String,
+        /// Also print a QR code of the canonical string
+        #[arg(long)]
+        qr: bool,
+    },
+    /// Compose a BIP-21 URI from an address, amount and lightning invoice or offer,
+    /// checking that they fit together
+    Compose {
+        /// On-chain address
+        #[arg(long, required_unless_present_any = ["lightning", "lno"])]
+        address: Option<String>,
+        /// Amount, e.g. 1000sat or 0.001btc, taken from the invoice if left out
+        #[arg(long, value_parser = amount_arg)]
+        amount: Option<u64>,
+        /// Label of the receiver
+        #[arg(long)]
+        label: Option<String>,
+        /// Message describing the payment
+        #[arg(long)]
+        message: Option<String>,
+        /// BOLT-11 invoice for the same network and amount
+        #[arg(long, value_name = "INVOICE")]
+        lightning: Option<String>,
+        /// BOLT-12 offer
+        #[arg(long, value_name = "OFFER")]
+        lno: Option<String>,
+        /// Payjoin endpoint, which needs an address
+        #[arg(long, value_name = "URL", requires = "address")]
+        pj: Option<String>,
+        /// Also print a QR code of the URI
+        #[arg(long)]
+        qr: bool,
+    },
+    /// Print a completion script for the shell, with the values of --select,
+    /// --format and other options
+    Completions {
+        #[arg(value_enum)]
+        shell: clap_complete::Shell,
+    },
+    /// Print the man page, with an example of each kind
+    Man,
+    /// Parse strings pasted at a prompt, with commands like `:format markdown` and
+    /// `:resolve on` changing the options of the following ones
+    #[cfg(feature = "repl")]
+    Repl {
+        /// Allow network access, which can also be turned on with `:online on`
+        #[arg(long)]
+        online: bool,
+    },
+    /// Parse a bundled string of each kind and network and check the results, exit
+    /// status 1 if any is wrong
+    Selftest,
+    /// Search the log of --record-history, printing the matching entries
+    History {
+        /// Text of the inputs to find, or a whole input to find it hashed
+        pattern: Option<String>,
+        /// Only entries of this kind
+        #[arg(long, value_parser = PossibleValuesParser::new(KINDS), ignore_case = true)]
+        kind: Option<String>,
+        /// Print only the last N entries found
+        #[arg(long, value_name = "N", default_value_t = 50)]
+        limit: usize,
+    },
 }
 
-#[derive(Debug)]
-enum Error {
-    Serialize(serde_json::Error),
-    Bech32(nip19::Error),
+#[derive(Subcommand, Debug)]
+enum Convert {
+    /// A nostr key, hex, npub, nsec or nprofile, in hex and every NIP-19 encoding
+    Nostr {
+        /// The key to convert
+        key: String,
+        /// Read a hex key as a secret key rather than a public key
+        #[arg(long)]
+        secret: bool,
+        /// Relay to hint at in the nprofile, may be repeated
+        #[arg(long = "relay", value_name = "URL")]
+        relays: Vec<String>,
+        /// Print a secret key as it is instead of masking it
+        #[arg(long)]
+        show_secrets: bool,
+    },
+    /// The scriptPubKey of an address, its hash160 or witness program, and the
+    /// Electrum script hash
+    Script {
+        /// The address to convert
+        address: String,
+    },
 }
 
-impl From<serde_json::Error> for Error {
-    fn from(e: serde_json::Error) -> Self {
-        Error::Serialize(e)
-    }
+#[derive(Subcommand, Debug)]
+enum Encode {
+    /// The LNURL of an https url, in lowercase and in the uppercase of QR codes
+    Lnurl {
+        /// The url of the pay, withdraw or other service
+        url: String,
+        /// Also print a QR code of the uppercase LNURL
+        #[arg(long)]
+        qr: bool,
+    },
 }
 
-impl From<nip19::Error> for Error {
-    fn from(e: nip19::Error) -> Self {
-        Error::Bech32(e)
-    }
+#[derive(Subcommand, Debug)]
+enum Decode {
+    /// The human-readable part, variant and payload of any bech32 or bech32m string
+    Bech32 {
+        /// The string to decode
+        input: String,
+    },
+    /// The version byte, payload and checksum validity of any Base58Check string
+    Base58 {
+        /// The string to decode
+        input: String,
+    },
 }
 
-impl fmt::Display for Error {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            Error::Serialize(e) => write!(f, "{e}"),
-            Error::Bech32(e) => write!(f, "{e}"),
-        }
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum Format {
+    /// A JSON object per input
+    Json,
+    /// One compact JSON object per line, failures included
+    Ndjson,
+    /// An HTML definition list
+    Html,
+    /// A Markdown table
+    Markdown,
+    /// A CBOR item per input
+    Cbor,
+    /// A MessagePack map per input
+    Msgpack,
+}
+
+/// Shapes of the output of several selected fields
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum SelectAs {
+    /// The values on one line, separated by tabs
+    Tsv,
+    /// An object of the selected fields alone, in the output format
+    Object,
+}
+
+/// JSON Schema describing the output object
+const SCHEMA: &str = include_str!("../schema/output.schema.json");
+
+/// Keys that may appear in the output
+const FIELDS: [&str; 49] = [
+    "kind",
+    "bip353",
+    "network",
+    "address",
+    "invoice",
+    "pubkey",
+    "amount",
+    "unit",
+    "amounts",
+    "fiat",
+    "memo",
+    "description_hash",
+    "description_matches",
+    "lnurl",
+    "lnaddr",
+    "payjoin",
+    "payjoin_status",
+    "resolved",
+    "requested_invoice",
+    "chain",
+    "node",
+    "probe",
+    "nostr",
+    "txid",
+    "version",
+    "locktime",
+    "size",
+    "vsize",
+    "weight",
+    "inputs",
+    "outputs",
+    "output_value",
+    "verified",
+    "expires_at",
+    "expired",
+    "time_remaining",
+    "warnings",
+    "descriptor",
+    "checksum",
+    "checksum_given",
+    "candidates",
+    "secret",
+    "secret_value",
+    "xpub",
+    "fingerprint",
+    "redacted",
+    "input",
+    "parsed_at",
+    "waila_version",
+];
+
+/// Strings parsed before their output is written, keeping memory bounded on long files
+const BATCH: usize = 1024;
+
+/// Fields printed by `--raw`, the one of a kind given by `primary_field`
+const PRIMARY_FIELDS: [&str; 7] = [
+    "address",
+    "invoice",
+    "pubkey",
+    "lnurl",
+    "lnaddr",
+    "txid",
+    "descriptor",
+];
+
+/// Denominations that may be given to `--units`, ubtc and bits being the same
+const UNITS: [&str; 6] = ["btc", "mbtc", "ubtc", "bits", "sat", "msat"];
+
+/// Networks that may be given to `--expect-network`
+const NETWORKS: [&str; 4] = ["mainnet", "testnet", "signet", "regtest"];
+
+/// Kinds that may be given to `--kind` and `--exit-kind`
+const KINDS: [&str; 12] = [
+    "onchain",
+    "unifieduri",
+    "invoice",
+    "offer",
+    "publickey",
+    "lnurl",
+    "lnaddress",
+    "nostrvalue",
+    "txid",
+    "transaction",
+    "descriptor",
+    "secret",
+];
+
+fn main() {
+    #[cfg(feature = "detectors")]
+    detectors::register();
+    let matches = Cli::command().get_matches();
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    init_logging(cli.verbose);
+    i18n::set(cli.lang.unwrap_or_else(i18n::Lang::from_env));
+    if let Err(e) = dispatch(cli, &matches) {
+        eprintln!("{}", error_json(&e));
+        std::process::exit(e.exit_code());
     }
 }
 
-impl std::error::Error for Error {}
+/// `{"error": {"code": ..., "message": ...}}`, with the suggestions of a near miss or
+/// the chain of an address of another one
+fn error_json(e: &Error) -> Value {
+    let mut error = json!({ "code": e.code(), "message": i18n::error(e) });
+    let suggestions = e.suggestions();
+    if !suggestions.is_empty() {
+        error["suggestions"] = json!(suggestions);
+    }
+    if let Error::ForeignChain(chain) = e {
+        error["chain"] = json!(chain);
+    }
 
-type Result<T> = core::result::Result<T, Error>;
+    json!({ "error": error })
+}
 
-fn main() -> Result<()> {
-    let args = Args::parse();
-    let s = args.query;
-    let unit = match args.unit.as_str() {
-        "btc" => Denomination::Bitcoin,
-        "mbtc" => Denomination::MilliBitcoin,
-        "msat" => Denomination::MilliSatoshi,
-        _ => Denomination::Satoshi,
+/// Log to stderr at the level of `verbose`, or as set by `RUST_LOG` without it
+fn init_logging(verbose: u8) {
+    let level = match verbose {
+        0 => None,
+        1 => Some("info"),
+        2 => Some("debug"),
+        _ => Some("trace"),
     };
-
-    let Ok(payment_params) = PaymentParams::from_str(&s) else {
-        bail!("not a bitcoin string");
+    // the libraries below are only heard from when they warn
+    let filter = match level {
+        Some(level) => EnvFilter::new(format!("warn,waila_cli={level}")),
+        None => EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("warn")),
     };
+    tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(io::stderr)
+        .init();
+}
 
-    /* Build a `serde_json::Map` with the following keys. All fields, if applicable, are of type String,
-    or `Map<String, String>` in the case of 'nostr'.
-        kind
-        network
-        address
-        invoice
-        pubkey
-        amount
-        memo
-        lnurl
-        lnaddr
-        payjoin
-        nostr
-    */
-    let mut map = Map::new();
-
-    // Any additional `PaymentParams` variants must be included here
-    let kind = match payment_params {
-        PaymentParams::OnChain(_) => "OnChain",
-        PaymentParams::Bip21(_) => "UnifiedUri",
-        PaymentParams::Bolt11(_) => "Invoice",
-        PaymentParams::Bolt12(_) => "Offer",
-        PaymentParams::NodePubkey(_) => "PublicKey",
-        PaymentParams::LnUrl(_) => "LnUrl",
-        PaymentParams::LightningAddress(_) => "LnAddress",
-        PaymentParams::Nostr(_) => "NostrValue",
+/// Run the command given, parsing strings if there is none
+fn dispatch(cli: Cli, matches: &ArgMatches) -> Result<()> {
+    let mut args = match cli.command {
+        None => cli.args,
+        Some(Command::Parse(args)) => args,
+        Some(Command::Resolve(mut args)) => {
+            args.resolve = true;
+            args
+        }
+        Some(Command::Qr(mut args)) => {
+            args.qr_only = true;
+            args.qr_out = args.qr_file.is_none();
+            args
+        }
+        Some(Command::Convert(Convert::Nostr {
+            key,
+            secret,
+            relays,
+            show_secrets,
+        })) => {
+            // the relays of the config file unless some are given
+            let relays = if relays.is_empty() {
+                config::load(None, None)?.relays()
+            } else {
+                relays
+            };
+            let key = convert::nostr(&key, secret, &relays, show_secrets)?;
+            print_json(&key, cli.style)?;
+            return Ok(());
+        }
+        Some(Command::Convert(Convert::Script { address })) => {
+            let script = convert::script(&address)?;
+            print_json(&script, cli.style)?;
+            return Ok(());
+        }
+        Some(Command::Derive {
+            xpub,
+            path,
+            count,
+            types,
+            descriptor,
+            range: (from, to),
+            network,
+        }) => {
+            let addresses = match (xpub, descriptor) {
+                (Some(xpub), _) => derive::addresses(&xpub, &path, count, &types)?,
+                (None, Some(descriptor)) => {
+                    let network = network.map(|network| match network.to_lowercase().as_str() {
+                        "mainnet" => Network::Bitcoin,
+                        "testnet" => Network::Testnet,
+                        "signet" => Network::Signet,
+                        _ => Network::Regtest,
+                    });
+                    derive::descriptor_addresses(&descriptor, from..=to, network)?
+                }
+                (None, None) => unreachable!("clap requires an xpub or a descriptor"),
+            };
+            print_json(&addresses, cli.style)?;
+            return Ok(());
+        }
+        Some(Command::Encode(Encode::Lnurl { url, qr: print_qr })) => {
+            let lnurl = encode::lnurl(&url)?;
+            print_json(&lnurl, cli.style)?;
+            if print_qr {
+                println!("{}", qr::render_terminal(&lnurl.uppercase)?);
+            }
+            return Ok(());
+        }
+        Some(Command::Decode(Decode::Bech32 { input })) => {
+            let decoded = decode::bech32(&input)?;
+            print_json(&decoded, cli.style)?;
+            return Ok(());
+        }
+        Some(Command::Decode(Decode::Base58 { input })) => {
+            let decoded = decode::base58(&input)?;
+            print_json(&decoded, cli.style)?;
+            return Ok(());
+        }
+        Some(Command::Serve {
+            listen,
+            token,
+            online,
+            time_budget,
+        }) => {
+            net::set_online(online);
+            cache::enable(None, Duration::from_secs(cache::DEFAULT_TTL));
+            let opts = Options {
+                time_budget: Some(Duration::from_millis(time_budget)),
+                ..Options::default()
+            };
+            return serve::serve(listen, token, opts);
+        }
+        #[cfg(feature = "grpc")]
+        Some(Command::Grpc {
+            listen,
+            token,
+            online,
+            time_budget,
+        }) => {
+            net::set_online(online);
+            cache::enable(None, Duration::from_secs(cache::DEFAULT_TTL));
+            let opts = Options {
+                time_budget: Some(Duration::from_millis(time_budget)),
+                ..Options::default()
+            };
+            return grpc::serve(listen, token, opts);
+        }
+        Some(Command::Amount {
+            amount: msat,
+            to,
+            fiat,
+            rate,
+            rate_source,
+            online,
+        }) => {
+            let mut converted = amount::convert(msat, &to)?;
+            if !fiat.is_empty() {
+                net::set_online(online);
+                let rates = if rate.is_empty() {
+                    fiat::Rates::fetch(&fiat, &fiat::Provider::Api(rate_source))?
+                } else {
+                    fiat::Rates::from_user(&fiat, &rate)?
+                };
+                converted["fiat"] = rates.convert(amount::msat_to_amount(msat));
+            }
+            print_json(&converted, cli.style)?;
+            return Ok(());
+        }
+        Some(Command::Compare { first, second }) => {
+            let report = compare::compare(&first, &second)?;
+            print_json(&report, cli.style)?;
+            return Ok(());
+        }
+        Some(Command::Deeplink {
+            input,
+            wallet,
+            qr: print_qr,
+        }) => {
+            let link = deeplink::deeplink(&input, wallet)?;
+            print_json(&link, cli.style)?;
+            if print_qr {
+                println!("{}", qr::render_terminal(&link.link)?);
+            }
+            return Ok(());
+        }
+        Some(Command::Diff { first, second }) => {
+            let report = diff::diff(&first, &second)?;
+            print_json(&report, cli.style)?;
+            return Ok(());
+        }
+        Some(Command::Canonicalize {
+            input,
+            qr: print_qr,
+        }) => {
+            let canonical = canonical::canonicalize(&input)?;
+            println!("{canonical}");
+            if print_qr {
+                println!("{}", qr::render_terminal(&canonical::for_qr(&input)?)?);
+            }
+            return Ok(());
+        }
+        Some(Command::Compose {
+            address,
+            amount,
+            label,
+            message,
+            lightning,
+            lno,
+            pj,
+            qr: print_qr,
+        }) => {
+            let uri = compose::compose(&compose::Parts {
+                address,
+                amount,
+                label,
+                message,
+                lightning,
+                offer: lno,
+                payjoin: pj,
+            })?;
+            println!("{uri}");
+            if print_qr {
+                println!("{}", qr::render_terminal(&uri)?);
+            }
+            return Ok(());
+        }
+        Some(Command::Completions { shell }) => {
+            let mut cmd = Cli::command();
+            let name = cmd.get_name().to_string();
+            clap_complete::generate(shell, &mut cmd, name, &mut io::stdout());
+            return Ok(());
+        }
+        Some(Command::Man) => {
+            man::render(Cli::command(), &mut io::stdout())?;
+            return Ok(());
+        }
+        #[cfg(feature = "repl")]
+        Some(Command::Repl { online }) => {
+            // the options accept no flags without an input, a placeholder that is dropped
+            let mut argv = vec!["waila-cli", "-"];
+            if online {
+                argv.push("--online");
+            }
+            let matches = Cli::command().get_matches_from(argv);
+            let mut args = Cli::from_arg_matches(&matches)
+                .unwrap_or_else(|e| e.exit())
+                .args;
+            args.query.clear();
+            args.style = cli.style;
+            config::load(None, None)?.apply(&mut args, &matches)?;
+            return repl::run(args);
+        }
+        Some(Command::History {
+            pattern,
+            kind,
+            limit,
+        }) => {
+            for entry in history::search(pattern.as_deref(), kind.as_deref(), limit)? {
+                println!("{entry}");
+            }
+            return Ok(());
+        }
+        Some(Command::Selftest) => {
+            if !selftest::run()? {
+                std::process::exit(1);
+            }
+            return Ok(());
+        }
     };
-    if kind == "NostrValue" && !args.nostr {
-        // don't expose nostr results unsolicited
-        bail!("not a bitcoin string");
-    }
-    map.insert("kind".to_string(), Value::String(kind.to_string()));
 
-    if args.all {
-        map = build(&payment_params, map, unit);
+    args.style = cli.style;
+    // the options are those of the parse, resolve or qr command, if one was given
+    let matches = matches.subcommand().map_or(matches, |(_, matches)| matches);
+    let config = if args.no_config {
+        config::from_env()?
     } else {
-        map = build_sparse(&payment_params, map, unit);
+        config::load(args.config.as_deref(), args.profile.as_deref())?
     };
+    config.apply(&mut args, matches)?;
+
+    run(args)
+}
 
-    if args.nostr {
-        map.insert("nostr".to_string(), parse_nostr(&payment_params)?);
+fn run(mut args: Args) -> Result<()> {
+    if args.schema {
+        print!("{SCHEMA}");
+        return Ok(());
     }
 
-    let json_out = if args.flatten {
-        serde_json::to_string(&map)?
-    } else {
-        serde_json::to_string_pretty(&map)?
-    };
+    net::set_online(args.online && !args.offline);
+    if let Some(proxy) = &args.proxy {
+        net::set_proxy(proxy)?;
+    }
+    if !args.no_cache {
+        cache::enable(args.cache_dir.clone(), Duration::from_secs(args.cache_ttl));
+    }
 
-    println!("{json_out}");
+    // lines of --file are read as they are parsed, so memory stays bounded on huge files
+    let lines = args.file.as_deref().map(read_lines).transpose()?;
+    let mut inputs = args
+        .query
+        .iter()
+        .cloned()
+        .map(Ok)
+        .chain(lines.into_iter().flatten());
 
-    Ok(())
-}
+    let opts = options(&args, denomination(&args.unit), rates(&args)?);
 
-/// Construct a json map with all keys
-fn build(
-    payment_params: &PaymentParams,
-    mut map: Map<String, Value>,
-    unit: Denomination,
-) -> Map<String, Value> {
-    map.insert(
-        "network".to_string(),
-        if let Some(net) = payment_params.network() {
-            Value::String(net.to_string())
-        } else {
-            json!(null)
-        },
-    );
+    args.terminal = args.output.is_none() && io::stdout().is_terminal();
+    // results of a batch say what they are results of, as they are stored or sent on
+    if args.file.is_some() || args.query.len() > 1 {
+        args.metadata = !args.no_metadata;
+    }
+    let writer: Box<dyn Write> = match &args.output {
+        Some(path) => Box::new(
+            std::fs::OpenOptions::new()
+                .create(true)
+                .write(true)
+                .append(args.append)
+                .truncate(!args.append)
+                .open(path)?,
+        ),
+        None => Box::new(io::stdout().lock()),
+    };
+    // output is flushed after each batch
+    let mut writer = io::BufWriter::new(writer);
 
-    map.insert(
-        "address".to_string(),
-        if let Some(addr) = payment_params.address() {
-            Value::String(addr.to_string())
-        } else {
-            json!(null)
-        },
-    );
+    // the clipboard is left holding the value of the last input
+    let jobs = match args.jobs {
+        _ if args.copy.is_some() => 1,
+        Some(jobs) => usize::from(jobs),
+        None => std::thread::available_parallelism().map_or(1, usize::from),
+    };
 
-    map.insert(
-        "invoice".to_string(),
-        if let Some(inv) = payment_params.invoice() {
-            Value::String(inv.to_string())
-        } else {
-            json!(null)
-        },
-    );
+    let batch_size = if args.stream { 1 } else { BATCH };
 
-    map.insert(
-        "pubkey".to_string(),
-        if let Some(pk) = payment_params.node_pubkey() {
-            Value::String(pk.to_string())
-        } else {
-            json!(null)
-        },
-    );
-
-    map.insert(
-        "amount".to_string(),
-        if let Some(amt) = payment_params.amount() {
-            // convert to the correct type for our imports
-            let sat = amt.to_sat();
-            let amt = Amount::from_sat(sat);
-            Value::String(amt.to_string_with_denomination(unit))
-        } else {
-            json!(null)
-        },
-    );
+    // the exit status is that of the first failed input, or else of the first input of a
+    // kind given to --exit-kind
+    let mut exit_code = 0;
+    let mut kind_code = 0;
+    loop {
+        let batch = inputs
+            .by_ref()
+            .take(batch_size)
+            .collect::<Result<Vec<String>>>()?;
+        if batch.is_empty() {
+            break;
+        }
+        for (s, result) in batch.iter().zip(process_batch(&batch, &args, &opts, jobs)) {
+            match result {
+                Ok(processed) => {
+                    if kind_code == 0 {
+                        kind_code = exit_kind(processed.kind, &args.exit_kind);
+                    }
+                    if let Some(out) = processed.out {
+                        writer.write_all(&out)?;
+                    }
+                    if let Some(data) = processed.copy {
+                        writer.flush()?;
+                        copy_to_clipboard(&data);
+                    }
+                }
+                Err(e) if e.is_input_error() => {
+                    if exit_code == 0 {
+                        exit_code = e.exit_code();
+                    }
+                    if args.quiet {
+                        continue;
+                    }
+                    if args.format == Format::Ndjson {
+                        let mut record = json!({
+                            "input": masked_input(s, &args),
+                            "error": i18n::error(&e),
+                            "code": e.code(),
+                        });
+                        let suggestions = e.suggestions();
+                        if !suggestions.is_empty() {
+                            record["suggestions"] = serde_json::to_value(suggestions)?;
+                        }
+                        if let Error::ForeignChain(chain) = &e {
+                            record["chain"] = json!(chain);
+                        }
+                        writeln!(writer, "{record}")?;
+                    } else {
+                        eprintln!("{}", error_json(&e));
+                    }
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        writer.flush()?;
+    }
 
-    map.insert(
-        "memo".to_string(),
-        if let Some(m) = payment_params.memo() {
-            Value::String(m)
-        } else {
-            json!(null)
-        },
-    );
+    if exit_code == 0 {
+        exit_code = kind_code;
+    }
+    if exit_code != 0 {
+        std::process::exit(exit_code);
+    }
 
-    map.insert(
-        "lnurl".to_string(),
-        if let Some(lnurl) = payment_params.lnurl() {
-            Value::String(lnurl.to_string())
-        } else {
-            json!(null)
-        },
-    );
+    Ok(())
+}
 
-    map.insert(
-        "lnaddr".to_string(),
-        if let Some(lnaddr) = payment_params.lightning_address() {
-            Value::String(lnaddr.to_string())
-        } else {
-            json!(null)
-        },
-    );
+/// The denomination named by `--units`, one of [`UNITS`]
+fn denomination(unit: &str) -> Denomination {
+    match unit.to_ascii_lowercase().as_str() {
+        "btc" => Denomination::Bitcoin,
+        "mbtc" => Denomination::MilliBitcoin,
+        "ubtc" => Denomination::MicroBitcoin,
+        "bits" => Denomination::Bit,
+        "msat" => Denomination::MilliSatoshi,
+        "sat" => Denomination::Satoshi,
+        unit => unreachable!("unit {unit} is not one of UNITS"),
+    }
+}
 
-    map.insert(
-        "payjoin".to_string(),
-        if let Some(url) = payment_params.payjoin_endpoint() {
-            Value::String(url.to_string())
-        } else {
-            json!(null)
+/// The rates of the `--fiat` currencies, given with `--rate` or fetched
+fn rates(args: &Args) -> Result<Option<fiat::Rates>> {
+    if args.fiat.is_empty() {
+        return Ok(None);
+    }
+    if !args.rate.is_empty() {
+        return fiat::Rates::from_user(&args.fiat, &args.rate).map(Some);
+    }
+    let provider = match (&args.rate_url, &args.rate_pointer) {
+        (Some(url), Some(pointer)) => fiat::Provider::Custom {
+            url: url.clone(),
+            pointer: pointer.clone(),
         },
-    );
+        _ => fiat::Provider::Api(args.rate_source),
+    };
 
-    map
+    fiat::Rates::fetch(&args.fiat, &provider).map(Some)
 }
 
-/// Construct a json map with non-null fields only
-fn build_sparse(
-    payment_params: &PaymentParams,
-    mut map: Map<String, Value>,
-    unit: Denomination,
-) -> Map<String, Value> {
-    if let Some(net) = payment_params.network() {
-        map.insert("network".to_string(), Value::String(net.to_string()));
+/// The analysis options given by `args`
+fn options(args: &Args, unit: Denomination, rates: Option<fiat::Rates>) -> Options {
+    Options {
+        all: args.all,
+        nostr: args.nostr,
+        unit,
+        numeric_amounts: args.numeric_amounts,
+        rates,
+        resolve: args.resolve,
+        request_invoice: args.request_invoice,
+        cln_rpc: args.cln_rpc.clone(),
+        cln_rest: args.cln_rest.clone(),
+        cln_rune: args.cln_rune.clone(),
+        dns_server: args.dns_server,
+        doh: args.doh.clone(),
+        lookup: args.lookup,
+        history: args.history,
+        esplora: args.esplora.clone(),
+        esplora_urls: args.esplora_urls.clone(),
+        electrum: args.electrum.clone(),
+        bitcoind: args.bitcoind.clone(),
+        bitcoind_cookie: args.bitcoind_cookie.clone(),
+        bitcoind_auth: args.bitcoind_auth.clone(),
+        graph: args.graph,
+        graph_url: args.graph_url.clone(),
+        probe: args.probe,
+        handshake: args.handshake,
+        kind: args.kind.clone(),
+        expect_network: args.expect_network.clone(),
+        reject_expired: args.reject_expired,
+        min_amount: args.min_amount,
+        max_amount: args.max_amount,
+        description: args.description.clone(),
+        strict: args.strict,
+        show_secrets: args.show_secrets,
+        // quiet output only needs the kind checked
+        kind_only: args.quiet,
+        fields: needed_fields(args),
+        max_input: args.max_input,
+        time_budget: args.time_budget.map(Duration::from_millis),
     }
+}
 
-    if let Some(addr) = payment_params.address() {
-        map.insert("address".to_string(), Value::String(addr.to_string()));
+/// The fields the output of `args` needs, all if empty
+fn needed_fields(args: &Args) -> Vec<String> {
+    if args.emit.is_some() || args.emit_command.is_some() {
+        return vec![];
     }
-
-    if let Some(inv) = payment_params.invoice() {
-        map.insert("invoice".to_string(), Value::String(inv.to_string()));
+    let mut fields = match (args.select.is_empty(), args.raw) {
+        (false, _) => args.select.clone(),
+        // the primary field is known once the kind is
+        (true, true) => PRIMARY_FIELDS.iter().map(|f| f.to_string()).collect(),
+        (true, false) => return vec![],
+    };
+    if let Some(field) = &args.qr_field {
+        fields.push(field.clone());
     }
+    if let Some(Some(field)) = &args.copy {
+        fields.push(field.clone());
+    }
+
+    fields
+}
 
-    if let Some(pk) = payment_params.node_pubkey() {
-        map.insert("pubkey".to_string(), Value::String(pk.to_string()));
+/// The results of processing `inputs` on up to `jobs` threads, in the order of the inputs
+fn process_batch(
+    inputs: &[String],
+    args: &Args,
+    opts: &Options,
+    jobs: usize,
+) -> Vec<Result<Processed>> {
+    if jobs == 1 || inputs.len() == 1 {
+        return inputs.iter().map(|s| process(s, args, opts)).collect();
     }
+    let chunk_size = inputs.len().div_ceil(jobs);
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = inputs
+            .chunks(chunk_size)
+            .map(|chunk| {
+                scope.spawn(move || {
+                    chunk
+                        .iter()
+                        .map(|s| process(s, args, opts))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| {
+                handle
+                    .join()
+                    .unwrap_or_else(|e| std::panic::resume_unwind(e))
+            })
+            .collect()
+    })
+}
+
+/// The non-empty lines of the file at `path`, or of stdin if `path` is "-", read as
+/// they are taken
+fn read_lines(path: &str) -> Result<impl Iterator<Item = Result<String>>> {
+    let reader: Box<dyn BufRead> = if path == "-" {
+        Box::new(BufReader::new(io::stdin()))
+    } else {
+        Box::new(BufReader::new(std::fs::File::open(path)?))
+    };
 
-    if let Some(amt) = payment_params.amount() {
-        // convert to the correct type for our imports
-        let sat = amt.to_sat();
-        let amt = Amount::from_sat(sat);
+    Ok(reader.lines().filter_map(|line| match line {
+        Ok(line) => {
+            let line = line.trim();
+            (!line.is_empty()).then(|| Ok(line.to_string()))
+        }
+        Err(e) => Some(Err(e.into())),
+    }))
+}
+
+/// A parsed string and its output
+struct Processed {
+    kind: &'static str,
+    /// `None` if nothing is to be printed
+    out: Option<Vec<u8>>,
+    /// What `--copy` puts on the clipboard, once the output is printed
+    copy: Option<String>,
+}
+
+/// Parse a single bitcoin string and render the output selected by `args`.
+/// With `--candidates`, 'candidates' lists the readings of the string as objects
+/// with a 'kind', 'confidence' (Number) and 'reason'.
+fn process(s: &str, args: &Args, opts: &Options) -> Result<Processed> {
+    let analysis = waila_cli::analyze(s, opts);
+    if args.record_history {
+        let kind = analysis.as_ref().map(|analysis| analysis.kind);
+        history::record(s, kind, args.hash_inputs);
+    }
+    let analysis = analysis?;
+    let payment = analysis.payment_string.clone();
+    let (kind, mut map) = (analysis.kind, analysis.into_fields());
+    if args.quiet {
+        return Ok(Processed {
+            kind,
+            out: None,
+            copy: None,
+        });
+    }
+    i18n::localize_warnings(&mut map);
+    if args.candidates {
+        let candidates = candidates::interpret(s, kind);
+        map.insert("candidates".to_string(), serde_json::to_value(candidates)?);
+    }
+    if args.metadata {
+        let parsed_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_secs());
+        map.insert("input".to_string(), json!(masked_input(s, args)));
+        map.insert("parsed_at".to_string(), json!(parsed_at));
         map.insert(
-            "amount".to_string(),
-            Value::String(amt.to_string_with_denomination(unit)),
+            "waila_version".to_string(),
+            json!(env!("CARGO_PKG_VERSION")),
         );
     }
+    if let Some(tool) = args.emit_command {
+        let command = emit::command(payment.as_deref(), kind, tool)?;
+        let out = format!("{command}\n").into_bytes();
+        return Ok(Processed {
+            kind,
+            out: Some(out),
+            copy: None,
+        });
+    }
+    if let Some(emit::Emit::Bdk) = args.emit {
+        let blob = emit::bdk(payment.as_deref(), kind, &map)?;
+        let blob = render::convert_keys(blob, args.style.key_case);
+        let out = format!("{}\n", json_text(&blob, args.style, args.terminal)?).into_bytes();
+        return Ok(Processed {
+            kind,
+            out: Some(out),
+            copy: None,
+        });
+    }
+
+    let copy = clipboard_data(s, &map, args)?;
+    let out = render_output(s, kind, map, args)?;
+
+    Ok(Processed {
+        kind,
+        out: Some(out),
+        copy,
+    })
+}
+
+/// What `--copy` puts on the clipboard for the result `map` of the input `s`
+fn clipboard_data(s: &str, map: &Map<String, Value>, args: &Args) -> Result<Option<String>> {
+    let Some(field) = &args.copy else {
+        return Ok(None);
+    };
+    let data = match field {
+        Some(field) => match map.get(field) {
+            Some(value) => to_raw_string(value)?,
+            None => return Err(Error::MissingField(field.clone())),
+        },
+        None if map.get("redacted") == Some(&Value::Bool(true)) => return Err(Error::Redacted),
+        // strings canonicalize doesn't know, like keys, are copied as given
+        None => canonical::canonicalize(s).unwrap_or_else(|_| s.trim().to_string()),
+    };
+
+    Ok(Some(data))
+}
 
-    if let Some(m) = payment_params.memo() {
-        map.insert("memo".to_string(), Value::String(m));
+/// Put `data` on the clipboard. The output is already printed, so a failure
+/// is only warned about.
+fn copy_to_clipboard(data: &str) {
+    if let Err(e) = clipboard::copy(data) {
+        tracing::warn!("could not copy to the clipboard: {e}");
     }
+}
+
+/// Print the JSON `value` of a command other than parsing styled as results are
+fn print_json(value: &impl Serialize, style: Style) -> Result<()> {
+    let value = render::convert_keys(serde_json::to_value(value)?, style.key_case);
+    println!("{}", json_text(&value, style, io::stdout().is_terminal())?);
 
-    if let Some(lnurl) = payment_params.lnurl() {
-        map.insert("lnurl".to_string(), Value::String(lnurl.to_string()));
+    Ok(())
+}
+
+/// `value` as JSON text, indented for reading in a `terminal` and compact for a pipe
+/// unless `style` says otherwise, and colored when indented if it allows
+fn json_text(value: &Value, style: Style, terminal: bool) -> Result<String> {
+    let pretty = !style.flatten && (style.pretty || terminal);
+
+    Ok(match pretty {
+        true if style.color.enabled(terminal) => render::colored_json(value),
+        true => serde_json::to_string_pretty(value)?,
+        false => serde_json::to_string(value)?,
+    })
+}
+
+/// The input as it may be printed, its secrets masked unless `--show-secrets` is set
+fn masked_input(s: &str, args: &Args) -> String {
+    if args.show_secrets {
+        return s.to_string();
     }
+    if let Some(secret) = secret::detect(s) {
+        return secret.masked();
+    }
+
+    secret::redact_keys(s).unwrap_or_else(|| s.to_string())
+}
 
-    if let Some(lnurl) = payment_params.lnurl() {
-        map.insert("lnurl".to_string(), Value::String(lnurl.to_string()));
+/// Render the output selected by `args` for the result `map` of the input `s`
+fn render_output(
+    s: &str,
+    kind: &'static str,
+    map: Map<String, Value>,
+    args: &Args,
+) -> Result<Vec<u8>> {
+    let select: Vec<&str> = if args.raw {
+        let Some(field) = primary_field(kind) else {
+            return Err(Error::NoRawValue(kind));
+        };
+        vec![field]
+    } else {
+        args.select.iter().map(String::as_str).collect()
+    };
+    // selected values are printed as text, unless they are an object in the output format
+    let text = !select.is_empty() && (args.raw || args.select_as == SelectAs::Tsv);
+
+    let qr_data = if args.qr_out || args.qr_file.is_some() {
+        match &args.qr_field {
+            Some(field) => match map.get(field) {
+                Some(value) => Some(to_raw_string(value)?),
+                None => return Err(Error::MissingField(field.clone())),
+            },
+            None if map.get("redacted") == Some(&Value::Bool(true)) => return Err(Error::Redacted),
+            // strings canonicalize doesn't know, like keys, are encoded as given
+            None => Some(canonical::for_qr(s).unwrap_or_else(|_| s.trim().to_string())),
+        }
+    } else {
+        None
+    };
+    if let (Some(path), Some(data)) = (&args.qr_file, &qr_data) {
+        qr::write_file(data, path, args.qr_size, args.qr_ec)?;
+    }
+    if args.qr_only {
+        return match (args.qr_out, &qr_data) {
+            (true, Some(data)) => Ok(format!("{}\n", qr::render_terminal(data)?).into_bytes()),
+            _ => Ok(vec![]),
+        };
     }
 
-    if let Some(lnaddr) = payment_params.lightning_address() {
-        map.insert("lnaddr".to_string(), Value::String(lnaddr.to_string()));
+    // only text for reading is grouped, JSON and the binary formats stay as they are
+    let readable = text || matches!(args.format, Format::Html | Format::Markdown);
+    let map = if args.group_digits && readable {
+        let separators = render::Separators::from_env();
+        let Value::Object(map) = render::group_amounts(Value::Object(map), separators) else {
+            unreachable!("values of an object are grouped in place");
+        };
+        map
+    } else {
+        map
+    };
+
+    let mut out = if text {
+        let value = |field: &str| {
+            let value = map.get(field)?.clone();
+            Some(render::convert_keys(value, args.style.key_case))
+        };
+        if let [field] = select.as_slice() {
+            let Some(value) = value(field) else {
+                return Err(Error::MissingField(field.to_string()));
+            };
+            to_raw_string(&value)?
+        } else {
+            // a field the result lacks is empty, keeping the others in their columns
+            let values = select
+                .iter()
+                .map(|field| match value(field) {
+                    Some(value) => Ok(to_raw_string(&value)?
+                        .replace('\t', "\\t")
+                        .replace('\n', "\\n")),
+                    None => Ok(String::new()),
+                })
+                .collect::<Result<Vec<_>>>()?;
+            values.join("\t")
+        }
+    } else {
+        // the selected fields alone, those the result lacks as null
+        let map = if select.is_empty() {
+            map
+        } else {
+            select
+                .iter()
+                .map(|field| {
+                    let value = map.get(*field).cloned().unwrap_or(Value::Null);
+                    (field.to_string(), value)
+                })
+                .collect()
+        };
+        let converted = render::convert_keys(Value::Object(map), args.style.key_case);
+        let Value::Object(map) = converted else {
+            unreachable!("keys of an object are converted in place");
+        };
+        match args.format {
+            Format::Json => json_text(&Value::Object(map), args.style, args.terminal)?,
+            Format::Ndjson => serde_json::to_string(&map)?,
+            Format::Html => render::html(&map),
+            Format::Markdown => render::markdown(&map),
+            // binary formats are written as is, one item after another
+            Format::Cbor => {
+                let mut buf = vec![];
+                ciborium::into_writer(&map, &mut buf).map_err(|e| Error::Encode(e.to_string()))?;
+                return Ok(buf);
+            }
+            Format::Msgpack => {
+                let buf =
+                    rmp_serde::to_vec_named(&map).map_err(|e| Error::Encode(e.to_string()))?;
+                return Ok(buf);
+            }
+        }
+    };
+
+    if let (true, Some(data)) = (args.qr_out, &qr_data) {
+        out.push('\n');
+        out.push_str(&qr::render_terminal(data)?);
     }
+    out.push('\n');
+
+    Ok(out.into_bytes())
+}
 
-    if let Some(url) = payment_params.payjoin_endpoint() {
-        map.insert("payjoin".to_string(), Value::String(url.to_string()));
+/// Parse a range of indexes, FROM-TO or a single index
+fn range_arg(s: &str) -> core::result::Result<(u32, u32), String> {
+    let index = |s: &str| s.trim().parse::<u32>().map_err(|e| format!("{s}: {e}"));
+    let (from, to) = match s.split_once('-') {
+        Some((from, to)) => (index(from)?, index(to)?),
+        None => (index(s)?, index(s)?),
+    };
+    if from > to {
+        return Err(format!("{from} is after {to}"));
     }
 
-    map
+    Ok((from, to))
+}
+
+/// Parse an amount argument to millisatoshis
+fn amount_arg(s: &str) -> core::result::Result<u64, String> {
+    amount::parse_msat(s).map_err(|e| e.to_string())
 }
 
-/// Attempts to parse a nostr pubkey from [`PaymentParams`].
-/// Returns both hex and bech32 encoding.
-///
-/// ## Errors
-/// If unable to encode bech32
-fn parse_nostr(payment_params: &PaymentParams) -> Result<serde_json::Value> {
-    let Some(k) = payment_params.nostr_pubkey() else {
-        return Ok(json!(null));
+/// Parse a `KIND=CODE` pair of `--exit-kind`. Codes up to 7 are those of errors, and
+/// those above 125 are taken by shells.
+fn exit_kind_arg(s: &str) -> core::result::Result<(String, i32), String> {
+    let Some((kind, code)) = s.split_once('=') else {
+        return Err("expected KIND=CODE".to_string());
     };
+    let kind = kind.to_ascii_lowercase();
+    if !KINDS.contains(&kind.as_str()) {
+        return Err(format!(
+            "unknown kind {kind}, expected one of {}",
+            KINDS.join(", ")
+        ));
+    }
+    let code: i32 = code
+        .parse()
+        .map_err(|_| format!("invalid exit status {code}"))?;
+    if !(8..=125).contains(&code) {
+        return Err(format!("exit status {code} is outside of 8 to 125"));
+    }
 
-    // convert to the correct type for our imports
-    let mykey = XOnlyPublicKey::from_str(&k.to_string()).expect("same value");
-    let bech32 = mykey.to_bech32()?;
+    Ok((kind, code))
+}
 
-    let hex = k.to_string();
+/// The exit status `--exit-kind` gives `kind`, 0 if none
+fn exit_kind(kind: &str, codes: &[(String, i32)]) -> i32 {
+    codes
+        .iter()
+        .find(|(known, _)| known.eq_ignore_ascii_case(kind))
+        .map_or(0, |(_, code)| *code)
+}
 
-    let mut obj = Map::new();
-    obj.insert("hex".to_string(), Value::String(hex));
-    obj.insert("bech32".to_string(), Value::String(bech32));
+/// The field holding the single dominant value of a given kind, if there is one
+fn primary_field(kind: &str) -> Option<&'static str> {
+    match kind {
+        "OnChain" => Some("address"),
+        "Invoice" => Some("invoice"),
+        "PublicKey" => Some("pubkey"),
+        "LnUrl" => Some("lnurl"),
+        "LnAddress" => Some("lnaddr"),
+        "Txid" | "Transaction" => Some("txid"),
+        "Descriptor" => Some("descriptor"),
+        _ => None,
+    }
+}
 
-    Ok(Value::Object(obj))
+/// Render a json value for plain text output, strings are printed without quotes
+fn to_raw_string(value: &Value) -> Result<String> {
+    match value {
+        Value::String(s) => Ok(s.clone()),
+        v => Ok(serde_json::to_string(v)?),
+    }
 }
@@ -0,0 +1,76 @@
+//! The man page, rendered from the command line definitions, with an example of
+//! each kind
+
+use std::io::{self, Write};
+
+/// What each example shows and its command
+const EXAMPLES: [(&str, &str); 13] = [
+    (
+        "An on-chain address",
+        "waila-cli tb1pwzv7fv35yl7ypwj8w7al2t8apd6yf4568cs772qjwper74xqc99sk8x7tk",
+    ),
+    (
+        "A BIP-21 URI, with its amount in bitcoin",
+        "waila-cli --units btc 'bitcoin:tb1pwzv7fv35yl7ypwj8w7al2t8apd6yf4568cs772qjwper74xqc99sk8x7tk?amount=0.001&label=coffee'",
+    ),
+    ("A BOLT-11 invoice, and whether it expired", "waila-cli --reject-expired lnbc..."),
+    ("A BOLT-12 offer", "waila-cli lno1..."),
+    (
+        "A lightning node, with its alias from the graph",
+        "waila-cli --online --lookup 0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+    ),
+    (
+        "An LNURL, resolved to its pay request",
+        "waila-cli --online --resolve LNURL1DP68GURN8GHJ7ETCV9KHQMR99E3K7MF09EMK2MRV944KUMMHDCHKCMN4WFK8QTMPD35KXEG9SAEVQ",
+    ),
+    (
+        "A lightning address, resolved",
+        "waila-cli resolve --online alice@example.com",
+    ),
+    (
+        "A BIP-353 name, verified with DNSSEC",
+        "waila-cli resolve --online '₿matt@mattcorallo.com'",
+    ),
+    (
+        "A nostr public key, in hex and as an npub",
+        "waila-cli --nostr npub10xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqpkge6d",
+    ),
+    (
+        "A transaction id, and where it confirmed",
+        "waila-cli --online --lookup 4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b",
+    ),
+    (
+        "A raw transaction, its inputs and outputs",
+        "bitcoin-cli getrawtransaction <TXID> | waila-cli -F -",
+    ),
+    (
+        "An output descriptor, checked against its checksum",
+        "waila-cli 'wpkh(0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798)'",
+    ),
+    (
+        "A mnemonic, reported as a secret with its words masked",
+        "waila-cli --select secret_value 'abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about'",
+    ),
+];
+
+/// Write the man page of `cmd` to `out`
+pub fn render(cmd: clap::Command, out: &mut dyn Write) -> io::Result<()> {
+    let man = clap_mangen::Man::new(cmd);
+    man.render_title(out)?;
+    man.render_name_section(out)?;
+    man.render_synopsis_section(out)?;
+    man.render_description_section(out)?;
+    man.render_options_section(out)?;
+    man.render_subcommands_section(out)?;
+    writeln!(out, ".SH EXAMPLES")?;
+    for (what, command) in EXAMPLES {
+        writeln!(out, ".TP\n{}\n\\fB{}\\fR", escape(what), escape(command))?;
+    }
+
+    man.render_version_section(out)
+}
+
+/// `s` with the characters roff treats specially escaped
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\e").replace('-', "\\-")
+}
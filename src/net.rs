@@ -0,0 +1,256 @@
+//! Network access shared by the features that go online. Nothing goes online
+//! unless allowed with [`set_online`]. When a SOCKS5 proxy is set, every connection
+//! goes through it. Without the `network` feature, only the switches remain.
+
+use crate::{Error, Result};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+#[cfg(feature = "network")]
+use {
+    crate::cache,
+    serde_json::Value,
+    std::cell::Cell,
+    std::io,
+    std::net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    std::time::{Duration, Instant},
+    tracing::{debug, info},
+};
+
+/// Timeout of a single request
+#[cfg(feature = "network")]
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// `host:port` of the SOCKS5 proxy
+static PROXY: OnceLock<String> = OnceLock::new();
+
+/// The HTTP agent, built on first use so runs that stay offline never set up TLS
+#[cfg(feature = "network")]
+static AGENT: OnceLock<ureq::Agent> = OnceLock::new();
+
+/// Whether network access is allowed
+static ONLINE: AtomicBool = AtomicBool::new(false);
+
+#[cfg(feature = "network")]
+thread_local! {
+    /// When the lookup running on this thread has to be done by
+    static DEADLINE: Cell<Option<Instant>> = Cell::new(None);
+}
+
+/// Allow or forbid network access
+pub fn set_online(online: bool) {
+    ONLINE.store(online, Ordering::Relaxed);
+}
+
+/// Fail unless network access is allowed
+pub fn ensure_online() -> Result<()> {
+    if !cfg!(feature = "network") {
+        return Err(Error::Unsupported("network"));
+    }
+    if ONLINE.load(Ordering::Relaxed) {
+        Ok(())
+    } else {
+        Err(Error::Offline)
+    }
+}
+
+/// Route all network access through the SOCKS5 proxy at `url`, e.g.
+/// `socks5://127.0.0.1:9050` for Tor. Host names are resolved by the proxy,
+/// which makes onion services reachable.
+pub fn set_proxy(url: &str) -> Result<()> {
+    let Some(addr) = url
+        .strip_prefix("socks5h://")
+        .or_else(|| url.strip_prefix("socks5://"))
+    else {
+        return Err(Error::Proxy(format!("expected a socks5:// url: {url}")));
+    };
+    #[cfg(feature = "network")]
+    ureq::Proxy::new(format!("socks5://{addr}"))?;
+    let _ = PROXY.set(addr.to_string());
+
+    Ok(())
+}
+
+/// An HTTP agent with waila's settings, shared by all requests so they reuse its
+/// connections. The proxy must be set before the first request.
+#[cfg(feature = "network")]
+pub fn agent() -> Result<ureq::Agent> {
+    ensure_online()?;
+    let agent = AGENT.get_or_init(|| {
+        let mut builder = ureq::AgentBuilder::new().timeout(TIMEOUT);
+        if let Some(addr) = PROXY.get() {
+            let proxy = ureq::Proxy::new(format!("socks5://{addr}")).expect("checked by set_proxy");
+            builder = builder.proxy(proxy);
+        }
+        builder.build()
+    });
+
+    Ok(agent.clone())
+}
+
+/// Run `f` with the requests it makes on this thread cut short at `deadline`, and
+/// those it would start after it failing
+#[cfg(feature = "network")]
+pub fn with_deadline<T>(deadline: Instant, f: impl FnOnce() -> T) -> T {
+    let outer = DEADLINE.with(|d| d.replace(Some(deadline)));
+    let result = f();
+    DEADLINE.with(|d| d.set(outer));
+
+    result
+}
+
+/// Timeout of the next request, shorter than [`TIMEOUT`] when the deadline of the
+/// lookup is closer
+#[cfg(feature = "network")]
+fn timeout() -> Result<Duration> {
+    let Some(deadline) = DEADLINE.with(Cell::get) else {
+        return Ok(TIMEOUT);
+    };
+    match deadline.checked_duration_since(Instant::now()) {
+        Some(left) if !left.is_zero() => Ok(left.min(TIMEOUT)),
+        _ => Err(Error::Io(io::Error::new(
+            io::ErrorKind::TimedOut,
+            "the lookup ran past its deadline",
+        ))),
+    }
+}
+
+/// An HTTP request of `method` to `url` by the shared agent, with the timeout left
+#[cfg(feature = "network")]
+pub fn request(method: &str, url: &str) -> Result<ureq::Request> {
+    let timeout = timeout()?;
+
+    Ok(agent()?.request(method, url).timeout(timeout))
+}
+
+/// GET `url` and parse the response body as JSON
+#[cfg(feature = "network")]
+pub fn get_json(url: &str) -> Result<Value> {
+    let req = request("GET", url)?;
+    let start = Instant::now();
+    let resp = req.call();
+    info!(url, elapsed = ?start.elapsed(), ok = resp.is_ok(), "GET");
+
+    Ok(resp?.into_json()?)
+}
+
+/// Like [`get_json`], answering from the cache when `url` was fetched recently.
+/// Only for responses that stay valid for a while, not for single use ones like invoices.
+#[cfg(feature = "network")]
+pub fn get_json_cached(url: &str) -> Result<Value> {
+    get_json_cached_if(url, |_| true)
+}
+
+/// Like [`get_json_cached`], storing the response only if `keep` accepts it, for urls
+/// that answer with either kind
+#[cfg(feature = "network")]
+pub fn get_json_cached_if(url: &str, keep: impl Fn(&Value) -> bool) -> Result<Value> {
+    ensure_online()?;
+    let key = format!("GET {url}");
+    if let Some(value) = cache::get(&key).and_then(|v| serde_json::from_slice(&v).ok()) {
+        return Ok(value);
+    }
+    let value = get_json(url)?;
+    if keep(&value) {
+        cache::put(&key, value.to_string().as_bytes());
+    }
+
+    Ok(value)
+}
+
+/// POST `body` as JSON to `url` and parse the response body as JSON
+#[cfg(feature = "network")]
+pub fn post_json(url: &str, body: Value) -> Result<Value> {
+    let req = request("POST", url)?;
+    let start = Instant::now();
+    let resp = req.send_json(body);
+    info!(url, elapsed = ?start.elapsed(), ok = resp.is_ok(), "POST");
+
+    Ok(resp?.into_json()?)
+}
+
+/// Like [`post_json`], answering from the cache when the same query was sent recently
+#[cfg(feature = "network")]
+pub fn post_json_cached(url: &str, body: Value) -> Result<Value> {
+    ensure_online()?;
+    let key = format!("POST {url} {body}");
+    if let Some(value) = cache::get(&key).and_then(|v| serde_json::from_slice(&v).ok()) {
+        return Ok(value);
+    }
+    let value = post_json(url, body)?;
+    cache::put(&key, value.to_string().as_bytes());
+
+    Ok(value)
+}
+
+/// Open a TCP connection to `host` on `port`
+#[cfg(feature = "network")]
+pub fn connect(host: &str, port: u16) -> Result<TcpStream> {
+    ensure_online()?;
+    let timeout = timeout()?;
+    debug!(host, port, proxy = PROXY.get().is_some(), "connecting");
+    let start = Instant::now();
+    let stream = if let Some(proxy) = PROXY.get() {
+        proxy_connect(proxy, host, port)?
+    } else {
+        let Some(addr) = (host, port).to_socket_addrs()?.next() else {
+            return Err(Error::Resolve(format!("no address found for {host}")));
+        };
+        TcpStream::connect_timeout(&addr, timeout)?
+    };
+    stream.set_read_timeout(Some(timeout))?;
+    stream.set_write_timeout(Some(timeout))?;
+    info!(host, port, elapsed = ?start.elapsed(), "connected");
+
+    Ok(stream)
+}
+
+#[cfg(all(feature = "network", not(target_arch = "wasm32")))]
+fn proxy_connect(proxy: &str, host: &str, port: u16) -> Result<TcpStream> {
+    Ok(socks::Socks5Stream::connect(proxy, (host, port))?.into_inner())
+}
+
+#[cfg(all(feature = "network", target_arch = "wasm32"))]
+fn proxy_connect(_proxy: &str, _host: &str, _port: u16) -> Result<TcpStream> {
+    Err(Error::Proxy(
+        "SOCKS5 proxies are not available in WebAssembly".to_string(),
+    ))
+}
+
+/// The address at which to reach `target`, for libraries that open their own
+/// TCP connection. With a proxy, this is a local relay that forwards a single
+/// connection through the proxy.
+#[cfg(feature = "network")]
+pub fn tcp_endpoint(target: SocketAddr) -> Result<SocketAddr> {
+    ensure_online()?;
+    if PROXY.get().is_none() {
+        return Ok(target);
+    }
+
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let local = listener.local_addr()?;
+    let remote = connect(&target.ip().to_string(), target.port())?;
+    std::thread::spawn(move || {
+        if let Ok((client, _)) = listener.accept() {
+            let _ = relay(client, remote);
+        }
+    });
+
+    Ok(local)
+}
+
+/// Copy bytes both ways until the client is done
+#[cfg(feature = "network")]
+fn relay(client: TcpStream, remote: TcpStream) -> io::Result<()> {
+    let mut client_read = client.try_clone()?;
+    let mut remote_write = remote.try_clone()?;
+    let upstream = std::thread::spawn(move || {
+        let _ = io::copy(&mut client_read, &mut remote_write);
+        let _ = remote_write.shutdown(Shutdown::Write);
+    });
+
+    let (mut remote_read, mut client_write) = (remote, client);
+    io::copy(&mut remote_read, &mut client_write)?;
+    let _ = upstream.join();
+
+    Ok(())
+}
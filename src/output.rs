@@ -0,0 +1,136 @@
+//! Typed pieces of the output, serialized into the fields of an [`Analysis`](crate::Analysis).
+//! Whether absent values appear as null is decided when the fields are inserted.
+//!
+//! Only the payment instructions of a payment string and the results of the `convert`
+//! commands are typed. Amounts, fiat, expiry, warnings, lookups and the descriptions of
+//! transactions, descriptors and secrets are built as JSON by their own modules and
+//! carried in [`Analysis::fields`](crate::Analysis::fields).
+
+use crate::amount::format_amount;
+use crate::{amount_msat, Result};
+use bitcoin::Denomination;
+use bitcoin_waila::PaymentParams;
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+/// The payment instructions of a parsed string, on chain, over lightning or both
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Payment {
+    /// bitcoin, testnet, signet or regtest
+    pub network: Option<String>,
+    /// Amount with denomination, e.g. "1000 satoshi"
+    pub amount: Option<String>,
+    pub memo: Option<String>,
+    #[serde(flatten)]
+    pub chain: ChainPayment,
+    #[serde(flatten)]
+    pub lightning: LightningInvoice,
+}
+
+/// Where to pay on chain
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ChainPayment {
+    pub address: Option<String>,
+    /// The BIP-78 or BIP-77 endpoint of a payjoin
+    pub payjoin: Option<String>,
+}
+
+/// Where to pay over lightning: an invoice or offer, a node, or the LNURL or lightning
+/// address to request an invoice from
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct LightningInvoice {
+    pub invoice: Option<String>,
+    pub pubkey: Option<String>,
+    pub lnurl: Option<String>,
+    pub lnaddr: Option<String>,
+}
+
+impl Payment {
+    /// The payment instructions of `payment_params`, the amount given in `unit`
+    pub fn new(payment_params: &PaymentParams, unit: Denomination) -> Self {
+        Self {
+            network: payment_params.network().map(|net| net.to_string()),
+            // out of range amounts are refused by `analyze`
+            amount: amount_msat(payment_params)
+                .ok()
+                .flatten()
+                .map(|msat| format_amount(msat, unit)),
+            memo: payment_params.memo(),
+            chain: ChainPayment {
+                address: payment_params.address().map(|addr| addr.to_string()),
+                payjoin: payment_params.payjoin_endpoint().map(|url| url.to_string()),
+            },
+            lightning: LightningInvoice {
+                invoice: payment_params.invoice().map(|inv| inv.to_string()),
+                pubkey: payment_params.node_pubkey().map(|pk| pk.to_string()),
+                lnurl: payment_params.lnurl().map(|lnurl| lnurl.to_string()),
+                lnaddr: payment_params
+                    .lightning_address()
+                    .map(|lnaddr| lnaddr.to_string()),
+            },
+        }
+    }
+}
+
+/// A nostr public key in both encodings
+#[derive(Debug, Clone, Serialize)]
+pub struct NostrKey {
+    pub hex: String,
+    pub bech32: String,
+}
+
+/// The output script of an address and the hashes identifying it, all in hex
+#[derive(Debug, Clone, Serialize)]
+pub struct Script {
+    pub address: String,
+    /// p2pkh, p2sh, p2wpkh, p2wsh or p2tr
+    pub address_type: Option<String>,
+    pub script_pubkey: String,
+    pub script_asm: String,
+    /// The hash in a p2pkh or p2sh script
+    pub hash160: Option<String>,
+    /// The version and program of a segwit script
+    pub witness_version: Option<u8>,
+    pub witness_program: Option<String>,
+    /// The sha256 of the script in reverse byte order, which Electrum servers index by
+    pub scripthash: String,
+}
+
+/// A nostr key in hex and its NIP-19 encodings, the npub as 'bech32'
+#[derive(Debug, Clone, Serialize)]
+pub struct NostrKeys {
+    #[serde(flatten)]
+    pub key: NostrKey,
+    /// The public key with the relay hints
+    pub nprofile: String,
+    pub relays: Vec<String>,
+    /// The secret key, if one was given
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secret: Option<NostrSecret>,
+}
+
+/// A nostr secret key in hex and as an nsec, masked if `redacted`
+#[derive(Debug, Clone, Serialize)]
+pub struct NostrSecret {
+    pub hex: String,
+    pub nsec: String,
+    pub redacted: bool,
+}
+
+/// Insert the fields of `value` into `map`, those that are null only if `all`
+pub fn insert_fields(
+    map: &mut Map<String, Value>,
+    value: &impl Serialize,
+    all: bool,
+) -> Result<()> {
+    let Value::Object(fields) = serde_json::to_value(value)? else {
+        return Ok(());
+    };
+    for (key, value) in fields {
+        if all || !value.is_null() {
+            map.insert(key, value);
+        }
+    }
+
+    Ok(())
+}
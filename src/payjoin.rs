@@ -0,0 +1,111 @@
+//! Health check of payjoin endpoints, BIP-78 (v1) and BIP-77 (v2)
+
+use crate::{net, Result};
+use serde_json::{json, Value};
+use std::io::Read;
+use url::Url;
+
+/// Largest response body read from an endpoint
+const MAX_BODY: u64 = 64 * 1024;
+
+/// Check whether the payjoin endpoint `pj` can be used. A v1 endpoint must answer
+/// over https (or http for onion services). For v2 the OHTTP keys must be available
+/// from the directory, unless the URI carries them itself.
+pub fn check(pj: &str) -> Result<Value> {
+    let Ok(url) = Url::parse(pj) else {
+        return Ok(json!({ "endpoint": pj, "serviceable": false, "error": "invalid url" }));
+    };
+    // v2 endpoints carry their parameters in the fragment
+    let v2 = url.fragment().is_some_and(|f| !f.is_empty());
+    let onion = url.host_str().is_some_and(|h| h.ends_with(".onion"));
+    if url.scheme() != "https" && !(url.scheme() == "http" && onion) {
+        return Ok(json!({
+            "endpoint": pj,
+            "version": if v2 { 2 } else { 1 },
+            "serviceable": false,
+            "error": "endpoint must use https",
+        }));
+    }
+
+    if v2 {
+        check_v2(pj, &url)
+    } else {
+        check_v1(pj)
+    }
+}
+
+fn check_v1(pj: &str) -> Result<Value> {
+    // the endpoint only takes POST, any HTTP response means it is up
+    let report = match fetch(net::request("GET", pj)?) {
+        Ok((status, _)) => json!({
+            "endpoint": pj,
+            "version": 1,
+            "reachable": true,
+            "status": status,
+            "serviceable": true,
+        }),
+        Err(e) => json!({
+            "endpoint": pj,
+            "version": 1,
+            "reachable": false,
+            "serviceable": false,
+            "error": e,
+        }),
+    };
+
+    Ok(report)
+}
+
+fn check_v2(pj: &str, url: &Url) -> Result<Value> {
+    let fragment = url.fragment().unwrap_or_default().to_ascii_uppercase();
+    let keys_in_uri = fragment
+        .split(['+', '-'])
+        .any(|param| param.starts_with("OH1"));
+    let directory = url.origin().ascii_serialization();
+
+    let req = net::request("GET", &format!("{directory}/.well-known/ohttp-gateway"))?
+        .set("Accept", "application/ohttp-keys");
+    let report = match fetch(req) {
+        Ok((status, body)) => {
+            let keys_fetched = status == 200 && !body.is_empty();
+            json!({
+                "endpoint": pj,
+                "version": 2,
+                "directory": directory,
+                "reachable": true,
+                "status": status,
+                "ohttp_keys_in_uri": keys_in_uri,
+                "ohttp_keys_fetched": keys_fetched,
+                "serviceable": keys_in_uri || keys_fetched,
+            })
+        }
+        Err(e) => json!({
+            "endpoint": pj,
+            "version": 2,
+            "directory": directory,
+            "reachable": false,
+            "ohttp_keys_in_uri": keys_in_uri,
+            "serviceable": false,
+            "error": e,
+        }),
+    };
+
+    Ok(report)
+}
+
+/// Send `req`, returning the status and body of any HTTP response, or why there was none
+fn fetch(req: ureq::Request) -> core::result::Result<(u16, Vec<u8>), String> {
+    match req.call() {
+        Ok(resp) => {
+            let status = resp.status();
+            let mut body = vec![];
+            resp.into_reader()
+                .take(MAX_BODY)
+                .read_to_end(&mut body)
+                .map_err(|e| e.to_string())?;
+            Ok((status, body))
+        }
+        Err(ureq::Error::Status(status, _)) => Ok((status, vec![])),
+        Err(e) => Err(e.to_string()),
+    }
+}
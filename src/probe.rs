@@ -0,0 +1,191 @@
+//! Connectivity probe of lightning node URIs, `pubkey@host:port`
+
+use bitcoin::secp256k1::PublicKey;
+use std::str::FromStr;
+#[cfg(feature = "network")]
+use {
+    crate::{net, Error, Result},
+    bitcoin::hashes::hmac::{Hmac, HmacEngine},
+    bitcoin::hashes::{sha256, Hash, HashEngine},
+    bitcoin::secp256k1::ecdh::SharedSecret,
+    bitcoin::secp256k1::{rand, Secp256k1, SecretKey},
+    chacha20poly1305::aead::{Aead, KeyInit, Payload},
+    chacha20poly1305::{ChaCha20Poly1305, Key, Nonce},
+    serde_json::{json, Value},
+    std::io::{Read, Write},
+    std::time::Instant,
+};
+
+/// The default lightning port
+const DEFAULT_PORT: u16 = 9735;
+
+/// A lightning node and where to reach it
+#[derive(Debug, Clone)]
+pub struct NodeUri {
+    pub pubkey: PublicKey,
+    /// Host name or ip address, without brackets
+    pub host: String,
+    pub port: u16,
+}
+
+impl NodeUri {
+    /// Read a node URI, adding the default port if there is none
+    pub fn parse(s: &str) -> Option<Self> {
+        let (pubkey, host) = s.split_once('@')?;
+        let pubkey = PublicKey::from_str(pubkey).ok()?;
+        if host.is_empty() {
+            return None;
+        }
+        // an ipv6 host is in brackets, which end the address when there is no port
+        let (host, port) = match host.rsplit_once(':') {
+            Some((h, port)) if h.ends_with(']') || !h.contains(':') => (h, port.parse().ok()?),
+            _ => (host, DEFAULT_PORT),
+        };
+        let host = host
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .to_string();
+
+        Some(Self { pubkey, host, port })
+    }
+
+    /// `host:port`, with brackets around ipv6 hosts
+    pub fn address(&self) -> String {
+        if self.host.contains(':') {
+            format!("[{}]:{}", self.host, self.port)
+        } else {
+            format!("{}:{}", self.host, self.port)
+        }
+    }
+}
+
+/// Try to connect to the node, and optionally perform the first two acts of
+/// the BOLT 8 noise handshake, which proves the peer holds the node key.
+/// Failures to connect are part of the report rather than errors.
+#[cfg(feature = "network")]
+pub fn probe(node: &NodeUri, handshake: bool) -> Result<Value> {
+    net::ensure_online()?;
+    let address = node.address();
+    let start = Instant::now();
+    let stream = match net::connect(&node.host, node.port) {
+        Ok(stream) => stream,
+        Err(e) => {
+            return Ok(json!({
+                "address": address,
+                "reachable": false,
+                "error": e.to_string(),
+            }));
+        }
+    };
+    let latency_ms = start.elapsed().as_secs_f64() * 1_000.0;
+
+    let mut report = json!({
+        "address": address,
+        "reachable": true,
+        "latency_ms": (latency_ms * 100.0).round() / 100.0,
+    });
+    if handshake {
+        report["handshake"] = match noise_handshake(stream, &node.pubkey) {
+            Ok(()) => json!("ok"),
+            Err(e) => json!(format!("failed: {e}")),
+        };
+    }
+
+    Ok(report)
+}
+
+/// Act one and act two of the BOLT 8 handshake as initiator
+#[cfg(feature = "network")]
+fn noise_handshake(mut stream: impl Read + Write, remote: &PublicKey) -> Result<()> {
+    let secp = Secp256k1::new();
+
+    let mut h = sha256::Hash::hash(b"Noise_XK_secp256k1_ChaChaPoly_SHA256").to_byte_array();
+    let ck = h;
+    h = sha256_concat(&h, b"lightning");
+    h = sha256_concat(&h, &remote.serialize());
+
+    // act one: 0 || e.pub || tag
+    let e = SecretKey::new(&mut rand::thread_rng());
+    let e_pub = PublicKey::from_secret_key(&secp, &e);
+    h = sha256_concat(&h, &e_pub.serialize());
+    let es = SharedSecret::new(remote, &e).secret_bytes();
+    let (ck, temp_k1) = hkdf(&ck, &es);
+    let c = encrypt_with_ad(&temp_k1, &h, &[])?;
+    h = sha256_concat(&h, &c);
+
+    let mut act_one = vec![0u8];
+    act_one.extend_from_slice(&e_pub.serialize());
+    act_one.extend_from_slice(&c);
+    stream.write_all(&act_one)?;
+
+    // act two: 0 || re || tag
+    let mut act_two = [0u8; 50];
+    stream.read_exact(&mut act_two)?;
+    if act_two[0] != 0 {
+        return Err(Error::Resolve("unknown handshake version".to_string()));
+    }
+    let re = PublicKey::from_slice(&act_two[1..34])
+        .map_err(|_| Error::Resolve("invalid ephemeral key".to_string()))?;
+    h = sha256_concat(&h, &re.serialize());
+    let ee = SharedSecret::new(&re, &e).secret_bytes();
+    let (_ck, temp_k2) = hkdf(&ck, &ee);
+    decrypt_with_ad(&temp_k2, &h, &act_two[34..])?;
+
+    Ok(())
+}
+
+#[cfg(feature = "network")]
+fn sha256_concat(a: &[u8], b: &[u8]) -> [u8; 32] {
+    let mut engine = sha256::Hash::engine();
+    engine.input(a);
+    engine.input(b);
+    sha256::Hash::from_engine(engine).to_byte_array()
+}
+
+#[cfg(feature = "network")]
+fn hmac(key: &[u8], data: &[u8]) -> [u8; 32] {
+    let mut engine = HmacEngine::<sha256::Hash>::new(key);
+    engine.input(data);
+    Hmac::<sha256::Hash>::from_engine(engine).to_byte_array()
+}
+
+/// HKDF with `salt` and zero length info, producing two 32 byte keys
+#[cfg(feature = "network")]
+fn hkdf(salt: &[u8; 32], ikm: &[u8; 32]) -> ([u8; 32], [u8; 32]) {
+    let prk = hmac(salt, ikm);
+    let t1 = hmac(&prk, &[1]);
+    let mut t2_input = t1.to_vec();
+    t2_input.push(2);
+    let t2 = hmac(&prk, &t2_input);
+
+    (t1, t2)
+}
+
+/// ChaCha20-Poly1305 with a zero nonce, both acts use nonce 0
+#[cfg(feature = "network")]
+fn encrypt_with_ad(key: &[u8; 32], ad: &[u8], plaintext: &[u8]) -> Result<Vec<u8>> {
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(key));
+    cipher
+        .encrypt(
+            Nonce::from_slice(&[0u8; 12]),
+            Payload {
+                msg: plaintext,
+                aad: ad,
+            },
+        )
+        .map_err(|_| Error::Resolve("encryption failed".to_string()))
+}
+
+#[cfg(feature = "network")]
+fn decrypt_with_ad(key: &[u8; 32], ad: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>> {
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(key));
+    cipher
+        .decrypt(
+            Nonce::from_slice(&[0u8; 12]),
+            Payload {
+                msg: ciphertext,
+                aad: ad,
+            },
+        )
+        .map_err(|_| Error::Resolve("peer does not hold the node key".to_string()))
+}
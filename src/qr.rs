@@ -0,0 +1,95 @@
+//! QR code rendering of payment strings, with the `qr` feature and for PNG images
+//! the `png` feature
+
+use crate::{Error, Result};
+use clap::ValueEnum;
+#[cfg(feature = "png")]
+use image::Luma;
+#[cfg(feature = "qr")]
+use qrcode::render::{svg, unicode::Dense1x2};
+#[cfg(feature = "qr")]
+use qrcode::{EcLevel, QrCode};
+use std::path::Path;
+
+/// Error correction level of a QR code
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorCorrection {
+    /// Recovers 7% of data
+    Low,
+    /// Recovers 15% of data
+    Medium,
+    /// Recovers 25% of data
+    Quartile,
+    /// Recovers 30% of data
+    High,
+}
+
+#[cfg(feature = "qr")]
+impl From<ErrorCorrection> for EcLevel {
+    fn from(ec: ErrorCorrection) -> Self {
+        match ec {
+            ErrorCorrection::Low => EcLevel::L,
+            ErrorCorrection::Medium => EcLevel::M,
+            ErrorCorrection::Quartile => EcLevel::Q,
+            ErrorCorrection::High => EcLevel::H,
+        }
+    }
+}
+
+/// Render `data` as a QR code made of unicode half blocks, suitable for
+/// scanning off a terminal with dark background
+#[cfg(feature = "qr")]
+pub fn render_terminal(data: &str) -> Result<String> {
+    let code = QrCode::new(data.as_bytes())?;
+    let image = code
+        .render::<Dense1x2>()
+        .dark_color(Dense1x2::Light)
+        .light_color(Dense1x2::Dark)
+        .quiet_zone(true)
+        .build();
+
+    Ok(image)
+}
+
+/// Write `data` as a QR code image to `path`. The image format, PNG or SVG,
+/// is chosen by the file extension, and `size` is the minimum width in pixels.
+#[cfg(feature = "qr")]
+pub fn write_file(data: &str, path: &Path, size: u32, ec: ErrorCorrection) -> Result<()> {
+    let code = QrCode::with_error_correction_level(data.as_bytes(), ec.into())?;
+
+    let ext = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.to_ascii_lowercase());
+    match ext.as_deref() {
+        #[cfg(feature = "png")]
+        Some("png") => {
+            let image = code.render::<Luma<u8>>().min_dimensions(size, size).build();
+            image.save(path)?;
+        }
+        #[cfg(not(feature = "png"))]
+        Some("png") => return Err(Error::Unsupported("png")),
+        Some("svg") => {
+            let image = code
+                .render::<svg::Color>()
+                .min_dimensions(size, size)
+                .build();
+            std::fs::write(path, image)?;
+        }
+        _ => {
+            return Err(Error::QrFormat(path.display().to_string()));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(not(feature = "qr"))]
+pub fn render_terminal(_data: &str) -> Result<String> {
+    Err(Error::Unsupported("qr"))
+}
+
+#[cfg(not(feature = "qr"))]
+pub fn write_file(_data: &str, _path: &Path, _size: u32, _ec: ErrorCorrection) -> Result<()> {
+    Err(Error::Unsupported("qr"))
+}
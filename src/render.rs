@@ -0,0 +1,374 @@
+//! Rendering of results in formats other than JSON, and of JSON for terminals
+
+use crate::i18n;
+use clap::ValueEnum;
+use serde_json::{Map, Value};
+
+/// Naming convention of output keys
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyCase {
+    /// snake_case, the default
+    Snake,
+    /// camelCase
+    Camel,
+    /// kebab-case
+    Kebab,
+}
+
+impl KeyCase {
+    /// Convert a snake_case key
+    fn convert(self, key: &str) -> String {
+        match self {
+            KeyCase::Snake => key.to_string(),
+            KeyCase::Kebab => key.replace('_', "-"),
+            KeyCase::Camel => {
+                let mut words = key.split('_');
+                let mut out = words.next().unwrap_or_default().to_string();
+                for word in words {
+                    let mut chars = word.chars();
+                    if let Some(c) = chars.next() {
+                        out.extend(c.to_uppercase());
+                        out.push_str(chars.as_str());
+                    }
+                }
+                out
+            }
+        }
+    }
+}
+
+/// Rename the keys of all objects in `value` to the given case
+pub fn convert_keys(value: Value, case: KeyCase) -> Value {
+    match value {
+        Value::Object(map) if case != KeyCase::Snake => Value::Object(
+            map.into_iter()
+                .map(|(k, v)| (case.convert(&k), convert_keys(v, case)))
+                .collect(),
+        ),
+        Value::Array(values) if case != KeyCase::Snake => {
+            Value::Array(values.into_iter().map(|v| convert_keys(v, case)).collect())
+        }
+        v => v,
+    }
+}
+
+/// When to color JSON
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Color {
+    /// When printing to a terminal and `NO_COLOR` isn't set
+    Auto,
+    Always,
+    Never,
+}
+
+impl Color {
+    /// Whether to color output, which goes to a terminal if `terminal`
+    pub fn enabled(self, terminal: bool) -> bool {
+        match self {
+            Color::Auto => {
+                terminal
+                    && std::env::var_os("NO_COLOR")
+                        .filter(|v| !v.is_empty())
+                        .is_none()
+            }
+            Color::Always => true,
+            Color::Never => false,
+        }
+    }
+}
+
+const KEY: &str = "\x1b[34;1m";
+const STRING: &str = "\x1b[32m";
+const NULL: &str = "\x1b[90m";
+const RESET: &str = "\x1b[0m";
+
+/// Indented JSON of a result colored with ANSI escapes, keys in blue, strings in green
+/// and null in gray, laid out like `serde_json::to_string_pretty`
+pub fn colored_json(value: &Value) -> String {
+    let mut out = String::new();
+    colored_value(value, 0, &mut out);
+
+    out
+}
+
+fn colored_object(map: &Map<String, Value>, depth: usize, out: &mut String) {
+    if map.is_empty() {
+        out.push_str("{}");
+        return;
+    }
+    let indent = "  ".repeat(depth + 1);
+    out.push('{');
+    for (i, (key, value)) in map.iter().enumerate() {
+        out.push_str(if i == 0 { "\n" } else { ",\n" });
+        // the key is written escaped, as a json string
+        let key = Value::String(key.clone());
+        out.push_str(&format!("{indent}{KEY}{key}{RESET}: "));
+        colored_value(value, depth + 1, out);
+    }
+    out.push_str(&format!("\n{}}}", "  ".repeat(depth)));
+}
+
+fn colored_value(value: &Value, depth: usize, out: &mut String) {
+    let indent = "  ".repeat(depth + 1);
+    match value {
+        Value::Object(map) => colored_object(map, depth, out),
+        Value::Array(values) if !values.is_empty() => {
+            out.push('[');
+            for (i, value) in values.iter().enumerate() {
+                out.push_str(if i == 0 { "\n" } else { ",\n" });
+                out.push_str(&indent);
+                colored_value(value, depth + 1, out);
+            }
+            out.push_str(&format!("\n{}]", "  ".repeat(depth)));
+        }
+        Value::String(_) => out.push_str(&format!("{STRING}{value}{RESET}")),
+        Value::Null => out.push_str(&format!("{NULL}null{RESET}")),
+        // numbers, booleans and empty arrays
+        v => out.push_str(&v.to_string()),
+    }
+}
+
+/// Keys of amounts, in satoshis, millisatoshis, bitcoin or fiat, whose digits
+/// `--group-digits` groups. The values below `rates` and `values` are those of fiat.
+const AMOUNT_KEYS: [&str; 15] = [
+    "amount",
+    "btc",
+    "sat",
+    "msat",
+    "amount_msat",
+    "rates",
+    "values",
+    "output_value",
+    "confirmed_balance",
+    "unconfirmed_balance",
+    "fee",
+    "net_amount",
+    "capacity",
+    "min_sendable",
+    "max_sendable",
+];
+
+/// Separators of digit groups and of the decimals of a number
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Separators {
+    pub group: char,
+    pub decimal: char,
+}
+
+impl Separators {
+    /// The separators of the locale in `LC_ALL`, `LC_NUMERIC` or `LANG`, those of
+    /// English if none is set
+    pub fn from_env() -> Self {
+        let locale = ["LC_ALL", "LC_NUMERIC", "LANG"]
+            .iter()
+            .filter_map(|var| std::env::var(var).ok())
+            .find(|locale| !locale.is_empty())
+            .unwrap_or_default();
+        // e.g. de_CH.UTF-8 or fr_FR@euro
+        let name = locale.split(['.', '@']).next().unwrap_or_default();
+        let (language, region) = name.split_once('_').unwrap_or((name, ""));
+
+        let (group, decimal) = match (language, region) {
+            ("de" | "it", "CH") => ('\'', '.'),
+            ("es", "MX" | "US") => (',', '.'),
+            (
+                "de" | "es" | "it" | "nl" | "pt" | "da" | "id" | "tr" | "el" | "ro" | "hr" | "sl"
+                | "sr",
+                _,
+            ) => ('.', ','),
+            (
+                "fr" | "ru" | "uk" | "pl" | "cs" | "sk" | "sv" | "nb" | "nn" | "fi" | "hu" | "bg"
+                | "et" | "lv" | "lt",
+                _,
+            ) => ('\u{a0}', ','),
+            _ => (',', '.'),
+        };
+
+        Separators { group, decimal }
+    }
+}
+
+/// `value` with the digits of its amounts grouped, e.g. `1234567 sat` as
+/// `1,234,567 sat`. Grouped amounts that were numbers become strings.
+pub fn group_amounts(value: Value, separators: Separators) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.into_iter()
+                .map(|(k, v)| {
+                    let v = if AMOUNT_KEYS.contains(&k.as_str()) {
+                        group_all(v, separators)
+                    } else {
+                        group_amounts(v, separators)
+                    };
+                    (k, v)
+                })
+                .collect(),
+        ),
+        Value::Array(values) => Value::Array(
+            values
+                .into_iter()
+                .map(|v| group_amounts(v, separators))
+                .collect(),
+        ),
+        v => v,
+    }
+}
+
+/// `value` with the digits of every number in it grouped
+fn group_all(value: Value, separators: Separators) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.into_iter()
+                .map(|(k, v)| (k, group_all(v, separators)))
+                .collect(),
+        ),
+        Value::Array(values) => Value::Array(
+            values
+                .into_iter()
+                .map(|v| group_all(v, separators))
+                .collect(),
+        ),
+        Value::Number(n) => Value::String(group_digits(&n.to_string(), separators)),
+        Value::String(s) => Value::String(group_digits(&s, separators)),
+        v => v,
+    }
+}
+
+/// `text` with the number it starts with written with `separators`, the rest, such as
+/// a unit, left as it is
+fn group_digits(text: &str, separators: Separators) -> String {
+    let end = text
+        .find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '-'))
+        .unwrap_or(text.len());
+    let (number, rest) = text.split_at(end);
+    let (sign, number) = match number.strip_prefix('-') {
+        Some(number) => ("-", number),
+        None => ("", number),
+    };
+    let (int, frac) = match number.split_once('.') {
+        Some((int, frac)) => (int, Some(frac)),
+        None => (number, None),
+    };
+    if int.is_empty() || !int.bytes().all(|b| b.is_ascii_digit()) {
+        return text.to_string();
+    }
+
+    let mut out = sign.to_string();
+    for (i, c) in int.chars().enumerate() {
+        if i > 0 && (int.len() - i) % 3 == 0 {
+            out.push(separators.group);
+        }
+        out.push(c);
+    }
+    if let Some(frac) = frac {
+        out.push(separators.decimal);
+        out.push_str(frac);
+    }
+    out.push_str(rest);
+
+    out
+}
+
+/// Render a result as an HTML definition list. Each term and description
+/// carries a `waila-<field>` class, and the list a `waila-kind-<kind>` class.
+pub fn html(map: &Map<String, Value>) -> String {
+    let kind = map.get("kind").and_then(Value::as_str).unwrap_or_default();
+    let mut out = format!(
+        "<dl class=\"waila waila-kind-{}\">\n",
+        escape_html(&kind.to_lowercase())
+    );
+    html_entries(map, 1, &mut out);
+    out.push_str("</dl>");
+
+    out
+}
+
+fn html_entries(map: &Map<String, Value>, depth: usize, out: &mut String) {
+    let indent = "  ".repeat(depth);
+    for (key, value) in map {
+        let class = format!("waila-{}", escape_html(key));
+        out.push_str(&format!(
+            "{indent}<dt class=\"{class}\">{}</dt>\n",
+            escape_html(key)
+        ));
+        match value {
+            Value::Object(obj) => {
+                out.push_str(&format!("{indent}<dd class=\"{class}\">\n"));
+                out.push_str(&format!("{indent}  <dl>\n"));
+                html_entries(obj, depth + 2, out);
+                out.push_str(&format!("{indent}  </dl>\n"));
+                out.push_str(&format!("{indent}</dd>\n"));
+            }
+            Value::Null => {
+                out.push_str(&format!("{indent}<dd class=\"{class} waila-null\"></dd>\n"));
+            }
+            v => {
+                out.push_str(&format!(
+                    "{indent}<dd class=\"{class}\">{}</dd>\n",
+                    escape_html(&scalar(v))
+                ));
+            }
+        }
+    }
+}
+
+/// Render a result as a Markdown table. Nested objects are flattened into
+/// dotted field names, e.g. `nostr.hex`.
+pub fn markdown(map: &Map<String, Value>) -> String {
+    let mut rows = vec![];
+    markdown_rows(map, "", &mut rows);
+
+    let mut out = format!(
+        "| {} | {} |\n| --- | --- |",
+        i18n::localize("Field"),
+        i18n::localize("Value")
+    );
+    for (field, value) in rows {
+        out.push_str(&format!("\n| {field} | {value} |"));
+    }
+
+    out
+}
+
+fn markdown_rows(map: &Map<String, Value>, prefix: &str, rows: &mut Vec<(String, String)>) {
+    for (key, value) in map {
+        let field = format!("{prefix}{key}");
+        match value {
+            Value::Object(obj) => markdown_rows(obj, &format!("{field}."), rows),
+            Value::Null => rows.push((field, String::new())),
+            v => {
+                let text = scalar(v).replace('|', "\\|");
+                let cell = if text.contains('`') {
+                    text
+                } else {
+                    format!("`{text}`")
+                };
+                rows.push((field, cell));
+            }
+        }
+    }
+}
+
+/// Plain text of a json value that isn't an object
+fn scalar(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        v => v.to_string(),
+    }
+}
+
+fn escape_html(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+
+    out
+}
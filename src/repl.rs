@@ -0,0 +1,244 @@
+//! An interactive session. Each line is parsed like a query given on the command line,
+//! and lines starting with ':' change the options of the following ones.
+
+use crate::{
+    copy_to_clipboard, denomination, error_json, history, options, process, rates, render, Args,
+    Format, Processed, FIELDS, KINDS, UNITS,
+};
+use clap::ValueEnum;
+use rustyline::error::ReadlineError;
+use rustyline::DefaultEditor;
+use std::io::{self, IsTerminal, Write};
+use std::path::PathBuf;
+use std::time::Duration;
+use waila_cli::{cache, net, secret, Error, Result};
+
+const PROMPT: &str = "waila> ";
+
+const HELP: &str = "\
+Paste a bitcoin string to parse it, or change how the following ones are parsed:
+  :format FORMAT    json, ndjson, html or markdown
+  :units UNIT       btc, mbtc, ubtc, bits, sat or msat
+  :select FIELDS    print only FIELDS, e.g. address,amount, everything again with :select off
+  :kind KIND        fail unless the string is of KIND, any again with :kind off
+  :key-case CASE    snake, camel or kebab
+  :resolve on|off   and likewise :online, :lookup, :probe, :handshake, :all, :nostr,
+                    :flatten, :raw, :numeric-amounts, :candidates, :strict,
+                    :reject-expired and :show-secrets
+  :set              show the current settings
+  :help             show this help
+  :quit             leave, as does Ctrl-D";
+
+/// Settings that are turned on and off
+const TOGGLES: [&str; 14] = [
+    "online",
+    "resolve",
+    "lookup",
+    "probe",
+    "handshake",
+    "all",
+    "nostr",
+    "flatten",
+    "raw",
+    "numeric-amounts",
+    "candidates",
+    "strict",
+    "reject-expired",
+    "show-secrets",
+];
+
+/// Read strings and commands from the terminal until the end of input, starting
+/// with the options of `args`
+pub fn run(mut args: Args) -> Result<()> {
+    args.terminal = io::stdout().is_terminal();
+    if let Some(proxy) = &args.proxy {
+        net::set_proxy(proxy)?;
+    }
+    if !args.no_cache {
+        cache::enable(args.cache_dir.clone(), Duration::from_secs(args.cache_ttl));
+    }
+
+    let mut editor = DefaultEditor::new().map_err(readline_error)?;
+    let history = history_path();
+    if let Some(path) = &history {
+        // there is no history before the first session
+        let _ = editor.load_history(path);
+    }
+    println!(
+        "waila-cli {}, :help lists the commands",
+        env!("CARGO_PKG_VERSION")
+    );
+
+    loop {
+        let line = match editor.readline(PROMPT) {
+            Ok(line) => line,
+            // Ctrl-C drops the line, Ctrl-D ends the session
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(e) => return Err(readline_error(e)),
+        };
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        // secrets are kept out of the history file
+        if secret::detect(line).is_none() && secret::redact_keys(line).is_none() {
+            let _ = editor.add_history_entry(line);
+        }
+
+        if let Some(command) = line.strip_prefix(':') {
+            match apply(command, &mut args) {
+                Ok(true) => break,
+                Ok(false) => {}
+                Err(e) => eprintln!("{e}"),
+            }
+            continue;
+        }
+        match parse(line, &args) {
+            Ok(processed) => {
+                io::stdout().write_all(&processed.out.unwrap_or_default())?;
+                if let Some(data) = processed.copy {
+                    io::stdout().flush()?;
+                    copy_to_clipboard(&data);
+                }
+            }
+            Err(e) => eprintln!("{}", error_json(&e)),
+        }
+    }
+
+    if let Some(path) = &history {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        editor.save_history(path).map_err(readline_error)?;
+    }
+
+    Ok(())
+}
+
+/// The output of parsing `line` with the current options
+fn parse(line: &str, args: &Args) -> Result<Processed> {
+    net::set_online(args.online);
+    // rates are fetched for each string, recent ones come from the cache
+    let opts = options(args, denomination(&args.unit), rates(args)?);
+
+    process(line, args, &opts)
+}
+
+/// Apply the command `line`, given without its ':', to `args`. Returns whether the
+/// session ends.
+fn apply(line: &str, args: &mut Args) -> core::result::Result<bool, String> {
+    let (name, value) = match line.split_once(char::is_whitespace) {
+        Some((name, value)) => (name, Some(value.trim())),
+        None => (line, None),
+    };
+    match (name, value) {
+        ("q" | "quit" | "exit", _) => return Ok(true),
+        ("h" | "help", _) => println!("{HELP}"),
+        ("set", _) => println!("{}", settings(args)),
+        ("format", Some(value)) => {
+            let format = Format::from_str(value, true)?;
+            if matches!(format, Format::Cbor | Format::Msgpack) {
+                return Err(format!("{value} is binary, use it on the command line"));
+            }
+            args.format = format;
+        }
+        ("units", Some(value)) => args.unit = one_of(&value.to_lowercase(), &UNITS, "unit")?,
+        ("select", Some("off")) => args.select.clear(),
+        ("select", Some(fields)) => {
+            args.select = fields
+                .split(',')
+                .map(|field| one_of(field.trim(), &FIELDS, "field"))
+                .collect::<core::result::Result<_, _>>()?;
+        }
+        ("kind", Some("off")) => args.kind = None,
+        ("kind", Some(kind)) => args.kind = Some(one_of(&kind.to_lowercase(), &KINDS, "kind")?),
+        ("key-case", Some(value)) => args.style.key_case = render::KeyCase::from_str(value, true)?,
+        (name, value) => {
+            let Some(setting) = toggle(args, name) else {
+                return Err(format!("unknown command :{name}, :help lists the commands"));
+            };
+            *setting = match value {
+                Some("on") => true,
+                Some("off") => false,
+                _ => return Err(format!("expected :{name} on or :{name} off")),
+            };
+        }
+    }
+
+    Ok(false)
+}
+
+/// The current settings, one per line
+fn settings(args: &mut Args) -> String {
+    let or_off = |value: &Option<String>| value.clone().unwrap_or_else(|| "off".to_string());
+    let select = (!args.select.is_empty()).then(|| args.select.join(","));
+    let mut lines = vec![
+        format!("format {}", name(args.format)),
+        format!("units {}", args.unit),
+        format!("select {}", or_off(&select)),
+        format!("kind {}", or_off(&args.kind)),
+        format!("key-case {}", name(args.style.key_case)),
+    ];
+    for setting in TOGGLES {
+        if let Some(&mut on) = toggle(args, setting) {
+            lines.push(format!("{setting} {}", if on { "on" } else { "off" }));
+        }
+    }
+
+    lines.join("\n")
+}
+
+/// The on and off setting `name` of `args`
+fn toggle<'a>(args: &'a mut Args, name: &str) -> Option<&'a mut bool> {
+    let setting = match name {
+        "online" => &mut args.online,
+        "resolve" => &mut args.resolve,
+        "lookup" => &mut args.lookup,
+        "probe" => &mut args.probe,
+        "handshake" => &mut args.handshake,
+        "all" => &mut args.all,
+        "nostr" => &mut args.nostr,
+        "flatten" => &mut args.style.flatten,
+        "raw" => &mut args.raw,
+        "numeric-amounts" => &mut args.numeric_amounts,
+        "candidates" => &mut args.candidates,
+        "strict" => &mut args.strict,
+        "reject-expired" => &mut args.reject_expired,
+        "show-secrets" => &mut args.show_secrets,
+        _ => return None,
+    };
+
+    Some(setting)
+}
+
+/// `value` if it is one of `names`
+fn one_of(value: &str, names: &[&str], what: &str) -> core::result::Result<String, String> {
+    if names.contains(&value) {
+        Ok(value.to_string())
+    } else {
+        Err(format!(
+            "unknown {what} {value}, one of {}",
+            names.join(", ")
+        ))
+    }
+}
+
+/// The name of a value of a flag, as given on the command line
+fn name(value: impl ValueEnum) -> String {
+    value
+        .to_possible_value()
+        .map_or_else(String::new, |value| value.get_name().to_string())
+}
+
+/// `waila/history` under `$XDG_STATE_HOME` or `~/.local/state`
+fn history_path() -> Option<PathBuf> {
+    history::state_dir().map(|dir| dir.join("history"))
+}
+
+fn readline_error(e: ReadlineError) -> Error {
+    match e {
+        ReadlineError::Io(e) => Error::Io(e),
+        e => Error::Io(io::Error::other(e)),
+    }
+}
@@ -0,0 +1,301 @@
+//! Secret material: private keys, mnemonics and credentials. These are recognized
+//! so they can be reported, but their values are masked unless asked for.
+
+use bitcoin::base58;
+use bitcoin::bech32::{self, FromBase32};
+use bitcoin::bip32::{ExtendedPrivKey, ExtendedPubKey};
+use bitcoin::secp256k1::Secp256k1;
+use serde_json::{json, Map, Value};
+
+/// Version bytes of extended private keys and their networks
+const XPRV_VERSIONS: [([u8; 4], &str); 10] = [
+    ([0x04, 0x88, 0xad, 0xe4], "bitcoin"), // xprv
+    ([0x04, 0x9d, 0x78, 0x78], "bitcoin"), // yprv
+    ([0x04, 0xb2, 0x43, 0x0c], "bitcoin"), // zprv
+    ([0x02, 0x95, 0xb0, 0x05], "bitcoin"), // Yprv
+    ([0x02, 0xaa, 0x7a, 0x99], "bitcoin"), // Zprv
+    ([0x04, 0x35, 0x83, 0x94], "testnet"), // tprv
+    ([0x04, 0x4a, 0x4e, 0x28], "testnet"), // uprv
+    ([0x04, 0x5f, 0x18, 0xbc], "testnet"), // vprv
+    ([0x02, 0x42, 0x85, 0xb5], "testnet"), // Uprv
+    ([0x02, 0x57, 0x50, 0x48], "testnet"), // Vprv
+];
+
+/// Version bytes of the public keys of those in `XPRV_VERSIONS`, in its order
+const XPUB_VERSIONS: [[u8; 4]; 10] = [
+    [0x04, 0x88, 0xb2, 0x1e], // xpub
+    [0x04, 0x9d, 0x7c, 0xb2], // ypub
+    [0x04, 0xb2, 0x47, 0x46], // zpub
+    [0x02, 0x95, 0xb4, 0x3f], // Ypub
+    [0x02, 0xaa, 0x7e, 0xd3], // Zpub
+    [0x04, 0x35, 0x87, 0xcf], // tpub
+    [0x04, 0x4a, 0x52, 0x62], // upub
+    [0x04, 0x5f, 0x1c, 0xf6], // vpub
+    [0x02, 0x42, 0x89, 0xef], // Upub
+    [0x02, 0x57, 0x54, 0x83], // Vpub
+];
+
+/// Versions of the xprv and tprv, which the other variants are decoded as
+const XPRV: [u8; 4] = [0x04, 0x88, 0xad, 0xe4];
+const TPRV: [u8; 4] = [0x04, 0x35, 0x83, 0x94];
+
+/// Word counts of BIP-39 mnemonics
+const MNEMONIC_LENGTHS: [usize; 5] = [12, 15, 18, 21, 24];
+
+/// What a secret is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    /// A private key in wallet import format
+    Wif,
+    /// An extended private key
+    Xprv,
+    /// A nostr private key
+    Nsec,
+    /// A BIP-39 mnemonic, recognized by its shape
+    Mnemonic,
+    /// An LND macaroon, hex or base64
+    Macaroon,
+    /// A Nostr Wallet Connect URI, whose secret parameter authorizes payments
+    NwcUri,
+}
+
+impl Kind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Kind::Wif => "wif",
+            Kind::Xprv => "xprv",
+            Kind::Nsec => "nsec",
+            Kind::Mnemonic => "mnemonic",
+            Kind::Macaroon => "macaroon",
+            Kind::NwcUri => "nwc_uri",
+        }
+    }
+}
+
+/// A string holding secret material
+#[derive(Debug, Clone)]
+pub struct Secret {
+    pub kind: Kind,
+    /// Network of a key that tells it, as displayed by `Network`
+    pub network: Option<&'static str>,
+    value: String,
+}
+
+/// Try to read `s` as secret material
+pub fn detect(s: &str) -> Option<Secret> {
+    let secret = |kind, network| {
+        Some(Secret {
+            kind,
+            network,
+            value: s.to_string(),
+        })
+    };
+    if s.starts_with("nostr+walletconnect://") || s.starts_with("nostrwalletconnect://") {
+        return secret(Kind::NwcUri, None);
+    }
+    if is_mnemonic(s) {
+        return secret(Kind::Mnemonic, None);
+    }
+    if is_macaroon(s) {
+        return secret(Kind::Macaroon, None);
+    }
+    if let Ok((hrp, data, _)) = bech32::decode(s) {
+        let bytes = Vec::<u8>::from_base32(&data).unwrap_or_default();
+        if hrp == "nsec" && bytes.len() == 32 {
+            return secret(Kind::Nsec, None);
+        }
+    }
+    let (kind, network) = key_kind(s)?;
+
+    secret(kind, Some(network))
+}
+
+/// Kind and network of the base58 encoded private key `s`
+fn key_kind(s: &str) -> Option<(Kind, &'static str)> {
+    let bytes = base58::decode_check(s).ok()?;
+    match (bytes.len(), bytes.first()) {
+        // compressed keys carry a trailing 0x01
+        (33, Some(0x80)) | (34, Some(0x80)) => Some((Kind::Wif, "bitcoin")),
+        (33, Some(0xef)) | (34, Some(0xef)) => Some((Kind::Wif, "testnet")),
+        (78, _) => XPRV_VERSIONS
+            .iter()
+            .find(|(version, _)| bytes[..4] == *version)
+            .map(|(_, network)| (Kind::Xprv, *network)),
+        _ => None,
+    }
+}
+
+/// Whether `s` has the shape of a BIP-39 mnemonic. The words are not checked
+/// against a wordlist.
+fn is_mnemonic(s: &str) -> bool {
+    let words: Vec<&str> = s.split_whitespace().collect();
+
+    MNEMONIC_LENGTHS.contains(&words.len())
+        && words
+            .iter()
+            .all(|w| (3..=8).contains(&w.len()) && w.bytes().all(|c| c.is_ascii_lowercase()))
+}
+
+/// Whether `s` is an LND macaroon, which starts with the location "lnd"
+fn is_macaroon(s: &str) -> bool {
+    (s.starts_with("0201036c6e64") && s.bytes().all(|c| c.is_ascii_hexdigit()))
+        || s.starts_with("AgEDbG5k")
+}
+
+impl Secret {
+    /// The value with its secret part masked
+    pub fn masked(&self) -> String {
+        match self.kind {
+            Kind::Mnemonic => {
+                format!("<{} words>", self.value.split_whitespace().count())
+            }
+            // the wallet service and relays are not secret
+            Kind::NwcUri => match self.value.split_once('?') {
+                Some((base, query)) => {
+                    let params: Vec<String> = query
+                        .split('&')
+                        .map(|param| match param.split_once('=') {
+                            Some(("secret", _)) => "secret=…".to_string(),
+                            _ => param.to_string(),
+                        })
+                        .collect();
+                    format!("{base}?{}", params.join("&"))
+                }
+                None => self.value.clone(),
+            },
+            _ => mask(&self.value),
+        }
+    }
+
+    /// The extended public key of an xprv, in the variant of its version, e.g. a zpub
+    /// of a zprv, and the fingerprint of the key
+    pub fn xpub(&self) -> Option<(String, String)> {
+        if self.kind != Kind::Xprv {
+            return None;
+        }
+        let mut bytes = base58::decode_check(&self.value).ok()?;
+        let i = XPRV_VERSIONS
+            .iter()
+            .position(|(version, _)| bytes[..4] == *version)?;
+        let network = XPRV_VERSIONS[i].1;
+        // the variants differ from the xprv and tprv only in their version
+        bytes[..4].copy_from_slice(if network == "bitcoin" { &XPRV } else { &TPRV });
+        let xprv = ExtendedPrivKey::decode(&bytes).ok()?;
+        let secp = Secp256k1::new();
+        let xpub = ExtendedPubKey::from_priv(&secp, &xprv);
+        let mut encoded = xpub.encode();
+        encoded[..4].copy_from_slice(&XPUB_VERSIONS[i]);
+
+        Some((
+            base58::encode_check(&encoded),
+            xpub.fingerprint().to_string(),
+        ))
+    }
+
+    /// Construct a json map describing the secret, its value masked unless `show`.
+    /// An xprv is described by its xpub and fingerprint as well, which are not secret.
+    pub fn describe(&self, show: bool) -> Map<String, Value> {
+        let mut map = Map::new();
+        map.insert("secret".to_string(), json!(self.kind.as_str()));
+        if let Some(network) = self.network {
+            map.insert("network".to_string(), json!(network));
+        }
+        if let Some((xpub, fingerprint)) = self.xpub() {
+            map.insert("xpub".to_string(), json!(xpub));
+            map.insert("fingerprint".to_string(), json!(fingerprint));
+        }
+        let value = if show {
+            self.value.clone()
+        } else {
+            self.masked()
+        };
+        map.insert("secret_value".to_string(), json!(value));
+        map.insert("redacted".to_string(), json!(!show));
+
+        map
+    }
+}
+
+/// `s` with the private keys it contains masked, e.g. those of a descriptor.
+/// Returns `None` if there are none.
+pub fn redact_keys(s: &str) -> Option<String> {
+    let mut redacted = s.to_string();
+    let mut found = false;
+    for token in s.split(|c: char| !c.is_ascii_alphanumeric()) {
+        if key_kind(token).is_some() {
+            redacted = redacted.replace(token, &mask(token));
+            found = true;
+        }
+    }
+
+    found.then_some(redacted)
+}
+
+/// The first characters of `s`, which tell its type but not its value
+pub(crate) fn mask(s: &str) -> String {
+    let prefix: String = s.chars().take(4).collect();
+
+    format!("{prefix}…")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the master key of the first BIP-32 test vector
+    const XPRV: &str = "xprv9s21ZrQH143K3QTDL4LXw2F7HEK3wJUD2nW2nRk4stbPy6cq3jPPqjiChkVvvNKmPGJxWUtg6LnF5kejMRNNU3TGtRBeJgk33yuGBxrMPHi";
+    const XPUB: &str = "xpub661MyMwAqRbcFtXgS5sYJABqqG9YLmC4Q1Rdap9gSE8NqtwybGhePY2gZ29ESFjqJoCu1Rupje8YtGqsefD265TMg7usUDFdp6W1EGMcet8";
+    const WIF: &str = "5HueCGU8rMjxEXxiPuD5BDku4MkFqeZyd4dZ1jvhTVqvbTLvyTJ";
+    const NSEC: &str = "nsec1vl029mgpspedva04g90vltkh6fvh240zqtv9k0t9af8935ke9laqsnlfe5";
+    const MNEMONIC: &str = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+
+    #[test]
+    fn secrets_are_recognized() {
+        let kind = |s| detect(s).map(|secret| (secret.kind, secret.network));
+        assert_eq!(kind(WIF), Some((Kind::Wif, Some("bitcoin"))));
+        assert_eq!(kind(XPRV), Some((Kind::Xprv, Some("bitcoin"))));
+        assert_eq!(kind(NSEC), Some((Kind::Nsec, None)));
+        assert_eq!(kind(MNEMONIC), Some((Kind::Mnemonic, None)));
+        assert_eq!(kind("0201036c6e6402f801"), Some((Kind::Macaroon, None)));
+        assert_eq!(
+            kind("nostr+walletconnect://b889ff5b?relay=wss://relay.damus.io&secret=71a8c14c"),
+            Some((Kind::NwcUri, None))
+        );
+        // public keys are not secret
+        assert!(detect(XPUB).is_none());
+        assert!(detect("abandon abandon about").is_none());
+    }
+
+    #[test]
+    fn secrets_are_masked() {
+        assert_eq!(detect(WIF).unwrap().masked(), "5Hue…");
+        assert_eq!(detect(MNEMONIC).unwrap().masked(), "<12 words>");
+        let uri = "nostr+walletconnect://b889ff5b?relay=wss://relay.damus.io&secret=71a8c14c";
+        assert_eq!(
+            detect(uri).unwrap().masked(),
+            "nostr+walletconnect://b889ff5b?relay=wss://relay.damus.io&secret=…"
+        );
+
+        let described = detect(NSEC).unwrap().describe(false);
+        assert_eq!(described["secret_value"], "nsec…");
+        assert_eq!(described["redacted"], true);
+        let shown = detect(NSEC).unwrap().describe(true);
+        assert_eq!(shown["secret_value"], NSEC);
+        assert_eq!(shown["redacted"], false);
+    }
+
+    #[test]
+    fn xprvs_give_their_xpub_and_fingerprint() {
+        let (xpub, fingerprint) = detect(XPRV).unwrap().xpub().unwrap();
+        assert_eq!(xpub, XPUB);
+        assert_eq!(fingerprint, "3442193e");
+        assert!(detect(WIF).unwrap().xpub().is_none());
+    }
+
+    #[test]
+    fn keys_are_redacted_in_descriptors() {
+        let descriptor = format!("wpkh({XPRV}/0/*)");
+        assert_eq!(redact_keys(&descriptor).unwrap(), "wpkh(xprv…/0/*)");
+        assert!(redact_keys(&format!("wpkh({XPUB}/0/*)")).is_none());
+    }
+}
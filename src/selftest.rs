@@ -0,0 +1,130 @@
+//! A corpus of known strings, one of each kind and network, run through the whole
+//! command line to check that a build reads them as it should. The corpus lives in
+//! `selftest/vectors.json`, each vector giving the fields its output must have.
+
+use crate::{denomination, options, process, rates, Cli};
+use clap::Parser;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use waila_cli::Result;
+
+const VECTORS: &str = include_str!("../selftest/vectors.json");
+
+/// A known string and what parsing it gives
+#[derive(Debug, Deserialize)]
+struct Vector {
+    name: String,
+    /// Flags to parse the string with
+    #[serde(default)]
+    args: Vec<String>,
+    /// Cargo feature the vector needs, it is skipped without it
+    feature: Option<String>,
+    input: String,
+    /// Fields of the output, objects need only have the fields given
+    expect: Map<String, Value>,
+}
+
+/// Run every vector, printing whether it passed. Returns whether all did.
+pub fn run() -> Result<bool> {
+    let vectors: Vec<Vector> = serde_json::from_str(VECTORS)?;
+    let (mut passed, mut failed, mut skipped) = (0, 0, 0);
+    for vector in &vectors {
+        if let Some(feature) = vector.feature.as_deref().filter(|f| !enabled(f)) {
+            println!("skip {}: built without the {feature} feature", vector.name);
+            skipped += 1;
+            continue;
+        }
+        match check(vector) {
+            Ok(()) => {
+                println!("ok   {}", vector.name);
+                passed += 1;
+            }
+            Err(reason) => {
+                println!("FAIL {}: {reason}", vector.name);
+                failed += 1;
+            }
+        }
+    }
+    println!("\n{passed} passed, {failed} failed, {skipped} skipped");
+
+    Ok(failed == 0)
+}
+
+/// Whether `vector` gives the fields it expects, or why not
+fn check(vector: &Vector) -> core::result::Result<(), String> {
+    let argv = ["waila-cli".to_string()]
+        .into_iter()
+        .chain(vector.args.iter().cloned())
+        .chain([vector.input.clone()]);
+    let args = Cli::try_parse_from(argv).map_err(|e| e.to_string())?.args;
+    let rates = rates(&args).map_err(|e| e.to_string())?;
+    let opts = options(&args, denomination(&args.unit), rates);
+    let out = process(&vector.input, &args, &opts)
+        .map_err(|e| e.to_string())?
+        .out
+        .unwrap_or_default();
+    let output: Value =
+        serde_json::from_slice(&out).map_err(|e| format!("output is not JSON: {e}"))?;
+
+    for (key, expected) in &vector.expect {
+        match output.get(key) {
+            Some(value) if contains(value, expected) => {}
+            Some(value) => return Err(format!("{key} is {value}, expected {expected}")),
+            None => return Err(format!("{key} is missing, expected {expected}")),
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether `value` is `expected`, or has its fields if both are objects
+fn contains(value: &Value, expected: &Value) -> bool {
+    match (value, expected) {
+        (Value::Object(value), Value::Object(expected)) => {
+            expected.iter().all(|(key, expected)| {
+                value
+                    .get(key)
+                    .is_some_and(|value| contains(value, expected))
+            })
+        }
+        _ => value == expected,
+    }
+}
+
+/// Whether the build has the cargo `feature`
+fn enabled(feature: &str) -> bool {
+    match feature {
+        "nostr" => cfg!(feature = "nostr"),
+        "network" => cfg!(feature = "network"),
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn the_corpus_reads_as_expected() {
+        let vectors: Vec<Vector> = serde_json::from_str(VECTORS).unwrap();
+        let failures: Vec<String> = vectors
+            .iter()
+            .filter(|vector| vector.feature.as_deref().filter(|f| !enabled(f)).is_none())
+            .filter_map(|vector| {
+                check(vector)
+                    .err()
+                    .map(|reason| format!("{}: {reason}", vector.name))
+            })
+            .collect();
+        assert!(failures.is_empty(), "{failures:#?}");
+    }
+
+    #[test]
+    fn objects_need_only_the_fields_expected() {
+        let value = json!({"kind": "OnChain", "amount": {"sat": 1, "btc": "0.00000001"}});
+        assert!(contains(&value, &json!({"amount": {"sat": 1}})));
+        assert!(!contains(&value, &json!({"amount": {"sat": 2}})));
+        assert!(!contains(&value["kind"], &json!("Bolt11")));
+    }
+}
@@ -0,0 +1,323 @@
+//! A small HTTP server answering `POST /parse` and `POST /resolve` with the same
+//! JSON as the command line. The body is the string to parse, as is or as
+//! `{"query": "..."}`.
+
+use serde_json::{json, Value};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use waila_cli::{Options, Result};
+
+/// Largest request body accepted
+const MAX_BODY: usize = 64 * 1024;
+
+/// Largest request line and headers accepted
+const MAX_HEADER: u64 = 8 * 1024;
+
+/// How long a client may take to send its whole request
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Connections answered at once, more are refused with 503
+const MAX_CONNECTIONS: usize = 64;
+
+/// Answer requests on `listen` until the process is stopped, one thread per
+/// connection. With a `token`, requests must carry `Authorization: Bearer <token>`.
+pub fn serve(listen: SocketAddr, token: Option<String>, opts: Options) -> Result<()> {
+    let listener = TcpListener::bind(listen)?;
+    eprintln!("listening on http://{listen}");
+    let connections = Arc::new(AtomicUsize::new(0));
+    for stream in listener.incoming() {
+        let Ok(stream) = stream else {
+            continue;
+        };
+        if connections.fetch_add(1, Ordering::SeqCst) >= MAX_CONNECTIONS {
+            connections.fetch_sub(1, Ordering::SeqCst);
+            let _ = reply(stream, 503, &json!({ "error": "too many connections" }));
+            continue;
+        }
+        let (token, opts) = (token.clone(), opts.clone());
+        let connection = Connection(connections.clone());
+        std::thread::spawn(move || {
+            let _connection = connection;
+            // a client that went away needs no answer
+            let _ = handle(stream, token.as_deref(), &opts);
+        });
+    }
+
+    Ok(())
+}
+
+/// An open connection, counted until it is dropped
+struct Connection(Arc<AtomicUsize>);
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// A parsed request
+struct Request {
+    method: String,
+    path: String,
+    authorization: Option<String>,
+    body: String,
+}
+
+fn handle(stream: TcpStream, token: Option<&str>, opts: &Options) -> Result<()> {
+    let (status, body) = match read_request(&stream, Instant::now() + REQUEST_TIMEOUT) {
+        Ok(req) => respond(&req, token, opts),
+        Err((status, error)) => (status, json!({ "error": error })),
+    };
+
+    reply(stream, status, &body)
+}
+
+/// Write the answer with `status` and json `body`, closing the connection
+fn reply(mut stream: TcpStream, status: u16, body: &Value) -> Result<()> {
+    stream.set_write_timeout(Some(REQUEST_TIMEOUT))?;
+    let body = body.to_string();
+    let reason = match status {
+        200 => "OK",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        408 => "Request Timeout",
+        413 => "Payload Too Large",
+        422 => "Unprocessable Entity",
+        431 => "Request Header Fields Too Large",
+        503 => "Service Unavailable",
+        _ => "Internal Server Error",
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {status} {reason}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )?;
+    stream.flush()?;
+
+    Ok(())
+}
+
+/// Reads from a stream that fail once `deadline` has passed, however the
+/// client spreads its bytes over time
+struct Deadline<'a> {
+    stream: &'a TcpStream,
+    deadline: Instant,
+}
+
+impl Read for Deadline<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let left = self.deadline.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return Err(io::ErrorKind::TimedOut.into());
+        }
+        self.stream.set_read_timeout(Some(left))?;
+        self.stream.read(buf)
+    }
+}
+
+/// Read a request sent before `deadline`, or the status and error to answer with
+fn read_request(
+    stream: &TcpStream,
+    deadline: Instant,
+) -> core::result::Result<Request, (u16, &'static str)> {
+    const MALFORMED: (u16, &str) = (400, "malformed request");
+    const TOO_LARGE: (u16, &str) = (431, "request headers too large");
+    let failed = |e: io::Error| match e.kind() {
+        io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock => (408, "request timed out"),
+        _ => MALFORMED,
+    };
+    let mut reader = BufReader::new(Deadline { stream, deadline }.take(MAX_HEADER));
+    // a line cut off by the header limit has no line ending
+    let mut next_line = || {
+        let mut line = String::new();
+        reader.read_line(&mut line).map_err(failed)?;
+        match line.strip_suffix('\n') {
+            Some(line) => Ok(line.trim_end_matches('\r').to_string()),
+            None if reader.get_ref().limit() == 0 => Err(TOO_LARGE),
+            None => Err(MALFORMED),
+        }
+    };
+
+    let line = next_line()?;
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(path)) = (parts.next(), parts.next()) else {
+        return Err(MALFORMED);
+    };
+    let (method, path) = (method.to_string(), path.to_string());
+
+    let mut length = 0;
+    let mut authorization = None;
+    loop {
+        let header = next_line()?;
+        if header.is_empty() {
+            break;
+        }
+        let (name, value) = header.split_once(':').ok_or(MALFORMED)?;
+        match name.trim().to_ascii_lowercase().as_str() {
+            "content-length" => length = value.trim().parse().map_err(|_| MALFORMED)?,
+            "authorization" => authorization = Some(value.trim().to_string()),
+            _ => {}
+        }
+    }
+    // a larger body is refused by its length, before any of it is read
+    if length > MAX_BODY {
+        return Err((413, "request too large"));
+    }
+    reader.get_mut().set_limit(length as u64);
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).map_err(failed)?;
+
+    Ok(Request {
+        method,
+        path,
+        authorization,
+        body: String::from_utf8(body).map_err(|_| (400, "the body is not UTF-8"))?,
+    })
+}
+
+/// Status and json body of the answer to `req`
+fn respond(req: &Request, token: Option<&str>, opts: &Options) -> (u16, Value) {
+    if let Some(token) = token {
+        let expected = format!("Bearer {token}");
+        if req.authorization.as_deref() != Some(expected.as_str()) {
+            return (401, json!({ "error": "missing or wrong bearer token" }));
+        }
+    }
+    let resolve = match req.path.as_str() {
+        "/parse" => false,
+        "/resolve" => true,
+        _ => return (404, json!({ "error": "not found, use /parse or /resolve" })),
+    };
+    if req.method != "POST" {
+        return (405, json!({ "error": "use POST" }));
+    }
+    let query = match serde_json::from_str::<Value>(&req.body) {
+        Ok(Value::Object(obj)) => match obj.get("query").and_then(Value::as_str) {
+            Some(query) => query.to_string(),
+            None => return (400, json!({ "error": "expected a \"query\" string" })),
+        },
+        _ => req.body.trim().to_string(),
+    };
+    // the query is the whole body, which is already limited
+    let opts = Options {
+        resolve,
+        max_input: MAX_BODY,
+        ..opts.clone()
+    };
+    // the query is not echoed, it may hold secrets
+    match waila_cli::analyze(&query, &opts) {
+        Ok(analysis) => (200, Value::Object(analysis.into_fields())),
+        Err(e) if e.is_input_error() => (422, json!({ "error": e.to_string() })),
+        Err(e) => (500, json!({ "error": e.to_string() })),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ADDRESS: &str = "tb1pwzv7fv35yl7ypwj8w7al2t8apd6yf4568cs772qjwper74xqc99sk8x7tk";
+
+    fn request(method: &str, path: &str, authorization: Option<&str>, body: &str) -> Request {
+        Request {
+            method: method.to_string(),
+            path: path.to_string(),
+            authorization: authorization.map(str::to_string),
+            body: body.to_string(),
+        }
+    }
+
+    fn status(req: &Request, token: Option<&str>) -> u16 {
+        respond(req, token, &Options::default()).0
+    }
+
+    /// Send `sent` to a fresh connection and read the request off its other end
+    fn read_sent(sent: &[u8], timeout: Duration) -> core::result::Result<Request, u16> {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (server, _) = listener.accept().unwrap();
+        client.write_all(sent).unwrap();
+        read_request(&server, Instant::now() + timeout).map_err(|(status, _)| status)
+    }
+
+    #[test]
+    fn requests_need_the_token() {
+        let token = Some("hunter2");
+        let req = |authorization| request("POST", "/parse", authorization, ADDRESS);
+        assert_eq!(status(&req(Some("Bearer hunter2")), token), 200);
+        assert_eq!(status(&req(Some("Bearer hunter3")), token), 401);
+        assert_eq!(status(&req(Some("hunter2")), token), 401);
+        assert_eq!(status(&req(None), token), 401);
+        assert_eq!(status(&req(None), None), 200);
+    }
+
+    #[test]
+    fn requests_are_routed() {
+        assert_eq!(status(&request("POST", "/parse", None, ADDRESS), None), 200);
+        assert_eq!(status(&request("GET", "/parse", None, ""), None), 405);
+        assert_eq!(status(&request("POST", "/", None, ADDRESS), None), 404);
+        assert_eq!(status(&request("POST", "/parse", None, "{}"), None), 400);
+        assert_eq!(
+            status(&request("POST", "/parse", None, "nonsense"), None),
+            422
+        );
+
+        let body = json!({ "query": ADDRESS }).to_string();
+        let (status, answer) = respond(
+            &request("POST", "/parse", None, &body),
+            None,
+            &Options::default(),
+        );
+        assert_eq!(status, 200);
+        assert_eq!(answer["address"], ADDRESS);
+        assert_eq!(answer["network"], "testnet");
+    }
+
+    #[test]
+    fn requests_are_read() {
+        let sent = format!(
+            "POST /parse HTTP/1.1\r\nAuthorization: Bearer hunter2\r\nContent-Length: {}\r\n\r\n{ADDRESS}",
+            ADDRESS.len()
+        );
+        let req = read_sent(sent.as_bytes(), REQUEST_TIMEOUT).ok().unwrap();
+        assert_eq!((req.method.as_str(), req.path.as_str()), ("POST", "/parse"));
+        assert_eq!(req.authorization.as_deref(), Some("Bearer hunter2"));
+        assert_eq!(req.body, ADDRESS);
+    }
+
+    #[test]
+    fn requests_are_limited() {
+        let headers = format!(
+            "POST /parse HTTP/1.1\r\nX-Padding: {}\r\n\r\n",
+            "a".repeat(9000)
+        );
+        assert_eq!(
+            read_sent(headers.as_bytes(), REQUEST_TIMEOUT).err(),
+            Some(431)
+        );
+
+        // refused by its length, the body is never sent
+        let large = format!(
+            "POST /parse HTTP/1.1\r\nContent-Length: {}\r\n\r\n",
+            MAX_BODY + 1
+        );
+        assert_eq!(
+            read_sent(large.as_bytes(), REQUEST_TIMEOUT).err(),
+            Some(413)
+        );
+
+        let binary = b"POST /parse HTTP/1.1\r\nContent-Length: 2\r\n\r\n\xff\xfe";
+        assert_eq!(read_sent(binary, REQUEST_TIMEOUT).err(), Some(400));
+
+        // the rest of the body never comes
+        let start = Instant::now();
+        let slow = b"POST /parse HTTP/1.1\r\nContent-Length: 10\r\n\r\nabc";
+        assert_eq!(read_sent(slow, Duration::from_millis(100)).err(), Some(408));
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+}
@@ -0,0 +1,154 @@
+//! Did-you-mean hints for strings that almost parse: a wrong human readable
+//! part, a missing prefix, a truncated address or lookalike characters
+
+use bitcoin_waila::PaymentParams;
+use serde::Serialize;
+use std::fmt;
+use std::str::FromStr;
+
+/// Human readable parts of addresses, invoices, LNURLs and nostr entities
+const HRPS: [&str; 13] = [
+    "bc", "tb", "bcrt", "lnbc", "lntb", "lntbs", "lnbcrt", "lnurl", "npub", "nsec", "note",
+    "nprofile", "nevent",
+];
+
+/// Prefixes that are easily left out when copying
+const PREFIXES: [&str; 4] = ["ln", "bc1", "tb1", "bcrt1"];
+
+/// A hint for a string that failed to parse
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Suggestion {
+    /// The corrected string, if one parses
+    pub value: Option<String>,
+    pub reason: String,
+}
+
+impl fmt::Display for Suggestion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.value {
+            Some(value) => write!(f, "did you mean {value}? ({})", self.reason),
+            None => write!(f, "{}", self.reason),
+        }
+    }
+}
+
+impl Suggestion {
+    fn new(value: Option<String>, reason: impl Into<String>) -> Self {
+        Self {
+            value,
+            reason: reason.into(),
+        }
+    }
+}
+
+/// Hints for `s`, which failed to parse, the most specific first
+pub fn suggest(s: &str) -> Vec<Suggestion> {
+    let mut suggestions = vec![];
+    suggestions.extend(lookalikes(s));
+    suggestions.extend(hrp(s));
+    suggestions.extend(prefix(s));
+    suggestions.extend(truncated(s));
+    suggestions.dedup_by(|a, b| a.value.is_some() && a.value == b.value);
+
+    suggestions
+}
+
+fn parses(s: &str) -> bool {
+    PaymentParams::from_str(s).is_ok()
+}
+
+/// Characters outside of the bech32 or base58 alphabet replaced by the ones they resemble
+fn lookalikes(s: &str) -> Option<Suggestion> {
+    // bech32 has no b, i and o, base58 no 0, O, I and l
+    let bech32 = s.to_ascii_lowercase().rfind('1').map(|sep| {
+        let (hrp, data) = s.split_at(sep + 1);
+        let data: String = data
+            .chars()
+            .map(|c| match c {
+                'o' | 'O' => '0',
+                'i' | 'I' => 'l',
+                'b' | 'B' => '6',
+                c => c,
+            })
+            .collect();
+        format!("{hrp}{data}")
+    });
+    let base58: String = s
+        .chars()
+        .map(|c| match c {
+            '0' | 'O' => 'o',
+            'I' | 'l' => '1',
+            c => c,
+        })
+        .collect();
+
+    bech32
+        .into_iter()
+        .chain([base58])
+        .find(|candidate| candidate != s && parses(candidate))
+        .map(|candidate| Suggestion::new(Some(candidate), "lookalike characters replaced"))
+}
+
+/// The data of a bech32 string with another human readable part
+fn hrp(s: &str) -> Option<Suggestion> {
+    let lower = s.to_ascii_lowercase();
+    let (given, data) = lower.rsplit_once('1')?;
+    if given.is_empty() || data.len() < 6 {
+        return None;
+    }
+
+    HRPS.iter()
+        .filter(|hrp| **hrp != given)
+        .map(|hrp| format!("{hrp}1{data}"))
+        .find(|candidate| parses(candidate))
+        .map(|candidate| {
+            Suggestion::new(
+                Some(candidate),
+                format!("the checksum is valid with another prefix than {given}"),
+            )
+        })
+}
+
+/// The string with a prefix that was likely left out
+fn prefix(s: &str) -> Option<Suggestion> {
+    PREFIXES
+        .iter()
+        .map(|prefix| format!("{prefix}{s}"))
+        .find(|candidate| parses(candidate))
+        .map(|candidate| Suggestion::new(Some(candidate), "a prefix is missing"))
+}
+
+/// A hint if `s` is shorter than any address of its type
+fn truncated(s: &str) -> Option<Suggestion> {
+    let lower = s.to_ascii_lowercase();
+    // witness version 0 programs are 20 or 32 bytes, later ones 32 bytes
+    let segwit = lower
+        .rsplit_once('1')
+        .and_then(|(hrp, data)| match (hrp, data.chars().next()) {
+            ("bc" | "tb" | "bcrt", Some('q')) => Some(hrp.len() + 40),
+            ("bc" | "tb" | "bcrt", Some(_)) => Some(hrp.len() + 60),
+            _ => None,
+        });
+    let expected = match segwit {
+        Some(len) => len,
+        // base58 addresses are 25 bytes, at least 26 characters
+        None if s.len() >= 20
+            && s.starts_with(['1', '3', 'm', 'n', '2'])
+            && s.chars().all(|c| c.is_ascii_alphanumeric()) =>
+        {
+            26
+        }
+        None => return None,
+    };
+    if s.len() >= expected {
+        return None;
+    }
+
+    Some(Suggestion::new(
+        None,
+        format!(
+            "the address looks truncated, {} characters or more are missing",
+            expected - s.len()
+        ),
+    ))
+}
@@ -0,0 +1,95 @@
+//! Transactions and transaction ids, which are outside of what waila parses
+
+use bitcoin::consensus::deserialize;
+use bitcoin::hashes::hex::FromHex;
+use bitcoin::{Transaction, TxOut, Txid};
+use serde_json::{json, Map, Value};
+use std::str::FromStr;
+
+/// A transaction id or a consensus encoded transaction
+#[derive(Debug, Clone)]
+pub enum TxInput {
+    Txid(Txid),
+    Raw(Transaction),
+}
+
+/// Try to read `s` as a txid or a hex encoded raw transaction
+pub fn detect(s: &str) -> Option<TxInput> {
+    if s.len() == 64 {
+        if let Ok(txid) = Txid::from_str(s) {
+            return Some(TxInput::Txid(txid));
+        }
+    }
+    let bytes = Vec::<u8>::from_hex(s).ok()?;
+    let tx = deserialize::<Transaction>(&bytes).ok()?;
+
+    Some(TxInput::Raw(tx))
+}
+
+/// The value in satoshis of `outputs`, `None` if it overflows, which a decoded
+/// transaction may well do
+pub fn total_value<'a>(outputs: impl IntoIterator<Item = &'a TxOut>) -> Option<u64> {
+    outputs
+        .into_iter()
+        .try_fold(0u64, |total, out| total.checked_add(out.value))
+}
+
+impl TxInput {
+    /// Kind of the input as reported in the output
+    pub fn kind(&self) -> &'static str {
+        match self {
+            TxInput::Txid(_) => "Txid",
+            TxInput::Raw(_) => "Transaction",
+        }
+    }
+
+    pub fn txid(&self) -> Txid {
+        match self {
+            TxInput::Txid(txid) => *txid,
+            TxInput::Raw(tx) => tx.txid(),
+        }
+    }
+
+    /// Construct a json map describing the transaction, amounts in satoshis
+    pub fn describe(&self) -> Map<String, Value> {
+        let mut map = Map::new();
+        map.insert("txid".to_string(), Value::String(self.txid().to_string()));
+        if let TxInput::Raw(tx) = self {
+            map.insert("version".to_string(), json!(tx.version));
+            map.insert(
+                "locktime".to_string(),
+                json!(tx.lock_time.to_consensus_u32()),
+            );
+            map.insert("size".to_string(), json!(tx.size()));
+            map.insert("vsize".to_string(), json!(tx.vsize()));
+            map.insert("weight".to_string(), json!(tx.weight().to_wu()));
+            map.insert("inputs".to_string(), json!(tx.input.len()));
+            map.insert("outputs".to_string(), json!(tx.output.len()));
+            // null if the outputs are worth more than a u64 counts
+            map.insert("output_value".to_string(), json!(total_value(&tx.output)));
+        }
+
+        map
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::ScriptBuf;
+
+    fn output(value: u64) -> TxOut {
+        TxOut {
+            value,
+            script_pubkey: ScriptBuf::new(),
+        }
+    }
+
+    #[test]
+    fn output_values_are_summed_without_overflow() {
+        assert_eq!(total_value(&[output(1), output(2)]), Some(3));
+        assert_eq!(total_value(&[output(u64::MAX), output(0)]), Some(u64::MAX));
+        assert_eq!(total_value(&[output(u64::MAX), output(1)]), None);
+        assert_eq!(total_value(&[]), Some(0));
+    }
+}
@@ -0,0 +1,35 @@
+//! Query parameters of BIP-21 URIs
+
+use url::form_urlencoded;
+
+/// `req-` parameters understood by waila, none so far
+const KNOWN_REQUIRED: [&str; 0] = [];
+
+/// The decoded query parameters of `s` if it is a `bitcoin:` URI
+pub fn params(s: &str) -> Option<Vec<(String, String)>> {
+    let scheme = s.get(..8)?;
+    if !scheme.eq_ignore_ascii_case("bitcoin:") {
+        return None;
+    }
+    let query = s.split_once('?').map(|(_, q)| q).unwrap_or_default();
+
+    Some(
+        form_urlencoded::parse(query.as_bytes())
+            .map(|(key, value)| (key.into_owned(), value.into_owned()))
+            .collect(),
+    )
+}
+
+/// Required parameters of the URI `s` that waila doesn't understand. BIP-21 says
+/// a URI with any of them must not be acted on.
+pub fn unknown_required(s: &str) -> Vec<String> {
+    params(s)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(key, _)| key)
+        .filter(|key| {
+            key.to_ascii_lowercase().starts_with("req-")
+                && !KNOWN_REQUIRED.contains(&key.to_ascii_lowercase().as_str())
+        })
+        .collect()
+}
@@ -0,0 +1,211 @@
+//! Non-fatal issues found while parsing: deprecated or nonstandard constructs,
+//! dust, expiry and conflicting networks
+
+use bitcoin::blockdata::opcodes::all::{OP_CHECKMULTISIG, OP_PUSHNUM_1, OP_PUSHNUM_16};
+use bitcoin::blockdata::script::Instruction;
+use bitcoin::{Address, Network, Script, Transaction};
+use serde::Serialize;
+use std::str::FromStr;
+
+/// Largest standard OP_RETURN output script: OP_RETURN and a push of 80 bytes
+const MAX_OP_RETURN_SIZE: usize = 83;
+
+/// Smallest output value in satoshis relayed by default, that of P2PKH
+const DUST_LIMIT: u64 = 546;
+
+/// How much a warning should weigh in a decision to pay
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// Worth knowing, no reason to refuse
+    Info,
+    /// Likely a mistake or a risk
+    Warning,
+    /// Paying would fail or lose funds
+    Critical,
+}
+
+/// A non-fatal issue about the output `field`
+#[derive(Debug, Clone, Serialize)]
+pub struct Warning {
+    /// Stable identifier of the issue
+    pub code: &'static str,
+    pub severity: Severity,
+    /// The output field concerned
+    pub field: &'static str,
+    pub message: String,
+}
+
+impl Warning {
+    fn new(code: &'static str, severity: Severity, field: &'static str, message: String) -> Self {
+        Self {
+            code,
+            severity,
+            field,
+            message,
+        }
+    }
+}
+
+/// Warnings about the address `s`
+pub fn for_address(s: &str) -> Vec<Warning> {
+    let Ok(address) = Address::from_str(s) else {
+        return vec![];
+    };
+
+    for_script(
+        &address.assume_checked().script_pubkey(),
+        "address",
+        "the address",
+    )
+}
+
+/// A warning if paying `sat` on chain creates an output below the dust limit
+pub fn dust(sat: u64) -> Option<Warning> {
+    (sat < DUST_LIMIT).then(|| {
+        Warning::new(
+            "dust_amount",
+            Severity::Warning,
+            "amount",
+            format!("{sat} sat is below the dust limit of {DUST_LIMIT} sat, the payment won't relay on chain"),
+        )
+    })
+}
+
+/// A warning if the invoice or offer expired
+pub fn expired(expired: bool) -> Option<Warning> {
+    expired.then(|| {
+        Warning::new(
+            "expired",
+            Severity::Critical,
+            "expires_at",
+            "the invoice or offer has expired".to_string(),
+        )
+    })
+}
+
+/// A warning if the description given doesn't hash to the description hash of the invoice
+pub fn description_mismatch(mismatch: bool) -> Option<Warning> {
+    mismatch.then(|| {
+        Warning::new(
+            "description_mismatch",
+            Severity::Critical,
+            "description_hash",
+            "the description does not match the description hash of the invoice".to_string(),
+        )
+    })
+}
+
+/// A warning that the query is an extended private key, which spends from every
+/// address derived from it
+pub fn xprv() -> Warning {
+    Warning::new(
+        "secret_xprv",
+        Severity::Critical,
+        "secret",
+        "this is an extended private key: anyone who sees it can spend from all its addresses, don't share it".to_string(),
+    )
+}
+
+/// A warning if the `address` of a unified URI is not for the network of its
+/// `invoice`, as displayed by `Network`
+pub fn network_mismatch(address: &str, invoice_network: &str) -> Option<Warning> {
+    let address = Address::from_str(address).ok()?;
+    let network = Network::from_str(invoice_network).ok()?;
+    (!address.is_valid_for_network(network)).then(|| {
+        Warning::new(
+            "network_mismatch",
+            Severity::Critical,
+            "invoice",
+            format!("the invoice is for {network}, the address is not"),
+        )
+    })
+}
+
+/// Warnings about the inputs and outputs of `tx`
+pub fn for_tx(tx: &Transaction) -> Vec<Warning> {
+    let mut warnings = vec![];
+    for (i, input) in tx.input.iter().enumerate() {
+        // a P2PKH spend pushes the signature and the public key
+        let pushes: Vec<_> = input
+            .script_sig
+            .instructions()
+            .collect::<Result<_, _>>()
+            .unwrap_or_default();
+        if let [Instruction::PushBytes(_), Instruction::PushBytes(key)] = pushes.as_slice() {
+            if key.len() == 65 && key.as_bytes()[0] == 0x04 {
+                warnings.push(Warning::new(
+                    "uncompressed_key",
+                    Severity::Warning,
+                    "inputs",
+                    format!("input {i} spends with an uncompressed public key"),
+                ));
+            }
+        }
+    }
+    for (i, output) in tx.output.iter().enumerate() {
+        warnings.extend(for_script(
+            &output.script_pubkey,
+            "outputs",
+            &format!("output {i}"),
+        ));
+    }
+
+    warnings
+}
+
+/// Warnings about the output script `script` of `what`, shown in `field`
+fn for_script(script: &Script, field: &'static str, what: &str) -> Vec<Warning> {
+    let mut warnings = vec![];
+    if let Some(version) = script.witness_version() {
+        if version.to_num() > 1 {
+            warnings.push(Warning::new(
+                "future_witness_version",
+                Severity::Critical,
+                field,
+                format!(
+                    "{what} uses witness version {}, which has no consensus meaning yet and can be spent by anyone",
+                    version.to_num()
+                ),
+            ));
+        }
+    }
+    if is_bare_multisig(script) {
+        warnings.push(Warning::new(
+            "bare_multisig",
+            Severity::Info,
+            field,
+            format!(
+                "{what} is a bare multisig script, which is nonstandard to relay in most cases"
+            ),
+        ));
+    }
+    if script.is_op_return() && script.len() > MAX_OP_RETURN_SIZE {
+        warnings.push(Warning::new(
+            "large_op_return",
+            Severity::Info,
+            field,
+            format!(
+                "{what} is an OP_RETURN of {} bytes, larger than the standard {MAX_OP_RETURN_SIZE}",
+                script.len()
+            ),
+        ));
+    }
+
+    warnings
+}
+
+/// Whether `script` is `m <keys> n OP_CHECKMULTISIG`
+fn is_bare_multisig(script: &Script) -> bool {
+    let bytes = script.as_bytes();
+    let is_pushnum = |b: u8| (OP_PUSHNUM_1.to_u8()..=OP_PUSHNUM_16.to_u8()).contains(&b);
+    match (
+        bytes.first(),
+        bytes.len().checked_sub(2).and_then(|i| bytes.get(i)),
+    ) {
+        (Some(m), Some(n)) => {
+            is_pushnum(*m) && is_pushnum(*n) && bytes.last() == Some(&OP_CHECKMULTISIG.to_u8())
+        }
+        _ => false,
+    }
+}
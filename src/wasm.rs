@@ -0,0 +1,30 @@
+//! Bindings for JavaScript, built for `wasm32-unknown-unknown` with wasm-bindgen.
+//! Nothing goes online: the same detection as the command line runs in the browser.
+
+use crate::{analyze, Options};
+use serde_json::Value;
+use wasm_bindgen::prelude::*;
+
+/// Parse `query` into the json the command line prints, with every field if `all`
+#[wasm_bindgen]
+pub fn parse(query: &str, all: bool) -> Result<String, JsError> {
+    let opts = Options {
+        all,
+        ..Options::default()
+    };
+    let analysis = analyze(query, &opts).map_err(|e| JsError::new(&e.to_string()))?;
+
+    Ok(Value::Object(analysis.into_fields()).to_string())
+}
+
+/// The kind of `query`, e.g. `OnChain`
+#[wasm_bindgen]
+pub fn kind(query: &str) -> Result<String, JsError> {
+    let opts = Options {
+        kind_only: true,
+        ..Options::default()
+    };
+    let analysis = analyze(query, &opts).map_err(|e| JsError::new(&e.to_string()))?;
+
+    Ok(analysis.kind.to_string())
+}